@@ -1,3 +1,6 @@
+use crate::debug;
+use crate::emulator::blip::BlipBuffer;
+
 const DUTY_TABLE: [[u8; 8]; 4] = [
     [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
     [1, 0, 0, 0, 0, 0, 0, 1], // 25%
@@ -25,8 +28,57 @@ const NR42_READ_MASK: u8 = 0x00;
 const NR43_READ_MASK: u8 = 0x00;
 const NR44_READ_MASK: u8 = 0xBF;
 
-const CPU_CLOCK: u32 = 4_194_304;
-const FRAME_SEQUENCER_RATE: u16 = 8192; // CPU clocks per frame sequencer tick
+pub const CPU_CLOCK: u32 = 4_194_304;
+const HPF_CHARGE_PER_CLOCK: f64 = 0.999958; // DMG high-pass capacitor
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+pub const FRAME_SEQUENCER_DIV_MASK: u16 = 0x1000; // DIV bit 4, 512 Hz
+
+pub const SCOPE_LENGTH: usize = 512;
+const SCOPE_DECIMATION: u32 = 32; // M-cycles per scope point, ~15 ms shown
+
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+// Channel DAC: digital 0..15 maps linearly to analog +1..-1. A disabled DAC
+// outputs 0, an enabled one fed by a silent channel sits at +1 (DC offset
+// removed later by the high-pass filter).
+fn dac_output(dac_enabled: bool, digital: u8) -> f32 {
+    if !dac_enabled {
+        return 0.0;
+    }
+    1.0 - digital as f32 / 7.5
+}
+
+// DMG "zombie mode": writing NRx2 while the channel plays changes the
+// current volume instead of leaving it to the envelope
+fn zombie_volume(volume: u8, old_nr2: u8, new_nr2: u8, envelope_running: bool) -> u8 {
+    let mut volume = volume;
+    if old_nr2 & 0x07 == 0 && envelope_running {
+        volume += 1;
+    } else if old_nr2 & 0x08 == 0 {
+        volume += 2;
+    }
+    if (old_nr2 ^ new_nr2) & 0x08 != 0 {
+        volume = 16 - volume;
+    }
+    volume & 0x0F
+}
+
+// Enabling the length counter in the half of the frame sequencer period that
+// doesn't clock it clocks it once more. Returns true if that expired it.
+fn extra_length_clock(
+    length_counter: &mut u16,
+    was_enabled: bool,
+    now_enabled: bool,
+    first_half: bool,
+) -> bool {
+    if first_half && !was_enabled && now_enabled && *length_counter > 0 {
+        *length_counter -= 1;
+        return *length_counter == 0;
+    }
+    false
+}
 
 #[derive(Debug, Clone)]
 pub struct SquareChannel {
@@ -40,6 +92,7 @@ pub struct SquareChannel {
     sweep_timer: u8,
     sweep_shadow_freq: u16,
     sweep_enabled: bool,
+    sweep_negate_used: bool, // subtract calculation since the last trigger
 
     // Duty
     duty: u8,
@@ -54,6 +107,7 @@ pub struct SquareChannel {
     envelope_direction: bool, // false = down, true = up
     envelope_pace: u8,
     envelope_timer: u8,
+    envelope_running: bool,
     current_volume: u8,
 
     // Frequency
@@ -79,6 +133,7 @@ impl SquareChannel {
             sweep_timer: 0,
             sweep_shadow_freq: 0,
             sweep_enabled: false,
+            sweep_negate_used: false,
             duty: 0,
             duty_position: 0,
             length_counter: 0,
@@ -87,6 +142,7 @@ impl SquareChannel {
             envelope_direction: false,
             envelope_pace: 0,
             envelope_timer: 0,
+            envelope_running: false,
             current_volume: 0,
             frequency: 0,
             frequency_timer: 0,
@@ -98,15 +154,22 @@ impl SquareChannel {
         }
     }
 
-    pub fn write_register(&mut self, reg: u8, value: u8) {
+    // `length_first_half`: the next frame sequencer step doesn't clock length
+    pub fn write_register(&mut self, reg: u8, value: u8, length_first_half: bool) {
         match reg {
             0 => {
                 // NRx0 (sweep, channel 1 only)
                 self.nr0 = value;
                 if self.has_sweep {
                     self.sweep_pace = (value >> 4) & 0x07;
-                    self.sweep_direction = (value & 0x08) != 0;
                     self.sweep_step = value & 0x07;
+
+                    // Leaving negate mode after it was used kills the channel
+                    let direction = (value & 0x08) != 0;
+                    if self.sweep_direction && !direction && self.sweep_negate_used {
+                        self.enabled = false;
+                    }
+                    self.sweep_direction = direction;
                 }
             }
             1 => {
@@ -117,6 +180,10 @@ impl SquareChannel {
             }
             2 => {
                 // NRx2 (volume envelope)
+                if self.enabled {
+                    self.current_volume =
+                        zombie_volume(self.current_volume, self.nr2, value, self.envelope_running);
+                }
                 self.nr2 = value;
                 self.initial_volume = (value >> 4) & 0x0F;
                 self.envelope_direction = (value & 0x08) != 0;
@@ -136,26 +203,42 @@ impl SquareChannel {
                 // NRx4 (trigger + length enable + frequency high)
                 self.nr4 = value;
                 self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+
+                let was_enabled = self.length_enabled;
                 self.length_enabled = (value & 0x40) != 0;
+                let trigger = value & 0x80 != 0;
+                if extra_length_clock(
+                    &mut self.length_counter,
+                    was_enabled,
+                    self.length_enabled,
+                    length_first_half,
+                ) && !trigger
+                {
+                    self.enabled = false;
+                }
 
-                if value & 0x80 != 0 {
-                    self.trigger();
+                if trigger {
+                    self.trigger(length_first_half);
                 }
             }
             _ => {}
         }
     }
 
-    fn trigger(&mut self) {
+    fn trigger(&mut self, length_first_half: bool) {
         self.enabled = true;
 
         if self.length_counter == 0 {
             self.length_counter = 64;
+            if self.length_enabled && length_first_half {
+                self.length_counter -= 1;
+            }
         }
 
         self.frequency_timer = (2048 - self.frequency) * 4;
         self.current_volume = self.initial_volume;
         self.envelope_timer = self.envelope_pace;
+        self.envelope_running = true;
 
         // DAC check
         if self.nr2 & 0xF8 == 0 {
@@ -165,6 +248,7 @@ impl SquareChannel {
         // Sweep init (channel 1)
         if self.has_sweep {
             self.sweep_shadow_freq = self.frequency;
+            self.sweep_negate_used = false;
             self.sweep_timer = if self.sweep_pace != 0 {
                 self.sweep_pace
             } else {
@@ -181,9 +265,10 @@ impl SquareChannel {
         }
     }
 
-    fn calculate_sweep_frequency(&self) -> u16 {
+    fn calculate_sweep_frequency(&mut self) -> u16 {
         let shifted = self.sweep_shadow_freq >> self.sweep_step;
         if self.sweep_direction {
+            self.sweep_negate_used = true;
             self.sweep_shadow_freq.wrapping_sub(shifted)
         } else {
             self.sweep_shadow_freq.wrapping_add(shifted)
@@ -225,6 +310,8 @@ impl SquareChannel {
                 self.current_volume += 1;
             } else if !self.envelope_direction && self.current_volume > 0 {
                 self.current_volume -= 1;
+            } else {
+                self.envelope_running = false;
             }
         }
     }
@@ -262,17 +349,41 @@ impl SquareChannel {
         }
     }
 
-    pub fn output(&self) -> f32 {
+    pub fn dac_enabled(&self) -> bool {
+        self.nr2 & 0xF8 != 0
+    }
+
+    pub fn digital_output(&self) -> u8 {
         if !self.enabled {
-            return 0.0;
+            return 0;
         }
-        let sample = DUTY_TABLE[self.duty as usize][self.duty_position as usize];
-        (sample as f32) * (self.current_volume as f32 / 15.0)
+        DUTY_TABLE[self.duty as usize][self.duty_position as usize] * self.current_volume
     }
 
+    pub fn output(&self) -> f32 {
+        dac_output(self.dac_enabled(), self.digital_output())
+    }
+
+    pub fn frequency_hz(&self) -> f32 {
+        131072.0 / (2048 - self.frequency) as f32
+    }
+
+    pub fn info(&self) -> ChannelInfo {
+        ChannelInfo {
+            enabled: self.enabled,
+            frequency_hz: self.frequency_hz(),
+            volume: self.current_volume,
+            envelope: Some((self.envelope_direction, self.envelope_pace)),
+            duty: Some(self.duty),
+        }
+    }
+
+    // Power off clears everything but the length counter on DMG
     pub fn reset(&mut self) {
         let has_sweep = self.has_sweep;
+        let length_counter = self.length_counter;
         *self = Self::new(has_sweep);
+        self.length_counter = length_counter;
     }
 }
 
@@ -294,6 +405,7 @@ pub struct WaveChannel {
     // Wave RAM
     pub wave_ram: [u8; 16],
     wave_position: u8,
+    cycles_since_fetch: u16, // T-cycles since the last wave RAM read
 
     // DAC
     dac_enabled: bool,
@@ -323,6 +435,7 @@ impl WaveChannel {
             frequency_timer: 0,
             wave_ram: [0; 16],
             wave_position: 0,
+            cycles_since_fetch: u16::MAX,
             dac_enabled: false,
             nr0: 0,
             nr1: 0,
@@ -332,7 +445,7 @@ impl WaveChannel {
         }
     }
 
-    pub fn write_register(&mut self, reg: u8, value: u8) {
+    pub fn write_register(&mut self, reg: u8, value: u8, length_first_half: bool) {
         match reg {
             0 => {
                 // NR30 - DAC enable
@@ -361,25 +474,54 @@ impl WaveChannel {
                 // NR34 - Trigger + length enable + frequency high
                 self.nr4 = value;
                 self.frequency = (self.frequency & 0xFF) | (((value & 0x07) as u16) << 8);
+
+                let was_enabled = self.length_enabled;
                 self.length_enabled = (value & 0x40) != 0;
+                let trigger = value & 0x80 != 0;
+                if extra_length_clock(
+                    &mut self.length_counter,
+                    was_enabled,
+                    self.length_enabled,
+                    length_first_half,
+                ) && !trigger
+                {
+                    self.enabled = false;
+                }
 
-                if value & 0x80 != 0 {
-                    self.trigger();
+                if trigger {
+                    self.trigger(length_first_half);
                 }
             }
             _ => {}
         }
     }
 
-    fn trigger(&mut self) {
+    fn trigger(&mut self, length_first_half: bool) {
+        // DMG: retriggering just as the channel fetches a sample corrupts
+        // the first bytes of wave RAM with the ones being read
+        if self.enabled && self.frequency_timer == 1 {
+            let index = (((self.wave_position + 1) % 32) / 2) as usize;
+            if index < 4 {
+                self.wave_ram[0] = self.wave_ram[index];
+            } else {
+                let block = index & !3;
+                self.wave_ram.copy_within(block..block + 4, 0);
+            }
+        }
+
         self.enabled = true;
 
         if self.length_counter == 0 {
             self.length_counter = 256;
+            if self.length_enabled && length_first_half {
+                self.length_counter -= 1;
+            }
         }
 
-        self.frequency_timer = (2048 - self.frequency) * 2;
+        // The first sample is fetched after a short delay
+        self.frequency_timer = (2048 - self.frequency) * 2 + 6;
         self.wave_position = 0;
+        self.cycles_since_fetch = u16::MAX;
 
         if !self.dac_enabled {
             self.enabled = false;
@@ -390,9 +532,32 @@ impl WaveChannel {
         if self.frequency_timer > 0 {
             self.frequency_timer -= 1;
         }
+        self.cycles_since_fetch = self.cycles_since_fetch.saturating_add(1);
         if self.frequency_timer == 0 {
             self.frequency_timer = (2048 - self.frequency) * 2;
             self.wave_position = (self.wave_position + 1) % 32;
+            self.cycles_since_fetch = 0;
+        }
+    }
+
+    // While playing, the CPU only reaches the byte the channel is reading, and
+    // on DMG only in the cycle it is read; otherwise reads give 0xFF
+    pub fn read_wave_ram(&self, index: usize) -> u8 {
+        if !self.enabled {
+            return self.wave_ram[index];
+        }
+        if self.cycles_since_fetch < 2 {
+            self.wave_ram[(self.wave_position / 2) as usize]
+        } else {
+            0xFF
+        }
+    }
+
+    pub fn write_wave_ram(&mut self, index: usize, value: u8) {
+        if !self.enabled {
+            self.wave_ram[index] = value;
+        } else if self.cycles_since_fetch < 2 {
+            self.wave_ram[(self.wave_position / 2) as usize] = value;
         }
     }
 
@@ -405,9 +570,13 @@ impl WaveChannel {
         }
     }
 
-    pub fn output(&self) -> f32 {
-        if !self.enabled || !self.dac_enabled {
-            return 0.0;
+    pub fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    pub fn digital_output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
         }
 
         let byte_index = (self.wave_position / 2) as usize;
@@ -417,21 +586,45 @@ impl WaveChannel {
             self.wave_ram[byte_index] & 0x0F
         };
 
-        let shifted = match self.volume_code {
+        match self.volume_code {
             0 => sample >> 4, // mute (shift by 4 = 0)
             1 => sample,      // 100%
             2 => sample >> 1, // 50%
             3 => sample >> 2, // 25%
             _ => 0,
-        };
+        }
+    }
 
-        shifted as f32 / 15.0
+    pub fn output(&self) -> f32 {
+        dac_output(self.dac_enabled, self.digital_output())
+    }
+
+    pub fn frequency_hz(&self) -> f32 {
+        65536.0 / (2048 - self.frequency) as f32
+    }
+
+    // Volume as the 0-15 scale of the other channels
+    pub fn info(&self) -> ChannelInfo {
+        ChannelInfo {
+            enabled: self.enabled,
+            frequency_hz: self.frequency_hz(),
+            volume: match self.volume_code {
+                1 => 15,
+                2 => 7,
+                3 => 3,
+                _ => 0,
+            },
+            envelope: None,
+            duty: None,
+        }
     }
 
     pub fn reset(&mut self) {
         let wave_ram = self.wave_ram;
+        let length_counter = self.length_counter;
         *self = Self::new();
         self.wave_ram = wave_ram; // Wave RAM persists across APU power off
+        self.length_counter = length_counter;
     }
 }
 
@@ -448,6 +641,7 @@ pub struct NoiseChannel {
     envelope_direction: bool,
     envelope_pace: u8,
     envelope_timer: u8,
+    envelope_running: bool,
     current_volume: u8,
 
     // Noise
@@ -480,6 +674,7 @@ impl NoiseChannel {
             envelope_direction: false,
             envelope_pace: 0,
             envelope_timer: 0,
+            envelope_running: false,
             current_volume: 0,
             clock_shift: 0,
             width_mode: false,
@@ -493,7 +688,7 @@ impl NoiseChannel {
         }
     }
 
-    pub fn write_register(&mut self, reg: u8, value: u8) {
+    pub fn write_register(&mut self, reg: u8, value: u8, length_first_half: bool) {
         match reg {
             1 => {
                 // NR41 - Length
@@ -502,6 +697,10 @@ impl NoiseChannel {
             }
             2 => {
                 // NR42 - Volume envelope
+                if self.enabled {
+                    self.current_volume =
+                        zombie_volume(self.current_volume, self.nr2, value, self.envelope_running);
+                }
                 self.nr2 = value;
                 self.initial_volume = (value >> 4) & 0x0F;
                 self.envelope_direction = (value & 0x08) != 0;
@@ -521,27 +720,43 @@ impl NoiseChannel {
             4 => {
                 // NR44 - Trigger + length enable
                 self.nr4 = value;
+
+                let was_enabled = self.length_enabled;
                 self.length_enabled = (value & 0x40) != 0;
+                let trigger = value & 0x80 != 0;
+                if extra_length_clock(
+                    &mut self.length_counter,
+                    was_enabled,
+                    self.length_enabled,
+                    length_first_half,
+                ) && !trigger
+                {
+                    self.enabled = false;
+                }
 
-                if value & 0x80 != 0 {
-                    self.trigger();
+                if trigger {
+                    self.trigger(length_first_half);
                 }
             }
             _ => {}
         }
     }
 
-    fn trigger(&mut self) {
+    fn trigger(&mut self, length_first_half: bool) {
         self.enabled = true;
 
         if self.length_counter == 0 {
             self.length_counter = 64;
+            if self.length_enabled && length_first_half {
+                self.length_counter -= 1;
+            }
         }
 
         self.frequency_timer = self.get_frequency_timer();
         self.lfsr = 0x7FFF;
         self.current_volume = self.initial_volume;
         self.envelope_timer = self.envelope_pace;
+        self.envelope_running = true;
 
         if self.nr2 & 0xF8 == 0 {
             self.enabled = false;
@@ -598,24 +813,71 @@ impl NoiseChannel {
                 self.current_volume += 1;
             } else if !self.envelope_direction && self.current_volume > 0 {
                 self.current_volume -= 1;
+            } else {
+                self.envelope_running = false;
             }
         }
     }
 
-    pub fn output(&self) -> f32 {
+    pub fn dac_enabled(&self) -> bool {
+        self.nr2 & 0xF8 != 0
+    }
+
+    pub fn digital_output(&self) -> u8 {
         if !self.enabled {
-            return 0.0;
+            return 0;
         }
         // LFSR bit 0 inverted
-        let sample = if self.lfsr & 0x01 == 0 { 1.0 } else { 0.0 };
-        sample * (self.current_volume as f32 / 15.0)
+        let sample = if self.lfsr & 0x01 == 0 { 1 } else { 0 };
+        sample * self.current_volume
+    }
+
+    pub fn output(&self) -> f32 {
+        dac_output(self.dac_enabled(), self.digital_output())
+    }
+
+    // LFSR clock rate, not a pitch
+    pub fn frequency_hz(&self) -> f32 {
+        CPU_CLOCK as f32 / self.get_frequency_timer() as f32
+    }
+
+    pub fn info(&self) -> ChannelInfo {
+        ChannelInfo {
+            enabled: self.enabled,
+            frequency_hz: self.frequency_hz(),
+            volume: self.current_volume,
+            envelope: Some((self.envelope_direction, self.envelope_pace)),
+            duty: None,
+        }
     }
 
     pub fn reset(&mut self) {
+        let length_counter = self.length_counter;
         *self = Self::new();
+        self.length_counter = length_counter;
     }
 }
 
+// Snapshot of a channel for the audio debugger
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelInfo {
+    pub enabled: bool,
+    pub frequency_hz: f32,
+    pub volume: u8,                   // 0-15
+    pub envelope: Option<(bool, u8)>, // (increasing, pace)
+    pub duty: Option<u8>,             // NRx1 duty index
+}
+
+// Nearest equal-tempered note, e.g. "A4" for 440 Hz
+pub fn note_name(frequency_hz: f32) -> Option<String> {
+    if !(16.0..=20_000.0).contains(&frequency_hz) {
+        return None;
+    }
+    let midi = (69.0 + 12.0 * (frequency_hz / 440.0).log2()).round() as i32;
+    let octave = midi / 12 - 1;
+    Some(format!("{}{}", NOTE_NAMES[(midi % 12) as usize], octave))
+}
+
 #[derive(Debug, Clone)]
 pub struct APU {
     // Control registers
@@ -629,14 +891,28 @@ pub struct APU {
     pub channel3: WaveChannel,
     pub channel4: NoiseChannel,
 
-    // Frame sequencer (512 Hz = CPU_CLOCK / 8192)
-    frame_sequencer_counter: u16,
+    // Frame sequencer, clocked by the timer's divider (512 Hz)
     frame_sequencer_step: u8, // 0..7
 
-    // Sample generation
-    sample_counter: u32,
-    sample_rate: u32,
-    sample_buffer: Vec<(f32, f32)>,
+    // Sample generation: mixer output changes are fed to band-limited
+    // synthesis buffers and resampled to the host rate
+    output: StereoOutput,
+
+    // Each channel mixed on its own, for recording stems
+    stems: Option<Box<[StereoOutput; 4]>>,
+
+    // Analog input from the cartridge, mixed in through NR50 bits 7/3
+    pub vin: f32,
+
+    // Channels left out of the mix; register state is unaffected
+    pub muted: [bool; 4],
+
+    // Per-channel output history for the oscilloscope, ring buffers
+    scope: [[f32; SCOPE_LENGTH]; 4],
+    scope_position: usize,
+    scope_counter: u32,
+
+    pub high_pass_enabled: bool,
 }
 
 impl Default for APU {
@@ -655,18 +931,90 @@ impl APU {
             channel2: SquareChannel::new(false),
             channel3: WaveChannel::new(),
             channel4: NoiseChannel::new(),
-            frame_sequencer_counter: 0,
             frame_sequencer_step: 0,
-            sample_counter: 0,
-            sample_rate: 44100,
-            sample_buffer: Vec::new(),
+            output: StereoOutput::new(DEFAULT_SAMPLE_RATE),
+            stems: None,
+            vin: 0.0,
+            muted: [false; 4],
+            scope: [[0.0; SCOPE_LENGTH]; 4],
+            scope_position: 0,
+            scope_counter: 0,
+            high_pass_enabled: true,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.output.sample_rate()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.output.set_sample_rate(sample_rate);
+        if let Some(stems) = &mut self.stems {
+            for stem in stems.iter_mut() {
+                stem.set_sample_rate(sample_rate);
+            }
+        }
+    }
+
+    // Stems are only synthesized while enabled, they cost four extra mixes
+    pub fn set_stems_enabled(&mut self, enabled: bool) {
+        self.stems = if enabled {
+            let sample_rate = self.sample_rate();
+            Some(Box::new(std::array::from_fn(|_| {
+                StereoOutput::new(sample_rate)
+            })))
+        } else {
+            None
+        };
+    }
+
+    pub fn stems_enabled(&self) -> bool {
+        self.stems.is_some()
+    }
+
+    // Mutes every channel except `channel` (0-3)
+    pub fn solo(&mut self, channel: usize) {
+        for (i, muted) in self.muted.iter_mut().enumerate() {
+            *muted = i != channel;
         }
     }
 
+    pub fn is_soloed(&self, channel: usize) -> bool {
+        self.muted
+            .iter()
+            .enumerate()
+            .all(|(i, &muted)| muted == (i != channel))
+    }
+
+    pub fn channel_info(&self, channel: usize) -> ChannelInfo {
+        match channel {
+            0 => self.channel1.info(),
+            1 => self.channel2.info(),
+            2 => self.channel3.info(),
+            _ => self.channel4.info(),
+        }
+    }
+
+    // Oldest point first, SCOPE_LENGTH points
+    pub fn scope(&self, channel: usize) -> Vec<f32> {
+        let history = &self.scope[channel];
+        history[self.scope_position..]
+            .iter()
+            .chain(&history[..self.scope_position])
+            .copied()
+            .collect()
+    }
+
     fn is_powered(&self) -> bool {
         self.nr52 & 0x80 != 0
     }
 
+    // Steps 0, 2, 4 and 6 clock length; the next one being odd means the
+    // current period already had its clock
+    fn length_first_half(&self) -> bool {
+        self.frame_sequencer_step % 2 == 1
+    }
+
     pub fn read_register(&self, address: u16) -> u8 {
         match address {
             // Channel 1 registers
@@ -719,10 +1067,7 @@ impl APU {
             }
 
             // Wave RAM
-            0xFF30..=0xFF3F => {
-                let index = (address - 0xFF30) as usize;
-                self.channel3.wave_ram[index]
-            }
+            0xFF30..=0xFF3F => self.channel3.read_wave_ram((address - 0xFF30) as usize),
 
             _ => 0xFF,
         }
@@ -731,8 +1076,8 @@ impl APU {
     pub fn write_register(&mut self, address: u16, value: u8) {
         // Wave RAM is always writable
         if (0xFF30..=0xFF3F).contains(&address) {
-            let index = (address - 0xFF30) as usize;
-            self.channel3.wave_ram[index] = value;
+            self.channel3
+                .write_wave_ram((address - 0xFF30) as usize, value);
             return;
         }
 
@@ -742,6 +1087,7 @@ impl APU {
             self.nr52 = (self.nr52 & 0x7F) | (value & 0x80);
 
             if was_on && !self.is_powered() {
+                debug!(Apu, "Power off");
                 // Power off: reset all registers
                 self.channel1.reset();
                 self.channel2.reset();
@@ -749,49 +1095,59 @@ impl APU {
                 self.channel4.reset();
                 self.nr50 = 0;
                 self.nr51 = 0;
+            } else if !was_on && self.is_powered() {
+                debug!(Apu, "Power on");
+                // Power on: the frame sequencer restarts at step 0
+                self.frame_sequencer_step = 0;
             }
             return;
         }
 
         // Block writes when APU is off
         if !self.is_powered() {
-            // Exception: length counters (NRx1) can be written when off on DMG
+            // Exception: length counters (NRx1) can be written when off on DMG;
+            // the duty bits stay cleared
             match address {
-                0xFF11 => self.channel1.write_register(1, value),
-                0xFF16 => self.channel2.write_register(1, value),
-                0xFF1B => self.channel3.write_register(1, value),
-                0xFF20 => self.channel4.write_register(1, value),
+                0xFF11 => self.channel1.write_register(1, value & 0x3F, false),
+                0xFF16 => self.channel2.write_register(1, value & 0x3F, false),
+                0xFF1B => self.channel3.write_register(1, value, false),
+                0xFF20 => self.channel4.write_register(1, value & 0x3F, false),
                 _ => {}
             }
             return;
         }
 
+        let first_half = self.length_first_half();
+        if matches!(address, 0xFF14 | 0xFF19 | 0xFF1E | 0xFF23) && value & 0x80 != 0 {
+            debug!(Apu, "Channel {} triggered", (address - 0xFF14) / 5 + 1);
+        }
+
         match address {
             // Channel 1
-            0xFF10 => self.channel1.write_register(0, value),
-            0xFF11 => self.channel1.write_register(1, value),
-            0xFF12 => self.channel1.write_register(2, value),
-            0xFF13 => self.channel1.write_register(3, value),
-            0xFF14 => self.channel1.write_register(4, value),
+            0xFF10 => self.channel1.write_register(0, value, first_half),
+            0xFF11 => self.channel1.write_register(1, value, first_half),
+            0xFF12 => self.channel1.write_register(2, value, first_half),
+            0xFF13 => self.channel1.write_register(3, value, first_half),
+            0xFF14 => self.channel1.write_register(4, value, first_half),
 
             // Channel 2
-            0xFF16 => self.channel2.write_register(1, value),
-            0xFF17 => self.channel2.write_register(2, value),
-            0xFF18 => self.channel2.write_register(3, value),
-            0xFF19 => self.channel2.write_register(4, value),
+            0xFF16 => self.channel2.write_register(1, value, first_half),
+            0xFF17 => self.channel2.write_register(2, value, first_half),
+            0xFF18 => self.channel2.write_register(3, value, first_half),
+            0xFF19 => self.channel2.write_register(4, value, first_half),
 
             // Channel 3
-            0xFF1A => self.channel3.write_register(0, value),
-            0xFF1B => self.channel3.write_register(1, value),
-            0xFF1C => self.channel3.write_register(2, value),
-            0xFF1D => self.channel3.write_register(3, value),
-            0xFF1E => self.channel3.write_register(4, value),
+            0xFF1A => self.channel3.write_register(0, value, first_half),
+            0xFF1B => self.channel3.write_register(1, value, first_half),
+            0xFF1C => self.channel3.write_register(2, value, first_half),
+            0xFF1D => self.channel3.write_register(3, value, first_half),
+            0xFF1E => self.channel3.write_register(4, value, first_half),
 
             // Channel 4
-            0xFF20 => self.channel4.write_register(1, value),
-            0xFF21 => self.channel4.write_register(2, value),
-            0xFF22 => self.channel4.write_register(3, value),
-            0xFF23 => self.channel4.write_register(4, value),
+            0xFF20 => self.channel4.write_register(1, value, first_half),
+            0xFF21 => self.channel4.write_register(2, value, first_half),
+            0xFF22 => self.channel4.write_register(3, value, first_half),
+            0xFF23 => self.channel4.write_register(4, value, first_half),
 
             // Control
             0xFF24 => self.nr50 = value,
@@ -802,37 +1158,73 @@ impl APU {
     }
 
     pub fn step(&mut self, cpu_cycles: u8) {
-        if !self.is_powered() {
-            return;
-        }
-
-        let t_cycles = cpu_cycles as u32 * 4;
-
-        for _ in 0..t_cycles {
-            // Tick frequency timers every T-cycle
-            self.channel1.step_frequency();
-            self.channel2.step_frequency();
-            self.channel3.step_frequency();
-            self.channel4.step_frequency();
+        for _ in 0..cpu_cycles {
+            if self.is_powered() {
+                for _ in 0..4 {
+                    // Tick frequency timers every T-cycle
+                    self.channel1.step_frequency();
+                    self.channel2.step_frequency();
+                    self.channel3.step_frequency();
+                    self.channel4.step_frequency();
+                }
+            }
 
-            // Frame sequencer
-            self.frame_sequencer_counter += 1;
-            if self.frame_sequencer_counter >= FRAME_SEQUENCER_RATE {
-                self.frame_sequencer_counter = 0;
-                self.clock_frame_sequencer();
+            // Output is resolved per M-cycle, well below the sample period.
+            // Time keeps running while powered off so the stream never stalls.
+            let mix = self.mix_samples();
+            self.output.push(mix, 4);
+
+            if self.stems.is_some() {
+                let outputs = self.channel_outputs();
+                let stems: [(f32, f32); 4] = std::array::from_fn(|channel| {
+                    let mut solo = [0.0; 4];
+                    solo[channel] = outputs[channel];
+                    self.pan_and_scale(solo, 0.0)
+                });
+                if let Some(outputs) = &mut self.stems {
+                    for (output, stem) in outputs.iter_mut().zip(stems) {
+                        output.push(stem, 4);
+                    }
+                }
             }
 
-            // Sample generation
-            self.sample_counter += self.sample_rate;
-            if self.sample_counter >= CPU_CLOCK {
-                self.sample_counter -= CPU_CLOCK;
-                let sample = self.mix_samples();
-                self.sample_buffer.push(sample);
+            self.scope_counter += 1;
+            if self.scope_counter >= SCOPE_DECIMATION {
+                self.scope_counter = 0;
+                self.record_scope();
             }
         }
     }
 
-    fn clock_frame_sequencer(&mut self) {
+    // Recorded before muting so silenced channels can still be watched
+    fn record_scope(&mut self) {
+        let outputs = self.channel_outputs();
+        for (history, output) in self.scope.iter_mut().zip(outputs) {
+            history[self.scope_position] = output;
+        }
+        self.scope_position = (self.scope_position + 1) % SCOPE_LENGTH;
+    }
+
+    fn channel_outputs(&self) -> [f32; 4] {
+        [
+            self.channel1.output(),
+            self.channel2.output(),
+            self.channel3.output(),
+            self.channel4.output(),
+        ]
+    }
+
+    pub fn frame_sequencer_step(&self) -> u8 {
+        self.frame_sequencer_step
+    }
+
+    // Called on each falling edge of DIV bit 4, including the one caused by
+    // resetting DIV, so DIV writes shift audio timing as on hardware
+    pub fn clock_frame_sequencer(&mut self) {
+        if !self.is_powered() {
+            return;
+        }
+
         match self.frame_sequencer_step {
             0 => {
                 self.clock_length();
@@ -877,14 +1269,29 @@ impl APU {
     }
 
     fn mix_samples(&self) -> (f32, f32) {
-        let ch1 = self.channel1.output();
-        let ch2 = self.channel2.output();
-        let ch3 = self.channel3.output();
-        let ch4 = self.channel4.output();
+        let mut outputs = self.channel_outputs();
+        for (output, &muted) in outputs.iter_mut().zip(&self.muted) {
+            if muted {
+                *output = 0.0;
+            }
+        }
+        self.pan_and_scale(outputs, self.vin)
+    }
+
+    fn pan_and_scale(&self, outputs: [f32; 4], vin: f32) -> (f32, f32) {
+        let [ch1, ch2, ch3, ch4] = outputs;
 
         let mut left = 0.0f32;
         let mut right = 0.0f32;
 
+        // VIN (cartridge audio)
+        if self.nr50 & 0x80 != 0 {
+            left += vin;
+        }
+        if self.nr50 & 0x08 != 0 {
+            right += vin;
+        }
+
         // NR51 panning
         if self.nr51 & 0x10 != 0 {
             left += ch1;
@@ -923,6 +1330,88 @@ impl APU {
     }
 
     pub fn take_samples(&mut self) -> Vec<(f32, f32)> {
-        std::mem::take(&mut self.sample_buffer)
+        self.output.take_samples(self.high_pass_enabled)
+    }
+
+    // Per-channel samples since the last call, None unless stems are enabled
+    pub fn take_stem_samples(&mut self) -> Option<[Vec<(f32, f32)>; 4]> {
+        let high_pass = self.high_pass_enabled;
+        let stems = self.stems.as_mut()?;
+        Some(std::array::from_fn(|channel| {
+            stems[channel].take_samples(high_pass)
+        }))
+    }
+}
+
+// One stereo stream: band-limited synthesis followed by the output coupling
+// capacitors, one per side
+#[derive(Debug, Clone)]
+struct StereoOutput {
+    blip_left: BlipBuffer,
+    blip_right: BlipBuffer,
+    last_output: (f32, f32),
+    hpf_charge_factor: f32,
+    hpf_capacitor: (f32, f32),
+}
+
+impl StereoOutput {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            blip_left: BlipBuffer::new(CPU_CLOCK, sample_rate),
+            blip_right: BlipBuffer::new(CPU_CLOCK, sample_rate),
+            last_output: (0.0, 0.0),
+            hpf_charge_factor: hpf_charge_factor(sample_rate),
+            hpf_capacitor: (0.0, 0.0),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.blip_left.sample_rate()
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.blip_left.set_sample_rate(sample_rate);
+        self.blip_right.set_sample_rate(sample_rate);
+        self.hpf_charge_factor = hpf_charge_factor(sample_rate);
     }
+
+    // Output level for the next `clocks` CPU clocks
+    fn push(&mut self, (left, right): (f32, f32), clocks: u32) {
+        self.blip_left.add_delta(left - self.last_output.0);
+        self.blip_right.add_delta(right - self.last_output.1);
+        self.last_output = (left, right);
+
+        self.blip_left.advance(clocks);
+        self.blip_right.advance(clocks);
+    }
+
+    fn take_samples(&mut self, high_pass_enabled: bool) -> Vec<(f32, f32)> {
+        let left = self.blip_left.read_samples();
+        let right = self.blip_right.read_samples();
+
+        left.into_iter()
+            .zip(right)
+            .map(|(l, r)| {
+                if high_pass_enabled {
+                    (
+                        high_pass(&mut self.hpf_capacitor.0, l, self.hpf_charge_factor),
+                        high_pass(&mut self.hpf_capacitor.1, r, self.hpf_charge_factor),
+                    )
+                } else {
+                    (l, r)
+                }
+            })
+            .collect()
+    }
+}
+
+fn hpf_charge_factor(sample_rate: u32) -> f32 {
+    HPF_CHARGE_PER_CLOCK.powf(CPU_CLOCK as f64 / sample_rate as f64) as f32
+}
+
+// Removes the DAC DC offset, the way the DMG output capacitor does
+fn high_pass(capacitor: &mut f32, input: f32, charge_factor: f32) -> f32 {
+    let output = input - *capacitor;
+    *capacitor = input - output * charge_factor;
+    output
 }
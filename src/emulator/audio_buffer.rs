@@ -0,0 +1,135 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+const MAX_RATE_DELTA: f64 = 0.005; // +-0.5%, inaudible pitch change
+
+// Single-producer single-consumer ring of stereo frames. Each frame is packed
+// into one atomic word, so neither side ever blocks the other: the emulator
+// thread pushes, the audio callback pops.
+struct Shared {
+    slots: Box<[AtomicU64]>,
+    read: AtomicUsize,  // total frames popped
+    write: AtomicUsize, // total frames pushed
+}
+
+pub struct AudioProducer {
+    shared: Arc<Shared>,
+}
+
+pub struct AudioConsumer {
+    shared: Arc<Shared>,
+}
+
+pub fn audio_ring(capacity: usize) -> (AudioProducer, AudioConsumer) {
+    let shared = Arc::new(Shared {
+        slots: (0..capacity.max(1)).map(|_| AtomicU64::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+    (
+        AudioProducer {
+            shared: Arc::clone(&shared),
+        },
+        AudioConsumer { shared },
+    )
+}
+
+fn pack((left, right): (f32, f32)) -> u64 {
+    ((left.to_bits() as u64) << 32) | right.to_bits() as u64
+}
+
+fn unpack(value: u64) -> (f32, f32) {
+    (
+        f32::from_bits((value >> 32) as u32),
+        f32::from_bits(value as u32),
+    )
+}
+
+impl Shared {
+    fn len(&self) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let read = self.read.load(Ordering::Acquire);
+        write.wrapping_sub(read)
+    }
+}
+
+impl AudioProducer {
+    // Returns how many frames fit; the rest are dropped
+    pub fn push_slice(&mut self, frames: &[(f32, f32)]) -> usize {
+        let shared = &self.shared;
+        let write = shared.write.load(Ordering::Relaxed);
+        let read = shared.read.load(Ordering::Acquire);
+        let free = shared.slots.len() - write.wrapping_sub(read);
+        let count = frames.len().min(free);
+
+        for (i, &frame) in frames[..count].iter().enumerate() {
+            let index = write.wrapping_add(i) % shared.slots.len();
+            shared.slots[index].store(pack(frame), Ordering::Relaxed);
+        }
+        shared
+            .write
+            .store(write.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.slots.len()
+    }
+}
+
+impl AudioConsumer {
+    pub fn pop(&mut self) -> Option<(f32, f32)> {
+        let shared = &self.shared;
+        let read = shared.read.load(Ordering::Relaxed);
+        let write = shared.write.load(Ordering::Acquire);
+        if read == write {
+            return None;
+        }
+
+        let value = shared.slots[read % shared.slots.len()].load(Ordering::Relaxed);
+        shared.read.store(read.wrapping_add(1), Ordering::Release);
+        Some(unpack(value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Dynamic rate control: the emulator's 59.73 Hz and the host's audio clock
+// never agree exactly, so the APU output rate is nudged around the nominal
+// one to keep the ring buffer near its target fill instead of letting it
+// slowly underrun (crackles) or overflow (latency, dropped frames).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateControl {
+    pub nominal_rate: u32,
+    pub target_fill: usize,
+}
+
+impl RateControl {
+    pub fn new(nominal_rate: u32, target_fill: usize) -> Self {
+        Self {
+            nominal_rate,
+            target_fill: target_fill.max(1),
+        }
+    }
+
+    // Rate to synthesize at for the given buffer fill
+    pub fn rate(&self, fill: usize) -> u32 {
+        let error = (self.target_fill as f64 - fill as f64) / self.target_fill as f64;
+        let ratio = 1.0 + MAX_RATE_DELTA * error.clamp(-1.0, 1.0);
+        (self.nominal_rate as f64 * ratio).round() as u32
+    }
+}
@@ -0,0 +1,137 @@
+use std::f64::consts::PI;
+
+const PHASES: usize = 32; // sub-sample resolution of step positions
+const KERNEL_WIDTH: usize = 16; // taps per step, also the output delay
+const CUTOFF: f64 = 0.45; // fraction of the output rate, just under Nyquist
+
+// Band-limited step synthesis, in the spirit of blip_buf: the input is a
+// stream of amplitude changes at emulated clock times, each one is added to
+// the output as a windowed-sinc step placed at its fractional sample
+// position, so square edges don't alias the way point sampling does.
+#[derive(Debug, Clone)]
+pub struct BlipBuffer {
+    clock_rate: u32,
+    sample_rate: u32,
+    samples_per_clock: f64,
+
+    // Current time in output samples, relative to buffer[0]
+    position: f64,
+
+    // Impulses of pending deltas; integrated into samples on read
+    buffer: Vec<f32>,
+    integrator: f32,
+
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        Self {
+            clock_rate,
+            sample_rate,
+            samples_per_clock: sample_rate as f64 / clock_rate as f64,
+            position: 0.0,
+            buffer: vec![0.0; KERNEL_WIDTH],
+            integrator: 0.0,
+            kernel: build_kernel(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.samples_per_clock = sample_rate as f64 / self.clock_rate as f64;
+    }
+
+    pub fn clear(&mut self) {
+        self.position = 0.0;
+        self.buffer = vec![0.0; KERNEL_WIDTH];
+        self.integrator = 0.0;
+    }
+
+    // Amplitude changes by `delta` at the current time
+    pub fn add_delta(&mut self, delta: f32) {
+        if delta == 0.0 {
+            return;
+        }
+
+        let index = self.position as usize;
+        let phase = ((self.position - index as f64) * PHASES as f64).round() as usize;
+
+        if self.buffer.len() < index + KERNEL_WIDTH {
+            self.buffer.resize(index + KERNEL_WIDTH, 0.0);
+        }
+
+        for (slot, tap) in self.buffer[index..].iter_mut().zip(self.kernel[phase]) {
+            *slot += delta * tap;
+        }
+    }
+
+    pub fn advance(&mut self, clocks: u32) {
+        self.position += clocks as f64 * self.samples_per_clock;
+    }
+
+    // Samples no future delta can reach any more
+    pub fn samples_available(&self) -> usize {
+        self.position as usize
+    }
+
+    pub fn read_samples(&mut self) -> Vec<f32> {
+        let count = self.samples_available();
+        if self.buffer.len() < count + KERNEL_WIDTH {
+            self.buffer.resize(count + KERNEL_WIDTH, 0.0);
+        }
+
+        let samples = self
+            .buffer
+            .drain(..count)
+            .map(|impulse| {
+                self.integrator += impulse;
+                self.integrator
+            })
+            .collect();
+
+        self.position -= count as f64;
+        samples
+    }
+}
+
+fn build_kernel() -> Vec<[f32; KERNEL_WIDTH]> {
+    let center = (KERNEL_WIDTH / 2) as f64;
+
+    (0..=PHASES)
+        .map(|phase| {
+            let offset = phase as f64 / PHASES as f64;
+            let mut taps = [0.0f64; KERNEL_WIDTH];
+
+            for (k, tap) in taps.iter_mut().enumerate() {
+                // Distance from the (delayed) step position, in samples
+                let x = k as f64 - offset - center + 0.5;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (2.0 * PI * CUTOFF * x).sin() / (2.0 * PI * CUTOFF * x)
+                };
+                // Blackman window over the kernel span
+                let n = (x + center) / KERNEL_WIDTH as f64;
+                let window = if (0.0..=1.0).contains(&n) {
+                    0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos()
+                } else {
+                    0.0
+                };
+                *tap = sinc * window;
+            }
+
+            // Each step must add exactly `delta` once integrated
+            let sum: f64 = taps.iter().sum();
+            let mut kernel = [0.0f32; KERNEL_WIDTH];
+            for (out, tap) in kernel.iter_mut().zip(taps) {
+                *out = (tap / sum) as f32;
+            }
+            kernel
+        })
+        .collect()
+}
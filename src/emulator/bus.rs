@@ -1,7 +1,17 @@
 use crate::debug;
 use crate::emulator::{
-    apu::APU, joypad::Joypad, joypad::JoypadButton, memory::Memory, ppu::PPU, timer::Timer,
+    apu::{APU, FRAME_SEQUENCER_DIV_MASK},
+    debugger::{WatchHit, Watchpoint},
+    dma::Dma,
+    joypad::Joypad,
+    joypad::JoypadButton,
+    memory::Memory,
+    memory_viewer::WriteHistory,
+    ppu::PPU,
+    serial::{Serial, SerialLink},
+    timer::Timer,
 };
+use std::cell::Cell;
 
 #[derive(Debug, Clone)]
 pub struct Bus {
@@ -10,6 +20,25 @@ pub struct Bus {
     pub timer: Timer,
     pub joypad: Joypad,
     pub apu: APU,
+    pub dma: Dma,
+    pub serial: Serial,
+
+    // VRAM/OAM locking during PPU modes 2/3 and the OAM corruption bug.
+    // Can be turned off to debug homebrew that ignores STAT.
+    pub access_locking: bool,
+
+    // T-cycles elapsed in the instruction being executed, advanced on each
+    // CPU access so locking is checked against the PPU at that exact cycle
+    access_cycles: Cell<u32>,
+    tracking_accesses: bool,
+
+    // Debugger watchpoints, checked on CPU accesses. The first hit of an
+    // instruction is kept until the debugger takes it.
+    pub watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<WatchHit>>,
+
+    // Recent writes, for the memory editor's highlighting
+    pub write_history: Option<WriteHistory>,
 }
 
 impl Bus {
@@ -20,90 +49,208 @@ impl Bus {
             timer: Timer::new(),
             joypad: Joypad::new(),
             apu: APU::new(),
+            dma: Dma::new(),
+            serial: Serial::new(),
+            access_locking: true,
+            access_cycles: Cell::new(0),
+            tracking_accesses: false,
+            watchpoints: Vec::new(),
+            watch_hit: Cell::new(None),
+            write_history: None,
         }
     }
 
     pub fn read_byte(&self, address: u16) -> u8 {
+        let value = self.read_mapped(address);
+        self.check_watchpoints(address, value, false);
+        value
+    }
+
+    fn read_mapped(&self, address: u16) -> u8 {
+        let dots = self.next_access_cycle();
+
+        if let Some((source, last_byte)) = self.dma_state_at(dots)
+            && dma_conflicts_with(source, address)
+        {
+            return match address {
+                0xFE00..=0xFE9F => 0xFF,
+                // Same bus as the DMA source: the CPU sees the byte being copied
+                _ => last_byte,
+            };
+        }
+
+        match address {
+            0x8000..=0x9FFF if !self.can_access_vram(dots) => 0xFF,
+            0xFE00..=0xFE9F if !self.can_access_oam(dots) => 0xFF,
+            _ => self.peek(address),
+        }
+    }
+
+    // Debugger view of the address space: what a read would find, without
+    // watchpoints, access timing, PPU locking or DMA conflicts
+    pub fn peek(&self, address: u16) -> u8 {
         match address {
             0xFF04..=0xFF07 => self.timer.read_register(address),
             0xFF00 => self.joypad.read_register(),
+            0xFF01..=0xFF02 => self.serial.read_register(address),
             0xFF10..=0xFF26 => self.apu.read_register(address),
             0xFF30..=0xFF3F => self.apu.read_register(address),
-            0xFF46 => 0xFF, // DMA register is always 0xFF
+            0xFF46 => self.dma.read_register(),
             0xFF40..=0xFF4B => self.ppu.read_register(address),
-            0x8000..=0x9FFF => {
-                if self.ppu.can_access_vram() {
-                    self.memory.read_byte(address)
-                } else {
-                    0xFF
-                }
-            }
-            0xFE00..=0xFE9F => {
-                if self.ppu.can_access_oam() {
-                    self.memory.read_byte(address)
-                } else {
-                    0xFF
-                }
-            }
             _ => self.memory.read_byte(address),
         }
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
+        self.check_watchpoints(address, value, true);
+        let dots = self.next_access_cycle();
+
+        if let Some((source, _)) = self.dma_state_at(dots)
+            && dma_conflicts_with(source, address)
+        {
+            return;
+        }
+        let locked = match address {
+            0x8000..=0x9FFF => !self.can_access_vram(dots),
+            0xFE00..=0xFE9F => !self.can_access_oam(dots),
+            _ => false,
+        };
+        if locked {
+            return;
+        }
+
+        // Only writes that reach their target are highlighted
+        if let Some(history) = &mut self.write_history {
+            history.record(address);
+        }
+
         match address {
-            0xFF04..=0xFF07 => self.timer.write_register(address, value),
+            0xFF04..=0xFF07 => {
+                let divider = self.timer.divider();
+                self.timer.write_register(address, value);
+                self.clock_frame_sequencer(divider);
+            }
             0xFF00 => {
                 self.joypad.write_register(value);
             }
-            0xFF01 => {
-                debug!(
-                    "Serial data write: 0x{:02X} ('{}')",
-                    value,
-                    if value.is_ascii_graphic() || value == b' ' {
-                        value as char
-                    } else {
-                        '?'
-                    }
-                );
-                self.memory.write_byte(address, value);
-            }
-            0xFF02 => {
-                if value & 0x80 != 0 {
-                    debug!("Serial transfer started");
-                }
-                self.memory.write_byte(address, value);
-            }
+            0xFF01..=0xFF02 => self.serial.write_register(address, value),
             0xFF10..=0xFF26 => self.apu.write_register(address, value),
             0xFF30..=0xFF3F => self.apu.write_register(address, value),
             0xFF46 => {
-                // DMA Transfer
-                self.perform_dma_transfer(value);
+                debug!(Bus, "OAM DMA from ${:02X}00", value);
+                self.dma.write_register(value)
             }
             0xFF40..=0xFF4B => self.ppu.write_register(address, value),
-            0x8000..=0x9FFF => {
-                if self.ppu.can_access_vram() {
-                    self.memory.write_byte(address, value)
-                }
-            }
-            0xFE00..=0xFE9F => {
-                if self.ppu.can_access_oam() {
-                    self.memory.write_byte(address, value)
-                }
-            }
             _ => self.memory.write_byte(address, value),
         }
     }
 
-    fn perform_dma_transfer(&mut self, source_high_byte: u8) {
-        let source_address = (source_high_byte as u16) << 8;
+    fn check_watchpoints(&self, address: u16, value: u8, write: bool) {
+        if !self.tracking_accesses || self.watchpoints.is_empty() {
+            return;
+        }
+        let rom_bank = self.memory.rom_bank();
+        let hit = self
+            .watchpoints
+            .iter()
+            .any(|w| w.matches(address, write, rom_bank));
+        if hit && self.watch_hit.get().is_none() {
+            self.watch_hit.set(Some(WatchHit {
+                address,
+                value,
+                write,
+            }));
+        }
+    }
+
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.watch_hit.take()
+    }
 
-        for i in 0..0xA0 {
-            let source_addr = source_address + i;
-            let dest_addr = 0xFE00 + i;
+    pub fn begin_instruction(&mut self) {
+        self.access_cycles.set(0);
+        self.tracking_accesses = true;
+    }
 
-            let data = self.memory.read_byte(source_addr);
+    pub fn end_instruction(&mut self) {
+        self.access_cycles.set(0);
+        self.tracking_accesses = false;
+    }
 
-            self.memory.write_byte(dest_addr, data);
+    fn next_access_cycle(&self) -> u32 {
+        let dots = self.access_cycles.get();
+        if self.tracking_accesses {
+            self.access_cycles.set(dots + 4);
+        }
+        dots
+    }
+
+    fn can_access_vram(&self, dots: u32) -> bool {
+        !self.access_locking || self.ppu.can_access_vram_after(dots)
+    }
+
+    fn can_access_oam(&self, dots: u32) -> bool {
+        !self.access_locking || self.ppu.can_access_oam_after(dots)
+    }
+
+    // 16-bit INC/DEC put their operand on the address bus. If it points at
+    // 0xFE00-0xFEFF while the PPU scans OAM, the row being read gets mangled
+    // with the previous one.
+    pub fn trigger_oam_bug(&mut self, address: u16) {
+        if !self.access_locking || !(0xFE00..=0xFEFF).contains(&address) {
+            return;
+        }
+
+        let row = match self.ppu.oam_scan_row_after(self.access_cycles.get()) {
+            Some(row) if (1..20).contains(&row) => row,
+            _ => return,
+        };
+
+        let current = 0xFE00 + row * 8;
+        let previous = current - 8;
+        let word = |memory: &Memory, address: u16| {
+            memory.read_oam(address) as u16 | ((memory.read_oam(address + 1) as u16) << 8)
+        };
+
+        let a = word(&self.memory, current);
+        let b = word(&self.memory, previous);
+        let c = word(&self.memory, previous + 4);
+        let corrupted = ((a ^ c) & (b ^ c)) ^ c;
+
+        self.memory.write_byte(current, corrupted as u8);
+        self.memory.write_byte(current + 1, (corrupted >> 8) as u8);
+        for i in 2..8 {
+            let value = self.memory.read_oam(previous + i);
+            self.memory.write_byte(current + i, value);
+        }
+    }
+
+    // Source address and byte on the bus of the transfer running `dots`
+    // T-cycles into the current instruction, if one is. The DMA itself is
+    // only stepped after the instruction, so this replays the M-cycles
+    // before the access.
+    fn dma_state_at(&self, dots: u32) -> Option<(u16, u8)> {
+        if !self.dma.is_busy() {
+            return None;
+        }
+
+        let mut dma = self.dma.clone();
+        let mut last_byte = dma.last_byte();
+        for _ in 0..dots / 4 {
+            if let Some((source, _)) = dma.step() {
+                last_byte = self.memory.read_byte(source);
+            }
+        }
+        dma.is_active().then_some((dma.source(), last_byte))
+    }
+
+    pub fn dma_step(&mut self, cpu_cycles: u8) {
+        for _ in 0..cpu_cycles / 4 {
+            if let Some((source, destination)) = self.dma.step() {
+                let data = self.memory.read_byte(source);
+                self.memory.write_byte(destination, data);
+                self.dma.set_last_byte(data);
+            }
         }
     }
 
@@ -123,10 +270,31 @@ impl Bus {
     }
 
     pub fn timer_step(&mut self, cpu_cycles: u8) -> bool {
-        self.timer.step(cpu_cycles);
+        for _ in 0..cpu_cycles {
+            let divider = self.timer.divider();
+            self.timer.step(1);
+            self.clock_frame_sequencer(divider);
+        }
         self.timer.take_interrupt()
     }
 
+    // The APU frame sequencer ticks when DIV bit 4 goes from 1 to 0
+    fn clock_frame_sequencer(&mut self, previous_divider: u16) {
+        let falling = previous_divider & !self.timer.divider() & FRAME_SEQUENCER_DIV_MASK;
+        if falling != 0 {
+            self.apu.clock_frame_sequencer();
+        }
+    }
+
+    pub fn serial_step(&mut self, cpu_cycles: u8) -> bool {
+        self.serial.step(cpu_cycles);
+        self.serial.take_interrupt()
+    }
+
+    pub fn set_serial_link(&mut self, link: Box<dyn SerialLink>) {
+        self.serial.set_link(link);
+    }
+
     pub fn set_joypad_input(&mut self, button: JoypadButton, pressed: bool) {
         self.joypad.set_button(button, pressed);
     }
@@ -149,3 +317,17 @@ impl Default for Bus {
         Self::new()
     }
 }
+
+// During OAM DMA the CPU only keeps access to HRAM and the I/O registers.
+// OAM is owned by the DMA unit, and the bus the transfer reads from (VRAM or
+// the external ROM/WRAM bus) is busy.
+fn dma_conflicts_with(source: u16, address: u16) -> bool {
+    let source_on_vram_bus = (0x8000..=0x9FFF).contains(&source);
+
+    match address {
+        0xFF00..=0xFFFF => false,
+        0xFE00..=0xFE9F => true,
+        0x8000..=0x9FFF => source_on_vram_bus,
+        _ => !source_on_vram_bus,
+    }
+}
@@ -0,0 +1,100 @@
+// Deep enough for any sane program, bounds runaway growth when code calls
+// and then resets SP without returning
+const MAX_DEPTH: usize = 1024;
+
+// Entry point of a routine. Only the switchable ROM area is banked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Function {
+    pub bank: usize,
+    pub address: u16,
+}
+
+impl Function {
+    pub fn new(address: u16, rom_bank: usize) -> Self {
+        let bank = if (0x4000..0x8000).contains(&address) {
+            rom_bank
+        } else {
+            0
+        };
+        Self { bank, address }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameKind {
+    Call,
+    Rst,
+    Interrupt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub function: Function,
+    pub return_address: u16,
+    pub sp: u16, // where the return address was pushed
+    pub kind: FrameKind,
+    pub entry: u64, // serial number of the call, unique per frame
+}
+
+// Shadow of the CPU stack, updated by CALL/RST/interrupt entries and
+// RET/RETI exits. Outermost frame first.
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    entries: u64,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn top(&self) -> Option<&Frame> {
+        self.frames.last()
+    }
+
+    // Calls made so far
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn enter(&mut self, function: Function, return_address: u16, sp: u16, kind: FrameKind) {
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.entries += 1;
+        self.frames.push(Frame {
+            function,
+            return_address,
+            sp,
+            kind,
+            entry: self.entries,
+        });
+    }
+
+    // A return is about to pop its address from `sp`
+    pub fn leave(&mut self, sp: u16) {
+        // Frames below SP were abandoned: stack reset, or the return
+        // address was popped by hand
+        while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+            self.frames.pop();
+        }
+        // Otherwise the RET consumes an address the code pushed itself
+        // ("PUSH HL; RET" jumps), which leaves the current frame alone
+        if self.frames.last().is_some_and(|frame| frame.sp == sp) {
+            self.frames.pop();
+        }
+    }
+}
@@ -1,4 +1,11 @@
-use crate::{debug, emulator::bus::Bus, info};
+use crate::{
+    debug,
+    emulator::{
+        bus::Bus,
+        call_stack::{CallStack, FrameKind, Function},
+    },
+    info,
+};
 
 const FLAG_Z: u8 = 0b10000000; // Zero
 const FLAG_N: u8 = 0b01000000; // Subtraction
@@ -35,6 +42,8 @@ pub struct CPU {
 
     // HALT state
     pub halted: bool,
+
+    pub call_stack: CallStack,
 }
 
 impl Default for CPU {
@@ -58,33 +67,34 @@ impl CPU {
             pc: 0x0100,
             ime: false,
             halted: false,
+            call_stack: CallStack::new(),
         }
     }
 
-    fn get_register(&self, index: u8) -> u8 {
-        match index {
+    // Operand from the 3 low bits of an opcode: B, C, D, E, H, L, (HL), A
+    fn get_register(&self, bus: &Bus, index: u8) -> u8 {
+        match index & 0x07 {
             0 => self.b,
             1 => self.c,
             2 => self.d,
             3 => self.e,
             4 => self.h,
             5 => self.l,
-            6 => panic!("Register index 6 should be handled as (HL) memory access"),
-            7 => self.a,
-            _ => panic!("Invalid index register {}", index),
+            6 => bus.read_byte(self.hl()),
+            _ => self.a,
         }
     }
 
-    fn set_register(&mut self, index: u8, value: u8) {
-        match index {
+    fn set_register(&mut self, bus: &mut Bus, index: u8, value: u8) {
+        match index & 0x07 {
             0 => self.b = value,
             1 => self.c = value,
             2 => self.d = value,
             3 => self.e = value,
             4 => self.h = value,
             5 => self.l = value,
-            7 => self.a = value,
-            _ => panic!("Invalid index register {}", index),
+            6 => bus.write_byte(self.hl(), value),
+            _ => self.a = value,
         }
     }
 
@@ -196,8 +206,26 @@ impl CPU {
         bus.write_byte(self.sp, value as u8);
     }
 
+    // CALL, RST and interrupt dispatch
+    fn call(&mut self, bus: &mut Bus, address: u16, kind: FrameKind) {
+        self.stack_push(bus, self.pc);
+        let function = Function::new(address, bus.memory.rom_bank());
+        self.call_stack.enter(function, self.pc, self.sp, kind);
+        self.pc = address;
+    }
+
+    fn ret(&mut self, bus: &mut Bus) {
+        self.call_stack.leave(self.sp);
+        self.pc = self.stack_pop(bus);
+    }
+
+    pub fn interrupt(&mut self, bus: &mut Bus, vector: u16) {
+        self.call(bus, vector, FrameKind::Interrupt);
+    }
+
     pub fn debug_flags(&self) {
         debug!(
+            Cpu,
             "F register: 0x{:02X} (Z:{} N:{} H:{} C:{} bits_bas:{:04b})",
             self.f,
             if self.flag_z() { 1 } else { 0 },
@@ -340,6 +368,7 @@ impl CPU {
             }
             0x03 => {
                 // INC BC
+                bus.trigger_oam_bug(self.bc());
                 let value = self.bc().wrapping_add(1);
                 self.set_bc(value);
 
@@ -422,6 +451,7 @@ impl CPU {
             }
             0x0B => {
                 // DEC BC
+                bus.trigger_oam_bug(self.bc());
                 let value = self.bc().wrapping_sub(1);
                 self.set_bc(value);
 
@@ -498,6 +528,7 @@ impl CPU {
             }
             0x13 => {
                 // INC DE - Increment 16bit DE register
+                bus.trigger_oam_bug(self.de());
                 let value = self.de().wrapping_add(1);
                 self.set_de(value);
 
@@ -583,6 +614,7 @@ impl CPU {
             }
             0x1B => {
                 // DEC DE
+                bus.trigger_oam_bug(self.de());
                 let value = self.de().wrapping_sub(1);
                 self.set_de(value);
 
@@ -670,6 +702,7 @@ impl CPU {
             }
             0x23 => {
                 // INC HL
+                bus.trigger_oam_bug(self.hl());
                 let value = self.hl().wrapping_add(1);
                 self.set_hl(value);
 
@@ -777,6 +810,7 @@ impl CPU {
             }
             0x2B => {
                 // DEC HL
+                bus.trigger_oam_bug(self.hl());
                 let value = self.hl().wrapping_sub(1);
                 self.set_hl(value);
 
@@ -858,6 +892,7 @@ impl CPU {
             }
             0x33 => {
                 // INC SP
+                bus.trigger_oam_bug(self.sp);
                 self.sp = self.sp.wrapping_add(1);
 
                 8
@@ -944,6 +979,7 @@ impl CPU {
             }
             0x3B => {
                 // DEC SP
+                bus.trigger_oam_bug(self.sp);
                 self.sp = self.sp.wrapping_sub(1);
 
                 8
@@ -997,41 +1033,26 @@ impl CPU {
                 // 0x76 = HALT
                 if opcode == 0x76 {
                     self.halted = true;
-                    info!("CPU HALT executed at PC: 0x{:04X}", self.pc.wrapping_sub(1));
+                    info!(
+                        Cpu,
+                        "CPU HALT executed at PC: 0x{:04X}",
+                        self.pc.wrapping_sub(1)
+                    );
                     return 4;
                 }
 
-                if dest_reg == 6 {
-                    let address = self.hl();
-                    let value = self.get_register(src_reg);
-                    bus.write_byte(address, value);
-                    return 8;
-                }
-
-                if src_reg == 6 {
-                    let address = self.hl();
-                    let value = bus.read_byte(address);
-                    self.set_register(dest_reg, value);
-                    return 8;
-                }
-
                 // LD r, r
-                let value = self.get_register(src_reg);
-                self.set_register(dest_reg, value);
+                let value = self.get_register(bus, src_reg);
+                self.set_register(bus, dest_reg, value);
 
-                4
+                if src_reg == 6 || dest_reg == 6 { 8 } else { 4 }
             }
             0x80..=0xBF => {
                 // ALU operations
                 let operation = (opcode >> 3) & 0x07;
                 let src_reg = opcode & 0x07;
 
-                let src_value = if src_reg == 6 {
-                    // HL case - memory access
-                    bus.read_byte(self.hl())
-                } else {
-                    self.get_register(src_reg)
-                };
+                let src_value = self.get_register(bus, src_reg);
 
                 match operation {
                     0 => self.alu_add(src_value),
@@ -1050,7 +1071,7 @@ impl CPU {
             0xC0 => {
                 // RET NZ
                 if !self.flag_z() {
-                    self.pc = self.stack_pop(bus);
+                    self.ret(bus);
                     20
                 } else {
                     8
@@ -1087,8 +1108,7 @@ impl CPU {
                 self.pc = self.pc.wrapping_add(2);
 
                 if !self.flag_z() {
-                    self.stack_push(bus, self.pc);
-                    self.pc = address;
+                    self.call(bus, address, FrameKind::Call);
                     24
                 } else {
                     12
@@ -1111,14 +1131,13 @@ impl CPU {
             }
             0xC7 => {
                 // RST 00H - Call address 0x0000
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0000;
+                self.call(bus, 0x0000, FrameKind::Rst);
                 16
             }
             0xC8 => {
                 // RET Z
                 if self.flag_z() {
-                    self.pc = self.stack_pop(bus);
+                    self.ret(bus);
                     20
                 } else {
                     8
@@ -1126,7 +1145,7 @@ impl CPU {
             }
             0xC9 => {
                 // RET - Return from function
-                self.pc = self.stack_pop(bus);
+                self.ret(bus);
 
                 16
             }
@@ -1154,8 +1173,7 @@ impl CPU {
                 self.pc = self.pc.wrapping_add(2);
 
                 if self.flag_z() {
-                    self.stack_push(bus, self.pc);
-                    self.pc = address;
+                    self.call(bus, address, FrameKind::Call);
                     24
                 } else {
                     12
@@ -1166,11 +1184,7 @@ impl CPU {
                 let address = bus.read_word(self.pc);
                 self.pc = self.pc.wrapping_add(2);
 
-                // store current address
-                self.stack_push(bus, self.pc);
-
-                // jump to function address
-                self.pc = address;
+                self.call(bus, address, FrameKind::Call);
 
                 24
             }
@@ -1184,14 +1198,13 @@ impl CPU {
             }
             0xCF => {
                 // RST 08H - Call address 0x0008
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0008;
+                self.call(bus, 0x0008, FrameKind::Rst);
                 16
             }
             0xD0 => {
                 // RET NC
                 if !self.flag_c() {
-                    self.pc = self.stack_pop(bus);
+                    self.ret(bus);
                     20
                 } else {
                     8
@@ -1225,8 +1238,7 @@ impl CPU {
                 self.pc = self.pc.wrapping_add(2);
 
                 if !self.flag_c() {
-                    self.stack_push(bus, self.pc);
-                    self.pc = address;
+                    self.call(bus, address, FrameKind::Call);
                     24
                 } else {
                     12
@@ -1249,14 +1261,13 @@ impl CPU {
             }
             0xD7 => {
                 // RST 10H - Call address 0x0010
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0010;
+                self.call(bus, 0x0010, FrameKind::Rst);
                 16
             }
             0xD8 => {
                 // RET C
                 if self.flag_c() {
-                    self.pc = self.stack_pop(bus);
+                    self.ret(bus);
                     20
                 } else {
                     8
@@ -1264,7 +1275,7 @@ impl CPU {
             }
             0xD9 => {
                 // RETI - Return from interrupt
-                self.pc = self.stack_pop(bus);
+                self.ret(bus);
                 self.ime = true;
 
                 16
@@ -1291,8 +1302,7 @@ impl CPU {
                 self.pc = self.pc.wrapping_add(2);
 
                 if self.flag_c() {
-                    self.stack_push(bus, self.pc);
-                    self.pc = address;
+                    self.call(bus, address, FrameKind::Call);
                     24
                 } else {
                     12
@@ -1312,8 +1322,7 @@ impl CPU {
             }
             0xDF => {
                 // RST 18H - Call address 0x0018
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0018;
+                self.call(bus, 0x0018, FrameKind::Rst);
                 16
             }
             0xE0 => {
@@ -1364,8 +1373,7 @@ impl CPU {
             }
             0xE7 => {
                 // RST 20H - Call address 0x0020
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0020;
+                self.call(bus, 0x0020, FrameKind::Rst);
                 16
             }
             0xE8 => {
@@ -1420,8 +1428,7 @@ impl CPU {
             }
             0xEF => {
                 // RST 28H - Call address 0x0028
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0028;
+                self.call(bus, 0x0028, FrameKind::Rst);
 
                 16
             }
@@ -1473,8 +1480,7 @@ impl CPU {
             }
             0xF7 => {
                 // RST 30H - Call address 0x0030
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0030;
+                self.call(bus, 0x0030, FrameKind::Rst);
                 16
             }
             0xF8 => {
@@ -1534,8 +1540,7 @@ impl CPU {
             }
             0xFF => {
                 // RST 38H - Call to 0x0038
-                self.stack_push(bus, self.pc);
-                self.pc = 0x0038;
+                self.call(bus, 0x0038, FrameKind::Rst);
                 16
             }
         }
@@ -2000,11 +2005,7 @@ impl CPU {
                 let bit_number = (cb_opcode >> 3) & 0x07;
                 let register = cb_opcode & 0x07;
 
-                let value = if register == 6 {
-                    bus.read_byte(self.hl())
-                } else {
-                    self.get_register(register)
-                };
+                let value = self.get_register(bus, register);
 
                 let bit_set = (value & (1 << bit_number)) != 0;
 
@@ -2021,16 +2022,10 @@ impl CPU {
                 let bit_number = (cb_opcode >> 3) & 0x07;
                 let register = cb_opcode & 0x07;
 
-                if register == 6 {
-                    let address = self.hl();
-                    let value = bus.read_byte(address);
-                    bus.write_byte(address, value & !(1 << bit_number));
-                    16
-                } else {
-                    let value = self.get_register(register);
-                    self.set_register(register, value & !(1 << bit_number));
-                    8
-                }
+                let value = self.get_register(bus, register) & !(1 << bit_number);
+                self.set_register(bus, register, value);
+
+                if register == 6 { 16 } else { 8 }
             }
 
             0xC0..=0xFF => {
@@ -2038,16 +2033,10 @@ impl CPU {
                 let bit_number = (cb_opcode >> 3) & 0x07;
                 let register = cb_opcode & 0x07;
 
-                if register == 6 {
-                    let address = self.hl();
-                    let value = bus.read_byte(address);
-                    bus.write_byte(address, value | (1 << bit_number));
-                    16
-                } else {
-                    let value = self.get_register(register) | (1 << bit_number);
-                    self.set_register(register, value);
-                    8
-                }
+                let value = self.get_register(bus, register) | (1 << bit_number);
+                self.set_register(bus, register, value);
+
+                if register == 6 { 16 } else { 8 }
             }
         }
     }
@@ -0,0 +1,474 @@
+use crate::emulator::{
+    cpu::CPU,
+    disassembler::{Flow, disassemble},
+    gameboy::{Gameboy, MAX_FRAME_STEPS, StopReason},
+};
+use std::fmt;
+
+// Step over/out and run-to-cursor give up after this many instructions
+pub const MAX_RUN_STEPS: u32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    BC,
+    DE,
+    HL,
+    SP,
+    PC,
+}
+
+impl Register {
+    pub const ALL: [Register; 14] = [
+        Register::A,
+        Register::F,
+        Register::B,
+        Register::C,
+        Register::D,
+        Register::E,
+        Register::H,
+        Register::L,
+        Register::AF,
+        Register::BC,
+        Register::DE,
+        Register::HL,
+        Register::SP,
+        Register::PC,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Register::A => "A",
+            Register::F => "F",
+            Register::B => "B",
+            Register::C => "C",
+            Register::D => "D",
+            Register::E => "E",
+            Register::H => "H",
+            Register::L => "L",
+            Register::AF => "AF",
+            Register::BC => "BC",
+            Register::DE => "DE",
+            Register::HL => "HL",
+            Register::SP => "SP",
+            Register::PC => "PC",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Register> {
+        Register::ALL
+            .into_iter()
+            .find(|r| r.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn read(&self, cpu: &CPU) -> u16 {
+        match self {
+            Register::A => cpu.a as u16,
+            Register::F => cpu.f as u16,
+            Register::B => cpu.b as u16,
+            Register::C => cpu.c as u16,
+            Register::D => cpu.d as u16,
+            Register::E => cpu.e as u16,
+            Register::H => cpu.h as u16,
+            Register::L => cpu.l as u16,
+            Register::AF => cpu.af(),
+            Register::BC => cpu.bc(),
+            Register::DE => cpu.de(),
+            Register::HL => cpu.hl(),
+            Register::SP => cpu.sp,
+            Register::PC => cpu.pc,
+        }
+    }
+
+    // 8-bit registers take the low byte of `value`
+    pub fn write(&self, cpu: &mut CPU, value: u16) {
+        match self {
+            Register::A => cpu.a = value as u8,
+            Register::F => cpu.f = value as u8 & 0xF0,
+            Register::B => cpu.b = value as u8,
+            Register::C => cpu.c = value as u8,
+            Register::D => cpu.d = value as u8,
+            Register::E => cpu.e = value as u8,
+            Register::H => cpu.h = value as u8,
+            Register::L => cpu.l = value as u8,
+            Register::AF => cpu.set_af(value),
+            Register::BC => cpu.set_bc(value),
+            Register::DE => cpu.set_de(value),
+            Register::HL => cpu.set_hl(value),
+            Register::SP => cpu.sp = value,
+            Register::PC => cpu.pc = value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    // Longest operators first so "<=" isn't read as "<"
+    const OPERATORS: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    pub fn symbol(&self) -> &'static str {
+        Self::OPERATORS
+            .iter()
+            .find(|(_, c)| c == self)
+            .map(|(s, _)| *s)
+            .unwrap()
+    }
+
+    fn apply(&self, left: u16, right: u16) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+// Register test such as "A == $10" or "HL >= 0xC000"
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Condition {
+    pub register: Register,
+    pub comparison: Comparison,
+    pub value: u16,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (register, comparison, value) = Comparison::OPERATORS
+            .iter()
+            .find_map(|(symbol, comparison)| {
+                text.split_once(symbol)
+                    .map(|(left, right)| (left, *comparison, right))
+            })
+            .ok_or(format!("no comparison in condition '{}'", text))?;
+
+        Ok(Self {
+            register: Register::from_name(register.trim())
+                .ok_or(format!("unknown register '{}'", register.trim()))?,
+            comparison,
+            value: parse_number(value.trim())?,
+        })
+    }
+
+    pub fn holds(&self, cpu: &CPU) -> bool {
+        self.comparison.apply(self.register.read(cpu), self.value)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ${:X}",
+            self.register.name(),
+            self.comparison.symbol(),
+            self.value
+        )
+    }
+}
+
+// "$C000", "0xC000" and "C000" are hexadecimal, "#49152" decimal
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let text = text.trim();
+    let parsed = if let Some(decimal) = text.strip_prefix('#') {
+        decimal.parse()
+    } else {
+        let hex = text
+            .strip_prefix('$')
+            .or_else(|| text.strip_prefix("0x"))
+            .or_else(|| text.strip_prefix("0X"))
+            .unwrap_or(text);
+        u16::from_str_radix(hex, 16)
+    };
+    parsed.map_err(|_| format!("invalid number '{}'", text))
+}
+
+// "C000" or "C000-C0FF", inclusive
+pub fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    match text.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_number(start)?, parse_number(end)?);
+            if start > end {
+                return Err(format!("empty range '{}'", text));
+            }
+            Ok((start, end))
+        }
+        None => parse_number(text).map(|address| (address, address)),
+    }
+}
+
+// Whether `address` is in `bank`: None matches any bank, and only the
+// switchable ROM area has banks
+pub fn in_bank(bank: Option<u8>, address: u16, rom_bank: usize) -> bool {
+    bank.is_none_or(|bank| !(0x4000..0x8000).contains(&address) || bank as usize == rom_bank)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub address: u16,
+    pub bank: Option<u8>, // None for every bank
+    pub condition: Option<Condition>,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WatchKind {
+    Read,
+    #[default]
+    Write,
+    Access, // read or write
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 3] = [WatchKind::Read, WatchKind::Write, WatchKind::Access];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchKind::Read => "Read",
+            WatchKind::Write => "Write",
+            WatchKind::Access => "Access",
+        }
+    }
+}
+
+// Checked by the Bus on CPU accesses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16, // inclusive
+    pub kind: WatchKind,
+    pub bank: Option<u8>, // None for every bank
+}
+
+impl Watchpoint {
+    pub fn new(start: u16, end: u16, kind: WatchKind) -> Self {
+        Self {
+            start,
+            end,
+            kind,
+            bank: None,
+        }
+    }
+
+    pub fn matches(&self, address: u16, write: bool, rom_bank: usize) -> bool {
+        let kind = match self.kind {
+            WatchKind::Read => !write,
+            WatchKind::Write => write,
+            WatchKind::Access => true,
+        };
+        kind && (self.start..=self.end).contains(&address) && in_bank(self.bank, address, rom_bank)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakReason {
+    Breakpoint(u16),
+    // Stops after the instruction at `pc` made the access
+    Watchpoint { pc: u16, hit: WatchHit },
+    // Step, step over/out or run to cursor finished
+    Step,
+    StepLimit,
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakReason::Breakpoint(address) => write!(f, "Breakpoint at ${:04X}", address),
+            BreakReason::Watchpoint { pc, hit } => write!(
+                f,
+                "Watchpoint: {} ${:02X} {} ${:04X} at PC ${:04X}",
+                if hit.write { "wrote" } else { "read" },
+                hit.value,
+                if hit.write { "to" } else { "from" },
+                hit.address,
+                pc
+            ),
+            BreakReason::Step => write!(f, "Stepped"),
+            BreakReason::StepLimit => {
+                write!(f, "Stopped after {} instructions", MAX_RUN_STEPS)
+            }
+        }
+    }
+}
+
+// Drives a Gameboy instruction by instruction, stopping on breakpoints and
+// on watchpoints reported by the Bus
+#[derive(Debug, Clone, Default)]
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    // Stopped on this breakpoint: don't hit it again when resuming
+    resume_pc: Option<u16>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Replaces any breakpoint already at `address` in `bank`
+    pub fn add_breakpoint(&mut self, address: u16, bank: Option<u8>, condition: Option<Condition>) {
+        self.remove_breakpoint(address, bank);
+        self.breakpoints.push(Breakpoint {
+            address,
+            bank,
+            condition,
+            enabled: true,
+        });
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16, bank: Option<u8>) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints
+            .retain(|b| b.address != address || b.bank != bank);
+        self.breakpoints.len() != count
+    }
+
+    pub fn toggle_breakpoint(&mut self, address: u16, bank: Option<u8>) {
+        if !self.remove_breakpoint(address, bank) {
+            self.add_breakpoint(address, bank, None);
+        }
+    }
+
+    pub fn has_breakpoint(&self, address: u16, bank: Option<u8>) -> bool {
+        self.breakpoints
+            .iter()
+            .any(|b| b.address == address && b.bank == bank)
+    }
+
+    // Running through the debugger is only needed with something to stop on
+    pub fn is_active(&self, gameboy: &Gameboy) -> bool {
+        !self.breakpoints.is_empty() || !gameboy.bus.watchpoints.is_empty()
+    }
+
+    fn breakpoint_hit(&self, gameboy: &Gameboy) -> bool {
+        let cpu = &gameboy.cpu;
+        let rom_bank = gameboy.bus.memory.rom_bank();
+        self.breakpoints.iter().any(|b| {
+            b.enabled
+                && b.address == cpu.pc
+                && in_bank(b.bank, cpu.pc, rom_bank)
+                && b.condition.is_none_or(|c| c.holds(cpu))
+        })
+    }
+
+    // Executes instructions until `done` holds after one of them, or a
+    // breakpoint, watchpoint or emulation error stops it. The first
+    // instruction runs even with a breakpoint on it when `force_first` is set.
+    fn run_until(
+        &mut self,
+        gameboy: &mut Gameboy,
+        max_steps: u32,
+        force_first: bool,
+        mut done: impl FnMut(&Gameboy, u8, bool) -> bool,
+    ) -> Option<StopReason> {
+        let mut skip_breakpoint = force_first || self.resume_pc == Some(gameboy.cpu.pc);
+        self.resume_pc = None;
+
+        for _ in 0..max_steps {
+            let pc = gameboy.cpu.pc;
+            if !skip_breakpoint && self.breakpoint_hit(gameboy) {
+                self.resume_pc = Some(pc);
+                return Some(BreakReason::Breakpoint(pc).into());
+            }
+            skip_breakpoint = false;
+
+            let opcode = gameboy.bus.peek(pc);
+            gameboy.bus.take_watch_hit();
+            let vblank = match gameboy.step() {
+                Ok(vblank) => vblank,
+                Err(reason) => return Some(reason),
+            };
+            if let Some(hit) = gameboy.bus.take_watch_hit() {
+                return Some(BreakReason::Watchpoint { pc, hit }.into());
+            }
+            if done(gameboy, opcode, vblank) {
+                return None;
+            }
+        }
+        Some(BreakReason::StepLimit.into())
+    }
+
+    // Runs to the next VBlank; None when the frame completed
+    pub fn run_frame(&mut self, gameboy: &mut Gameboy) -> Option<StopReason> {
+        match self.run_until(gameboy, MAX_FRAME_STEPS, false, |_, _, vblank| vblank) {
+            Some(StopReason::Break(BreakReason::StepLimit)) => None,
+            reason => reason,
+        }
+    }
+
+    // Runs until something stops it, at most `max_steps` instructions
+    pub fn resume(&mut self, gameboy: &mut Gameboy, max_steps: u32) -> StopReason {
+        self.run_until(gameboy, max_steps, false, |_, _, _| false)
+            .unwrap_or(BreakReason::StepLimit.into())
+    }
+
+    pub fn step_into(&mut self, gameboy: &mut Gameboy) -> StopReason {
+        self.run_until(gameboy, 1, true, |_, _, _| true)
+            .unwrap_or(BreakReason::Step.into())
+    }
+
+    // Runs a CALL/RST until it returns, other instructions are single-stepped
+    pub fn step_over(&mut self, gameboy: &mut Gameboy) -> StopReason {
+        let instruction = disassemble(&gameboy.bus, gameboy.cpu.pc);
+        if instruction.flow != Flow::Call {
+            return self.step_into(gameboy);
+        }
+
+        let return_address = instruction.next_address();
+        let sp = gameboy.cpu.sp;
+        self.run_until(gameboy, MAX_RUN_STEPS, true, |gb, _, _| {
+            gb.cpu.pc == return_address && gb.cpu.sp >= sp
+        })
+        .unwrap_or(BreakReason::Step.into())
+    }
+
+    // Runs until the current function returns to its caller
+    pub fn step_out(&mut self, gameboy: &mut Gameboy) -> StopReason {
+        let sp = gameboy.cpu.sp;
+        self.run_until(gameboy, MAX_RUN_STEPS, true, |gb, opcode, _| {
+            let is_return = matches!(opcode, 0xC0 | 0xC8 | 0xD0 | 0xD8 | 0xC9 | 0xD9);
+            is_return && gb.cpu.sp > sp
+        })
+        .unwrap_or(BreakReason::Step.into())
+    }
+
+    pub fn run_to(&mut self, gameboy: &mut Gameboy, address: u16) -> StopReason {
+        self.run_until(gameboy, MAX_RUN_STEPS, true, |gb, _, _| {
+            gb.cpu.pc == address
+        })
+        .unwrap_or(BreakReason::Step.into())
+    }
+}
@@ -0,0 +1,232 @@
+use crate::emulator::{bus::Bus, symbols::SymbolTable};
+
+const R: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+const RP: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const RP2: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CC: [&str; 4] = ["NZ", "Z", "NC", "C"];
+const ALU: [&str; 8] = [
+    "ADD A,", "ADC A,", "SUB", "SBC A,", "AND", "XOR", "OR", "CP",
+];
+const ROT: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Normal,
+    Jump,   // JP, JR (taken or not)
+    Call,   // CALL, RST
+    Return, // RET, RETI
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String, // RGBDS syntax
+    pub flow: Flow,
+    // Absolute address operand: jump/call target or memory location
+    pub target: Option<u16>,
+}
+
+impl Instruction {
+    pub fn length(&self) -> u16 {
+        self.bytes.len() as u16
+    }
+
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.length())
+    }
+
+    // Text with the target address replaced by its label, if it has one
+    pub fn text_with_symbols(&self, symbols: &SymbolTable, rom_bank: usize) -> String {
+        match self
+            .target
+            .and_then(|target| Some((target, symbols.label_at(target, rom_bank)?)))
+        {
+            Some((target, label)) => self.text.replace(&format!("${:04X}", target), label),
+            None => self.text.clone(),
+        }
+    }
+}
+
+pub fn disassemble(bus: &Bus, address: u16) -> Instruction {
+    decode(&|a| bus.peek(a), address)
+}
+
+pub fn decode(read: &dyn Fn(u16) -> u8, address: u16) -> Instruction {
+    let opcode = read(address);
+    let d8 = read(address.wrapping_add(1));
+    let d16 = u16::from_le_bytes([d8, read(address.wrapping_add(2))]);
+    // JR target, relative to the next instruction
+    let relative = address.wrapping_add(2).wrapping_add(d8 as i8 as u16);
+
+    let x = opcode >> 6;
+    let y = ((opcode >> 3) & 0x07) as usize;
+    let z = opcode & 0x07;
+    let p = y >> 1;
+    let q = y & 1;
+
+    // (length, text, flow, target)
+    let (length, text, flow, target): (u16, String, Flow, Option<u16>) = match (x, z) {
+        (0, 0) => match y {
+            0 => (1, "NOP".into(), Flow::Normal, None),
+            1 => (3, format!("LD (${:04X}),SP", d16), Flow::Normal, Some(d16)),
+            2 => (2, "STOP".into(), Flow::Normal, None),
+            3 => (
+                2,
+                format!("JR ${:04X}", relative),
+                Flow::Jump,
+                Some(relative),
+            ),
+            _ => (
+                2,
+                format!("JR {},${:04X}", CC[y - 4], relative),
+                Flow::Jump,
+                Some(relative),
+            ),
+        },
+        (0, 1) if q == 0 => (3, format!("LD {},${:04X}", RP[p], d16), Flow::Normal, None),
+        (0, 1) => (1, format!("ADD HL,{}", RP[p]), Flow::Normal, None),
+        (0, 2) => {
+            let pointer = ["(BC)", "(DE)", "(HL+)", "(HL-)"][p];
+            let text = if q == 0 {
+                format!("LD {},A", pointer)
+            } else {
+                format!("LD A,{}", pointer)
+            };
+            (1, text, Flow::Normal, None)
+        }
+        (0, 3) => {
+            let op = if q == 0 { "INC" } else { "DEC" };
+            (1, format!("{} {}", op, RP[p]), Flow::Normal, None)
+        }
+        (0, 4) => (1, format!("INC {}", R[y]), Flow::Normal, None),
+        (0, 5) => (1, format!("DEC {}", R[y]), Flow::Normal, None),
+        (0, 6) => (2, format!("LD {},${:02X}", R[y], d8), Flow::Normal, None),
+        (0, _) => {
+            let op = ["RLCA", "RRCA", "RLA", "RRA", "DAA", "CPL", "SCF", "CCF"][y];
+            (1, op.into(), Flow::Normal, None)
+        }
+        (1, _) if opcode == 0x76 => (1, "HALT".into(), Flow::Normal, None),
+        (1, _) => (
+            1,
+            format!("LD {},{}", R[y], R[z as usize]),
+            Flow::Normal,
+            None,
+        ),
+        (2, _) => (
+            1,
+            format!("{} {}", ALU[y], R[z as usize]),
+            Flow::Normal,
+            None,
+        ),
+        (_, 0) => match y {
+            0..=3 => (1, format!("RET {}", CC[y]), Flow::Return, None),
+            4 => {
+                let io = 0xFF00 | d8 as u16;
+                (2, format!("LDH (${:04X}),A", io), Flow::Normal, Some(io))
+            }
+            5 => (2, format!("ADD SP,{}", d8 as i8), Flow::Normal, None),
+            6 => {
+                let io = 0xFF00 | d8 as u16;
+                (2, format!("LDH A,(${:04X})", io), Flow::Normal, Some(io))
+            }
+            _ => (2, format!("LD HL,SP{:+}", d8 as i8), Flow::Normal, None),
+        },
+        (_, 1) if q == 0 => (1, format!("POP {}", RP2[p]), Flow::Normal, None),
+        (_, 1) => match p {
+            0 => (1, "RET".into(), Flow::Return, None),
+            1 => (1, "RETI".into(), Flow::Return, None),
+            2 => (1, "JP HL".into(), Flow::Jump, None),
+            _ => (1, "LD SP,HL".into(), Flow::Normal, None),
+        },
+        (_, 2) => match y {
+            0..=3 => (
+                3,
+                format!("JP {},${:04X}", CC[y], d16),
+                Flow::Jump,
+                Some(d16),
+            ),
+            4 => (1, "LDH (C),A".into(), Flow::Normal, None),
+            5 => (3, format!("LD (${:04X}),A", d16), Flow::Normal, Some(d16)),
+            6 => (1, "LDH A,(C)".into(), Flow::Normal, None),
+            _ => (3, format!("LD A,(${:04X})", d16), Flow::Normal, Some(d16)),
+        },
+        (_, 3) => match y {
+            0 => (3, format!("JP ${:04X}", d16), Flow::Jump, Some(d16)),
+            1 => (2, decode_cb(d8), Flow::Normal, None),
+            6 => (1, "DI".into(), Flow::Normal, None),
+            7 => (1, "EI".into(), Flow::Normal, None),
+            _ => invalid(opcode),
+        },
+        (_, 4) if y < 4 => (
+            3,
+            format!("CALL {},${:04X}", CC[y], d16),
+            Flow::Call,
+            Some(d16),
+        ),
+        (_, 4) => invalid(opcode),
+        (_, 5) if q == 0 => (1, format!("PUSH {}", RP2[p]), Flow::Normal, None),
+        (_, 5) if p == 0 => (3, format!("CALL ${:04X}", d16), Flow::Call, Some(d16)),
+        (_, 5) => invalid(opcode),
+        (_, 6) => (2, format!("{} ${:02X}", ALU[y], d8), Flow::Normal, None),
+        (_, _) => {
+            let vector = (y * 8) as u16;
+            (1, format!("RST ${:02X}", vector), Flow::Call, Some(vector))
+        }
+    };
+
+    Instruction {
+        address,
+        bytes: (0..length).map(|i| read(address.wrapping_add(i))).collect(),
+        text,
+        flow,
+        target,
+    }
+}
+
+fn decode_cb(opcode: u8) -> String {
+    let y = (opcode >> 3) & 0x07;
+    let r = R[(opcode & 0x07) as usize];
+    match opcode >> 6 {
+        0 => format!("{} {}", ROT[y as usize], r),
+        1 => format!("BIT {},{}", y, r),
+        2 => format!("RES {},{}", y, r),
+        _ => format!("SET {},{}", y, r),
+    }
+}
+
+fn invalid(opcode: u8) -> (u16, String, Flow, Option<u16>) {
+    (1, format!("DB ${:02X}", opcode), Flow::Normal, None)
+}
+
+// `count` instructions starting at `address`
+pub fn disassemble_range(bus: &Bus, address: u16, count: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::with_capacity(count);
+    let mut address = address;
+    for _ in 0..count {
+        let instruction = disassemble(bus, address);
+        address = instruction.next_address();
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+// Code can't be decoded backwards, so the listing starts at the furthest
+// point before `pc` whose instruction stream lands exactly on it
+pub fn disassemble_around(bus: &Bus, pc: u16, before: usize, after: usize) -> Vec<Instruction> {
+    for distance in (1..=before as u16 * 3).rev() {
+        let start = pc.wrapping_sub(distance);
+        let mut lines = Vec::new();
+        let mut address = start;
+        while lines.len() <= before && address != pc && pc.wrapping_sub(address) <= distance {
+            let instruction = disassemble(bus, address);
+            address = instruction.next_address();
+            lines.push(instruction);
+        }
+        if address == pc && lines.len() <= before {
+            lines.extend(disassemble_range(bus, pc, after + 1));
+            return lines;
+        }
+    }
+    disassemble_range(bus, pc, after + 1)
+}
@@ -0,0 +1,105 @@
+const DMA_LENGTH: u8 = 0xA0; // 160 bytes, one per M-cycle
+const DMA_STARTUP_DELAY: u8 = 1; // M-cycles between the 0xFF46 write and the first copy
+
+#[derive(Debug, Clone)]
+pub struct Dma {
+    pub register: u8, // 0xFF46 - last written value
+
+    active: bool,
+    source: u16,
+    index: u8,
+
+    // Transfer requested by a write, still in its startup delay.
+    // An already running transfer keeps going until this one takes over.
+    pending: Option<(u16, u8)>,
+
+    // Byte currently on the bus, returned to the CPU on bus conflicts
+    last_byte: u8,
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Dma {
+    pub fn new() -> Self {
+        Self {
+            register: 0xFF,
+            active: false,
+            source: 0,
+            index: 0,
+            pending: None,
+            last_byte: 0xFF,
+        }
+    }
+
+    pub fn read_register(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write_register(&mut self, value: u8) {
+        self.register = value;
+
+        // 0xE000-0xFFFF is not wired to OAM/HRAM on DMG: the external bus
+        // only decodes 13 bits there, so those pages mirror WRAM.
+        let mut source = (value as u16) << 8;
+        if source >= 0xE000 {
+            source -= 0x2000;
+        }
+
+        self.pending = Some((source, DMA_STARTUP_DELAY));
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // Running or about to start
+    pub fn is_busy(&self) -> bool {
+        self.active || self.pending.is_some()
+    }
+
+    pub fn source(&self) -> u16 {
+        self.source
+    }
+
+    pub fn last_byte(&self) -> u8 {
+        self.last_byte
+    }
+
+    /// Advance the controller by one M-cycle. Returns the `(source, destination)`
+    /// pair the bus must copy during this cycle, if any.
+    ///
+    /// The bus steps it after each instruction. CPU accesses in the middle of
+    /// an instruction see the state at their own M-cycle through
+    /// `Bus::dma_state_at`; only the OAM copies land at the end.
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        if let Some((source, delay)) = self.pending {
+            if delay == 0 {
+                self.active = true;
+                self.source = source;
+                self.index = 0;
+                self.pending = None;
+            } else {
+                self.pending = Some((source, delay - 1));
+            }
+        }
+
+        if self.active {
+            let offset = self.index as u16;
+            self.index += 1;
+            if self.index == DMA_LENGTH {
+                self.active = false;
+            }
+            Some((self.source + offset, 0xFE00 + offset))
+        } else {
+            None
+        }
+    }
+
+    pub fn set_last_byte(&mut self, value: u8) {
+        self.last_byte = value;
+    }
+}
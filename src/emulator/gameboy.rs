@@ -1,5 +1,55 @@
-use crate::emulator::{bus::Bus, cpu::CPU, joypad::JoypadButton};
-use crate::{debug, error, print_cpu_state, print_ppu_state};
+use crate::debug;
+use crate::emulator::{
+    bus::Bus, cpu::CPU, debugger::BreakReason, joypad::JoypadButton, profiler::Profiler,
+};
+use std::fmt;
+
+// Cap on instructions per frame, should VBlank never come (LCD off)
+pub const MAX_FRAME_STEPS: u32 = 70000;
+// Same PC this many times in a row with no interrupt able to leave it
+const LOOP_THRESHOLD: u32 = 1000;
+// Opcodes that hang the CPU until reset
+const ILLEGAL_OPCODES: [u8; 11] = [
+    0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+];
+
+// Why execution stopped before the end of a step or frame. The Gameboy is
+// left as it was, so callers can show the state, fix it up and go on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    // Reported by the debugger
+    Break(BreakReason),
+    // Illegal opcode at `pc`, which stays on it
+    LockUp { pc: u16, opcode: u8 },
+    // PC in the unusable 0xFEA0-0xFEFF area
+    InvalidPc(u16),
+    LoopDetected(u16),
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Break(reason) => reason.fmt(f),
+            StopReason::LockUp { pc, opcode } => write!(
+                f,
+                "CPU locked up: illegal opcode ${:02X} at ${:04X}",
+                opcode, pc
+            ),
+            StopReason::InvalidPc(pc) => write!(f, "PC in unusable memory: ${:04X}", pc),
+            StopReason::LoopDetected(pc) => write!(
+                f,
+                "Infinite loop at ${:04X} with no interrupt to leave it",
+                pc
+            ),
+        }
+    }
+}
+
+impl From<BreakReason> for StopReason {
+    fn from(reason: BreakReason) -> Self {
+        StopReason::Break(reason)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Gameboy {
@@ -7,6 +57,8 @@ pub struct Gameboy {
     pub bus: Bus,
     pub last_pc: u16,
     pub pc_repeat_count: u32,
+    pub total_cycles: u64, // T-cycles since power on
+    pub profiler: Option<Profiler>,
 }
 
 impl Gameboy {
@@ -16,6 +68,8 @@ impl Gameboy {
             bus: Bus::new(),
             last_pc: 0,
             pc_repeat_count: 0,
+            total_cycles: 0,
+            profiler: None,
         }
     }
 
@@ -23,25 +77,35 @@ impl Gameboy {
         self.bus.load_rom(rom_data)
     }
 
-    fn validate_pc(&self) {
+    fn validate_pc(&self) -> Result<(), StopReason> {
         match self.cpu.pc {
-            0x0000..=0x7FFF => {} // ROM - OK
-            0x8000..=0x9FFF => {} // VRAM - OK
-            0xA000..=0xBFFF => {} // External RAM - OK
-            0xC000..=0xFDFF => {} // WRAM - OK
-            0xFE00..=0xFE9F => {} // OAM - OK
-            0xFF00..=0xFF7F => {} // I/O Registers - OK
-            0xFF80..=0xFFFE => {} // HRAM - OK
-            0xFFFF => {}          // IE Register - OK
-            _ => {
-                error!("ERROR: PC in invalid zone: 0x{:04X}", self.cpu.pc);
-                print_cpu_state!(self.cpu);
-                print_ppu_state!(self.bus.ppu);
-                panic!("PC in invalid memory zone");
-            }
+            0xFEA0..=0xFEFF => Err(StopReason::InvalidPc(self.cpu.pc)),
+            _ => Ok(()),
         }
     }
 
+    // A jump to itself only hangs when no interrupt can be taken
+    fn check_loop(&mut self) -> Result<(), StopReason> {
+        if self.cpu.pc != self.last_pc {
+            self.last_pc = self.cpu.pc;
+            self.pc_repeat_count = 0;
+            return Ok(());
+        }
+
+        let interruptible = self.cpu.interrupts_enabled() && self.bus.read_byte(0xFFFF) & 0x1F != 0;
+        if interruptible {
+            self.pc_repeat_count = 0;
+            return Ok(());
+        }
+
+        self.pc_repeat_count += 1;
+        if self.pc_repeat_count >= LOOP_THRESHOLD {
+            self.pc_repeat_count = 0;
+            return Err(StopReason::LoopDetected(self.cpu.pc));
+        }
+        Ok(())
+    }
+
     pub fn handle_input(&mut self, button: JoypadButton, pressed: bool) {
         self.bus.set_joypad_input(button, pressed);
 
@@ -51,47 +115,56 @@ impl Gameboy {
         }
     }
 
-    pub fn step(&mut self) -> bool {
-        if self.cpu.pc == self.last_pc {
-            self.pc_repeat_count += 1;
-            if self.pc_repeat_count > 1000 {
-                println!("\n=== INFINITE LOOP DETECTED ===");
-                println!("PC at: 0x{:04X}", self.cpu.pc);
-                return true;
-            }
-        } else {
-            self.last_pc = self.cpu.pc;
-            self.pc_repeat_count = 0;
-        }
-
-        // if let Some(data) = self.get_serial_output() {
-        //     let ch = data as char;
-        //     if ch == '\n' || ch == ' ' {
-        //         println!();
-        //     } else {
-        //         print!("{}", ch);
-        //     }
-        // }
+    // Runs one instruction, true when it ended on VBlank
+    pub fn step(&mut self) -> Result<bool, StopReason> {
+        self.check_loop()?;
+        self.validate_pc()?;
 
-        self.validate_pc();
+        let profile_node = self
+            .profiler
+            .as_mut()
+            .map(|profiler| profiler.node(&self.cpu.call_stack));
 
+        self.bus.begin_instruction();
         let opcode = self.bus.read_byte(self.cpu.pc);
+        if !self.cpu.halted && ILLEGAL_OPCODES.contains(&opcode) {
+            self.bus.end_instruction();
+            return Err(StopReason::LockUp {
+                pc: self.cpu.pc,
+                opcode,
+            });
+        }
         self.cpu.pc = self.cpu.pc.wrapping_add(1);
         let cycles = self.cpu.execute_instruction(opcode, &mut self.bus);
+        self.bus.end_instruction();
+        self.total_cycles += cycles as u64;
 
-        let timer_interrupt = self.bus.timer_step(cycles);
+        if let (Some(profiler), Some(node)) = (self.profiler.as_mut(), profile_node) {
+            profiler.add_cycles(node, cycles as u64);
+        }
+
+        self.bus.dma_step(cycles);
+
+        // Timer counts M-cycles
+        let timer_interrupt = self.bus.timer_step(cycles / 4);
         if timer_interrupt {
             let if_reg = self.bus.read_byte(0xFF0F);
             self.bus.write_byte(0xFF0F, if_reg | 0x04);
         }
 
+        if self.bus.serial_step(cycles / 4) {
+            let if_reg = self.bus.read_byte(0xFF0F);
+            self.bus.write_byte(0xFF0F, if_reg | 0x08);
+        }
+
         let vblank_interrupt = self.bus.ppu_step(cycles);
 
-        self.bus.apu_step(cycles);
+        // APU counts M-cycles
+        self.bus.apu_step(cycles / 4);
 
         self.handle_interrupts(vblank_interrupt, timer_interrupt);
 
-        vblank_interrupt
+        Ok(vblank_interrupt)
     }
 
     fn handle_interrupts(&mut self, vblank: bool, timer: bool) {
@@ -105,7 +178,7 @@ impl Gameboy {
         if vblank {
             let ie_reg = self.bus.read_byte(0xFFFF);
             if (ie_reg & 0x01) != 0 {
-                debug!("🔥 Handling VBlank interrupt");
+                debug!(Cpu, "🔥 Handling VBlank interrupt");
                 self.handle_interrupt(0x40, 0x01);
                 interrupt_triggered = true;
             }
@@ -115,7 +188,7 @@ impl Gameboy {
         if timer && !interrupt_triggered {
             let ie_reg = self.bus.read_byte(0xFFFF);
             if (ie_reg & 0x04) != 0 {
-                debug!("🔥 Handling Timer interrupt");
+                debug!(Cpu, "🔥 Handling Timer interrupt");
                 self.handle_interrupt(0x50, 0x04);
                 interrupt_triggered = true;
             }
@@ -129,15 +202,15 @@ impl Gameboy {
 
             if pending & 0x02 != 0 {
                 // LCD STAT interrupt
-                debug!("🔥 Handling LCD STAT interrupt");
+                debug!(Cpu, "🔥 Handling LCD STAT interrupt");
                 self.handle_interrupt(0x48, 0x02);
             } else if pending & 0x08 != 0 {
                 // Serial interrupt
-                debug!("🔥 Handling Serial interrupt");
+                debug!(Cpu, "🔥 Handling Serial interrupt");
                 self.handle_interrupt(0x58, 0x08);
             } else if pending & 0x10 != 0 {
                 // Joypad interrupt
-                debug!("🔥 Handling Joypad interrupt");
+                debug!(Cpu, "🔥 Handling Joypad interrupt");
                 self.handle_interrupt(0x60, 0x10);
             }
         }
@@ -145,30 +218,28 @@ impl Gameboy {
 
     fn handle_interrupt(&mut self, vector: u16, flag_bit: u8) {
         self.cpu.disable_interrupts();
-        self.cpu.stack_push(&mut self.bus, self.cpu.pc);
-        self.cpu.pc = vector;
+        self.cpu.interrupt(&mut self.bus, vector);
 
         let if_reg = self.bus.read_byte(0xFF0F);
         self.bus.write_byte(0xFF0F, if_reg & !flag_bit);
     }
 
-    pub fn run_frame(&mut self) -> u32 {
+    pub fn run_frame(&mut self) -> Result<u32, StopReason> {
         let mut cycles = 0;
         loop {
-            let vblank = self.step();
+            let vblank = self.step()?;
             cycles += 1;
 
             if vblank {
                 break;
             }
 
-            //avoid infinite loop protection
-            if cycles > 70000 {
+            if cycles >= MAX_FRAME_STEPS {
                 break;
             }
         }
 
-        cycles
+        Ok(cycles)
     }
 
     pub fn take_audio_samples(&mut self) -> Vec<(f32, f32)> {
@@ -179,17 +250,6 @@ impl Gameboy {
         &self.bus.ppu.framebuffer
     }
 
-    pub fn get_serial_output(&mut self) -> Option<u8> {
-        if self.bus.read_byte(0xFF02) & 0x80 != 0 {
-            let data = self.bus.read_byte(0xFF01);
-            self.bus
-                .write_byte(0xFF02, self.bus.read_byte(0xFF02) & 0x7F);
-            Some(data)
-        } else {
-            None
-        }
-    }
-
     pub fn print_debug_screen(&self) {
         self.bus.ppu.print_screen();
     }
@@ -0,0 +1,294 @@
+use crate::emulator::{apu::CPU_CLOCK, gameboy::Gameboy, memory::Mapper};
+
+const HEADER_SIZE: usize = 0x70;
+const FRAME_CYCLES: u64 = 70224; // one LCD frame, the VBlank play rate
+
+// Return address for the init call: an EI / JR -3 loop in the unused space
+// below the load address, so the CPU idles there waiting for play interrupts
+const RETURN_TRAP: u16 = 0x0070;
+const IDLE_LOOP: [u8; 3] = [0xFB, 0x18, 0xFD];
+
+const VBLANK_VECTOR: u16 = 0x40;
+const TIMER_VECTOR: u16 = 0x50;
+
+// A routine taking longer than this is considered stuck
+const MAX_CALL_STEPS: u32 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GbsHeader {
+    pub version: u8,
+    pub song_count: u8,
+    pub first_song: u8, // 1-based
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    pub timer_modulo: u8,
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+// GBS drivers reach data past 32 KiB by writing a bank number to
+// 0x2000-0x3FFF, MBC1 style (0 meaning 1). The rest of the ROM is read-only.
+#[derive(Debug, Clone)]
+struct GbsMapper {
+    rom: Vec<u8>, // whole 16 KiB banks
+    bank: usize,
+}
+
+impl GbsMapper {
+    fn new(rom: &[u8]) -> Self {
+        let mut rom = rom.to_vec();
+        rom.resize(rom.len().next_multiple_of(0x4000), 0xFF);
+        Self { rom, bank: 1 }
+    }
+}
+
+impl Mapper for GbsMapper {
+    fn write(&mut self, address: u16, value: u8) -> Option<&[u8]> {
+        if !(0x2000..=0x3FFF).contains(&address) {
+            return None;
+        }
+        let bank_count = self.rom.len() / 0x4000;
+        self.bank = ((value as usize) % bank_count).max(1);
+        Some(&self.rom[self.bank * 0x4000..(self.bank + 1) * 0x4000])
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.bank
+    }
+
+    fn clone_box(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
+
+// Game Boy Sound file: a header followed by the driver and music data, loaded
+// into a flat ROM image at `load_address`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GbsFile {
+    pub header: GbsHeader,
+    pub rom: Vec<u8>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+impl GbsFile {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE || &data[0..3] != b"GBS" {
+            return Err("not a GBS file".to_string());
+        }
+
+        let header = GbsHeader {
+            version: data[0x03],
+            song_count: data[0x04],
+            first_song: data[0x05],
+            load_address: read_u16(data, 0x06),
+            init_address: read_u16(data, 0x08),
+            play_address: read_u16(data, 0x0A),
+            stack_pointer: read_u16(data, 0x0C),
+            timer_modulo: data[0x0E],
+            timer_control: data[0x0F],
+            title: read_string(&data[0x10..0x30]),
+            author: read_string(&data[0x30..0x50]),
+            copyright: read_string(&data[0x50..0x70]),
+        };
+
+        if header.version != 1 {
+            return Err(format!("unsupported GBS version {}", header.version));
+        }
+        if header.song_count == 0 {
+            return Err("GBS file has no songs".to_string());
+        }
+        if !(0x0400..0x8000).contains(&header.load_address) {
+            return Err(format!(
+                "invalid load address 0x{:04X}",
+                header.load_address
+            ));
+        }
+
+        let load = header.load_address as usize;
+        let mut rom = vec![0u8; load];
+        rom.extend_from_slice(&data[HEADER_SIZE..]);
+        if rom.len() < 0x8000 {
+            rom.resize(0x8000, 0xFF);
+        }
+
+        // RST n and the interrupt vectors jump to load_address + n in GBS
+        // drivers, except the one driving play, which calls it and returns
+        let play_vector = if header.timer_control & 0x04 != 0 {
+            TIMER_VECTOR
+        } else {
+            VBLANK_VECTOR
+        };
+        for vector in (0..=0x60).step_by(8) {
+            let (opcode, target) = if vector == play_vector as usize {
+                (0xCD, header.play_address)
+            } else {
+                (0xC3, header.load_address + vector as u16)
+            };
+            rom[vector..vector + 3].copy_from_slice(&[opcode, target as u8, (target >> 8) as u8]);
+        }
+        let reti = play_vector as usize + 3;
+        rom[reti] = 0xD9;
+        let trap = RETURN_TRAP as usize;
+        rom[trap..trap + IDLE_LOOP.len()].copy_from_slice(&IDLE_LOOP);
+
+        Ok(Self { header, rom })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&data).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Play is driven by the timer interrupt when TAC bit 2 is set, by the
+    // VBlank interrupt otherwise
+    fn play_interrupt(&self) -> u8 {
+        if self.header.timer_control & 0x04 != 0 {
+            0x04
+        } else {
+            0x01
+        }
+    }
+
+    // T-cycles between play calls
+    pub fn play_period(&self) -> u64 {
+        let tac = self.header.timer_control;
+        if tac & 0x04 == 0 {
+            return FRAME_CYCLES;
+        }
+
+        let input_clock: u64 = match tac & 0x03 {
+            0 => 1024,
+            1 => 16,
+            2 => 64,
+            _ => 256,
+        };
+        let period = (256 - self.header.timer_modulo as u64) * input_clock;
+        // Bit 7 asks for CGB double speed, the timer then runs twice as fast
+        if tac & 0x80 != 0 { period / 2 } else { period }
+    }
+
+    pub fn play_rate_hz(&self) -> f32 {
+        CPU_CLOCK as f32 / self.play_period() as f32
+    }
+}
+
+// Drives a Gameboy as a sound player: calls the driver's init routine for the
+// selected song, then lets the VBlank or timer interrupt call its play routine
+#[derive(Debug, Clone)]
+pub struct GbsPlayer {
+    pub file: GbsFile,
+    song: u8,       // 0-based
+    frame_end: u64, // fixed frame grid, so overshoot doesn't accumulate
+}
+
+impl GbsPlayer {
+    pub fn new(file: GbsFile) -> Self {
+        let song = file.header.first_song.saturating_sub(1);
+        Self {
+            file,
+            song,
+            frame_end: 0,
+        }
+    }
+
+    pub fn song(&self) -> u8 {
+        self.song
+    }
+
+    pub fn song_count(&self) -> u8 {
+        self.file.header.song_count
+    }
+
+    // Resets `gameboy` into the player environment and initialises `song`
+    pub fn start_song(&mut self, gameboy: &mut Gameboy, song: u8) -> Result<(), String> {
+        if song >= self.song_count() {
+            return Err(format!(
+                "song {} out of range (1-{})",
+                song + 1,
+                self.song_count()
+            ));
+        }
+
+        // Output settings belong to the host, keep them across the reset
+        let apu = &gameboy.bus.apu;
+        let (sample_rate, stems, muted, high_pass) = (
+            apu.sample_rate(),
+            apu.stems_enabled(),
+            apu.muted,
+            apu.high_pass_enabled,
+        );
+        *gameboy = Gameboy::new();
+        let apu = &mut gameboy.bus.apu;
+        apu.set_sample_rate(sample_rate);
+        apu.set_stems_enabled(stems);
+        apu.muted = muted;
+        apu.high_pass_enabled = high_pass;
+        gameboy.load_rom(&self.file.rom)?;
+        gameboy
+            .bus
+            .memory
+            .set_mapper(Box::new(GbsMapper::new(&self.file.rom)));
+
+        let header = &self.file.header;
+        let bus = &mut gameboy.bus;
+        bus.write_byte(0xFF26, 0x80);
+        bus.write_byte(0xFF24, 0x77);
+        bus.write_byte(0xFF25, 0xFF);
+        bus.write_byte(0xFF05, header.timer_modulo);
+        bus.write_byte(0xFF06, header.timer_modulo);
+        bus.write_byte(0xFF07, header.timer_control);
+        // No interrupts during init
+        bus.write_byte(0xFFFF, 0x00);
+        bus.write_byte(0xFF0F, 0x00);
+
+        gameboy.cpu.sp = header.stack_pointer;
+        self.song = song;
+        self.call(gameboy, header.init_address, song)?;
+
+        // The idle loop enables interrupts, play runs from its vector
+        let bus = &mut gameboy.bus;
+        let enabled = bus.read_byte(0xFFFF);
+        bus.write_byte(0xFFFF, enabled | self.file.play_interrupt());
+        bus.write_byte(0xFF0F, 0x00);
+        self.frame_end = gameboy.total_cycles;
+        Ok(())
+    }
+
+    // Emulates one LCD frame worth of time
+    pub fn run_frame(&mut self, gameboy: &mut Gameboy) -> Result<(), String> {
+        self.frame_end += FRAME_CYCLES;
+
+        while gameboy.total_cycles < self.frame_end {
+            gameboy.step().map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    // Calls `address` with A = song and waits for it to return to the trap
+    fn call(&self, gameboy: &mut Gameboy, address: u16, song: u8) -> Result<(), String> {
+        gameboy.cpu.a = song;
+        gameboy.cpu.pc = RETURN_TRAP;
+        gameboy.cpu.stack_push(&mut gameboy.bus, RETURN_TRAP);
+        gameboy.cpu.pc = address;
+
+        for _ in 0..MAX_CALL_STEPS {
+            if gameboy.cpu.pc == RETURN_TRAP {
+                return Ok(());
+            }
+            gameboy.step().map_err(|e| e.to_string())?;
+        }
+        Err(format!("routine at 0x{:04X} did not return", address))
+    }
+}
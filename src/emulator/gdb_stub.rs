@@ -0,0 +1,474 @@
+use crate::emulator::{
+    debugger::{BreakReason, Debugger, Register, WatchKind, Watchpoint},
+    gameboy::{Gameboy, StopReason},
+};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// Instructions run between checks for a ^C from the client
+const CONTINUE_CHUNK: u32 = 10_000;
+const PACKET_SIZE: usize = 0x1000;
+
+// GDB register numbers, each 16 bits little-endian
+const REGISTERS: [Register; 6] = [
+    Register::AF,
+    Register::BC,
+    Register::DE,
+    Register::HL,
+    Register::SP,
+    Register::PC,
+];
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.cpu">
+    <reg name="af" bitsize="16" type="int" regnum="0"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="int"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+// Signals in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionEnd {
+    Detached, // D or the client went away: emulation may go on
+    Killed,   // k
+}
+
+pub struct GdbServer {
+    listener: TcpListener,
+}
+
+impl GdbServer {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        TcpListener::bind(address)
+            .map(|listener| Self { listener })
+            .map_err(|e| format!("{}: {}", address, e))
+    }
+
+    pub fn local_address(&self) -> String {
+        self.listener
+            .local_addr()
+            .map(|a| a.to_string())
+            .unwrap_or_default()
+    }
+
+    // Blocks until a client connects
+    pub fn accept(&self) -> Result<GdbStub, String> {
+        let (stream, _) = self.listener.accept().map_err(|e| e.to_string())?;
+        stream.set_nodelay(true).map_err(|e| e.to_string())?;
+        Ok(GdbStub::new(stream))
+    }
+}
+
+// One client connection speaking the GDB remote serial protocol. The target
+// stays stopped between commands; `c` and `s` drive Gameboy::step through
+// the Debugger, so breakpoints and watchpoints behave as in the GUI.
+pub struct GdbStub {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    no_ack: bool,
+    pub debugger: Debugger,
+}
+
+impl GdbStub {
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            incoming: Vec::new(),
+            no_ack: false,
+            debugger: Debugger::new(),
+        }
+    }
+
+    // Answers commands until the client detaches, kills or disconnects
+    pub fn serve(&mut self, gameboy: &mut Gameboy) -> Result<SessionEnd, String> {
+        loop {
+            let Some(packet) = self.read_packet()? else {
+                return Ok(SessionEnd::Detached);
+            };
+
+            match packet.as_str() {
+                "k" => return Ok(SessionEnd::Killed),
+                "D" | "D;1" => {
+                    self.send_packet("OK")?;
+                    return Ok(SessionEnd::Detached);
+                }
+                _ => {}
+            }
+
+            let reply = self.handle(gameboy, &packet)?;
+            self.send_packet(&reply)?;
+            if packet == "QStartNoAckMode" {
+                self.no_ack = true;
+            }
+        }
+    }
+
+    fn handle(&mut self, gameboy: &mut Gameboy, packet: &str) -> Result<String, String> {
+        let reply = match packet.as_bytes().first() {
+            Some(b'?') => format!("S{:02x}", SIGTRAP),
+            Some(b'g') => REGISTERS
+                .iter()
+                .map(|r| hex_word(r.read(&gameboy.cpu)))
+                .collect(),
+            Some(b'G') => write_registers(gameboy, &packet[1..]),
+            Some(b'p') => match parse_hex(&packet[1..]).and_then(register) {
+                Some(register) => hex_word(register.read(&gameboy.cpu)),
+                None => "E01".into(),
+            },
+            Some(b'P') => write_register(gameboy, &packet[1..]),
+            Some(b'm') => read_memory(gameboy, &packet[1..]),
+            Some(b'M') => write_memory(gameboy, &packet[1..]),
+            Some(b'Z') | Some(b'z') => self.set_break(gameboy, packet),
+            Some(b'c') => {
+                jump(gameboy, &packet[1..]);
+                self.resume(gameboy)?
+            }
+            Some(b's') => {
+                jump(gameboy, &packet[1..]);
+                let reason = self.debugger.step_into(gameboy);
+                stop_reply(gameboy, reason)
+            }
+            Some(b'H') => "OK".into(),
+            Some(b'q') | Some(b'Q') => query(packet),
+            _ => String::new(), // unsupported
+        };
+        Ok(reply)
+    }
+
+    // Z0/Z1 breakpoints, Z2/Z3/Z4 write/read/access watchpoints
+    fn set_break(&mut self, gameboy: &mut Gameboy, packet: &str) -> String {
+        let insert = packet.starts_with('Z');
+        let mut fields = packet[1..].split(',');
+        let (Some(kind), Some(address), Some(length)) = (
+            fields.next(),
+            fields.next().and_then(parse_hex),
+            fields.next().and_then(parse_hex),
+        ) else {
+            return "E01".into();
+        };
+        let address = address as u16;
+
+        let watch_kind = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.add_breakpoint(address, None, None);
+                } else {
+                    self.debugger.remove_breakpoint(address, None);
+                }
+                return "OK".into();
+            }
+            "2" => WatchKind::Write,
+            "3" => WatchKind::Read,
+            "4" => WatchKind::Access,
+            _ => return String::new(),
+        };
+
+        let end = address.saturating_add((length as u16).max(1) - 1);
+        let watchpoint = Watchpoint::new(address, end, watch_kind);
+        let watchpoints = &mut gameboy.bus.watchpoints;
+        if insert {
+            watchpoints.push(watchpoint);
+        } else if let Some(index) = watchpoints.iter().position(|w| *w == watchpoint) {
+            watchpoints.remove(index);
+        }
+        "OK".into()
+    }
+
+    fn resume(&mut self, gameboy: &mut Gameboy) -> Result<String, String> {
+        loop {
+            match self.debugger.resume(gameboy, CONTINUE_CHUNK) {
+                StopReason::Break(BreakReason::StepLimit) => {
+                    if self.poll_interrupt()? {
+                        return Ok(format!("S{:02x}", SIGINT));
+                    }
+                }
+                reason => return Ok(stop_reply(gameboy, reason)),
+            }
+        }
+    }
+
+    // Looks for a ^C without blocking
+    fn poll_interrupt(&mut self) -> Result<bool, String> {
+        let mut buffer = [0u8; 256];
+        self.stream
+            .set_nonblocking(true)
+            .map_err(|e| e.to_string())?;
+        let result = self.stream.read(&mut buffer);
+        self.stream
+            .set_nonblocking(false)
+            .map_err(|e| e.to_string())?;
+
+        match result {
+            Ok(0) => return Err("GDB client disconnected".into()),
+            Ok(count) => self.incoming.extend_from_slice(&buffer[..count]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e.to_string()),
+        }
+
+        match self.incoming.iter().position(|&b| b == 0x03) {
+            Some(index) => {
+                self.incoming.remove(index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // None once the client has closed the connection
+    fn next_byte(&mut self) -> Result<Option<u8>, String> {
+        if self.incoming.is_empty() {
+            let mut buffer = [0u8; 1024];
+            let count = self.stream.read(&mut buffer).map_err(|e| e.to_string())?;
+            if count == 0 {
+                return Ok(None);
+            }
+            self.incoming.extend_from_slice(&buffer[..count]);
+        }
+        Ok(Some(self.incoming.remove(0)))
+    }
+
+    // `$payload#checksum`, acknowledged unless no-ack mode is on
+    fn read_packet(&mut self) -> Result<Option<String>, String> {
+        loop {
+            // Acks and stray ^C between packets are skipped
+            loop {
+                match self.next_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+
+            let mut payload = Vec::new();
+            loop {
+                match self.next_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => payload.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            for digit in &mut checksum {
+                match self.next_byte()? {
+                    Some(byte) => *digit = byte,
+                    None => return Ok(None),
+                }
+            }
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok());
+            let valid = expected == Some(sum(&payload));
+            if !self.no_ack {
+                self.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid || self.no_ack {
+                return Ok(Some(String::from_utf8_lossy(&payload).into_owned()));
+            }
+        }
+    }
+
+    fn send_packet(&mut self, payload: &str) -> Result<(), String> {
+        let mut escaped = Vec::with_capacity(payload.len());
+        for byte in payload.bytes() {
+            if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+                escaped.extend_from_slice(&[b'}', byte ^ 0x20]);
+            } else {
+                escaped.push(byte);
+            }
+        }
+        let mut packet = vec![b'$'];
+        packet.extend_from_slice(&escaped);
+        packet.extend_from_slice(format!("#{:02x}", sum(&escaped)).as_bytes());
+
+        loop {
+            self.write_all(&packet)?;
+            if self.no_ack {
+                return Ok(());
+            }
+            match self.next_byte()? {
+                Some(b'-') => continue,
+                Some(_) => return Ok(()),
+                None => return Err("GDB client disconnected".into()),
+            }
+        }
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.stream.write_all(bytes).map_err(|e| e.to_string())
+    }
+}
+
+fn sum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+fn register(number: u32) -> Option<Register> {
+    REGISTERS.get(number as usize).copied()
+}
+
+fn hex_word(value: u16) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Two hex digits; None for anything else, non-ASCII bytes included
+fn parse_byte(digits: &[u8]) -> Option<u8> {
+    let [high, low] = digits else {
+        return None;
+    };
+    let high = (*high as char).to_digit(16)?;
+    let low = (*low as char).to_digit(16)?;
+    Some((high * 16 + low) as u8)
+}
+
+// Little-endian, as GDB sends registers
+fn parse_word(digits: &[u8]) -> Option<u16> {
+    let low = parse_byte(digits.get(0..2)?)?;
+    let high = parse_byte(digits.get(2..4)?)?;
+    Some(u16::from_le_bytes([low, high]))
+}
+
+fn write_registers(gameboy: &mut Gameboy, data: &str) -> String {
+    let data = data.as_bytes();
+    if data.len() < REGISTERS.len() * 4 {
+        return "E01".into();
+    }
+    for (i, register) in REGISTERS.iter().enumerate() {
+        match parse_word(&data[i * 4..]) {
+            Some(value) => register.write(&mut gameboy.cpu, value),
+            None => return "E01".into(),
+        }
+    }
+    "OK".into()
+}
+
+fn write_register(gameboy: &mut Gameboy, data: &str) -> String {
+    let Some((number, value)) = data.split_once('=') else {
+        return "E01".into();
+    };
+    match (
+        parse_hex(number).and_then(register),
+        parse_word(value.as_bytes()),
+    ) {
+        (Some(register), Some(value)) => {
+            register.write(&mut gameboy.cpu, value);
+            "OK".into()
+        }
+        _ => "E01".into(),
+    }
+}
+
+fn memory_range(text: &str) -> Option<(u16, usize)> {
+    let (address, length) = text.split_once(',')?;
+    Some((parse_hex(address)? as u16, parse_hex(length)? as usize))
+}
+
+fn read_memory(gameboy: &Gameboy, data: &str) -> String {
+    let Some((address, length)) = memory_range(data) else {
+        return "E01".into();
+    };
+    (0..length.min(PACKET_SIZE / 2))
+        .map(|i| format!("{:02x}", gameboy.bus.peek(address.wrapping_add(i as u16))))
+        .collect()
+}
+
+// Writes go through the bus like CPU stores, MBC registers included
+fn write_memory(gameboy: &mut Gameboy, data: &str) -> String {
+    let Some((range, bytes)) = data.split_once(':') else {
+        return "E01".into();
+    };
+    let Some((address, length)) = memory_range(range) else {
+        return "E01".into();
+    };
+    if bytes.len() != length * 2 {
+        return "E01".into();
+    }
+    let Some(values) = bytes
+        .as_bytes()
+        .chunks(2)
+        .map(parse_byte)
+        .collect::<Option<Vec<u8>>>()
+    else {
+        return "E01".into();
+    };
+    for (i, value) in values.into_iter().enumerate() {
+        gameboy
+            .bus
+            .write_byte(address.wrapping_add(i as u16), value);
+    }
+    "OK".into()
+}
+
+// `c ADDR` and `s ADDR` resume at ADDR
+fn jump(gameboy: &mut Gameboy, address: &str) {
+    if let Some(address) = parse_hex(address) {
+        gameboy.cpu.pc = address as u16;
+    }
+}
+
+fn stop_reply(gameboy: &Gameboy, reason: StopReason) -> String {
+    let hit = match reason {
+        StopReason::Break(BreakReason::Watchpoint { hit, .. }) => hit,
+        StopReason::LockUp { .. } => return format!("S{:02x}", SIGILL),
+        StopReason::InvalidPc(_) => return format!("S{:02x}", SIGSEGV),
+        _ => return format!("S{:02x}", SIGTRAP),
+    };
+    let kind = gameboy
+        .bus
+        .watchpoints
+        .iter()
+        .find(|w| w.matches(hit.address, hit.write, gameboy.bus.memory.rom_bank()))
+        .map(|w| w.kind);
+    let name = match kind {
+        Some(WatchKind::Read) => "rwatch",
+        Some(WatchKind::Access) => "awatch",
+        _ => "watch",
+    };
+    format!("T{:02x}{}:{:x};", SIGTRAP, name, hit.address)
+}
+
+fn query(packet: &str) -> String {
+    if packet.starts_with("qSupported") {
+        return format!(
+            "PacketSize={:x};qXfer:features:read+;QStartNoAckMode+",
+            PACKET_SIZE
+        );
+    }
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        let Some((offset, length)) = memory_range(range) else {
+            return "E01".into();
+        };
+        let offset = (offset as usize).min(TARGET_XML.len());
+        let end = (offset + length).min(TARGET_XML.len());
+        let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+        return format!("{}{}", marker, &TARGET_XML[offset..end]);
+    }
+    match packet {
+        "QStartNoAckMode" => "OK".into(),
+        "qAttached" => "1".into(),
+        "qC" => "QC1".into(),
+        "qfThreadInfo" => "m1".into(),
+        "qsThreadInfo" => "l".into(),
+        _ => String::new(),
+    }
+}
@@ -1,12 +1,59 @@
-use crate::emulator::gameboy::Gameboy;
+use crate::emulator::apu::{self, DEFAULT_SAMPLE_RATE};
+use crate::emulator::audio_buffer::{AudioProducer, RateControl, audio_ring};
+use crate::emulator::call_stack::FrameKind;
+use crate::emulator::debugger::{self, Condition, Debugger, WatchKind, Watchpoint};
+use crate::emulator::disassembler;
+use crate::emulator::gameboy::{Gameboy, StopReason};
+use crate::emulator::gbs::{GbsFile, GbsPlayer};
+use crate::emulator::headless::DEFAULT_ROM_PATH;
 use crate::emulator::joypad::JoypadButton;
+use crate::emulator::link;
+use crate::emulator::memory_viewer::{self, WriteHistory};
+use crate::emulator::net_link::{FrameSync, NetLink, NetListener, PendingPeer};
+use crate::emulator::palette::{LayerPalettes, PALETTE_CONFIG_PATH, Palette};
+use crate::emulator::postprocess::{Filter, Frame, PostProcessor};
+use crate::emulator::printer::{PrintQueue, Printer, save_new_jobs};
+use crate::emulator::profiler::{self, Profiler};
+use crate::emulator::recorder::{AudioRecorder, WavFormat};
+use crate::emulator::symbols::{self, SymbolTable};
+use crate::emulator::vram_viewer::{self, TilePalette};
+use crate::logger::{self, Level, Record, Target};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use eframe::egui;
 use egui::{ColorImage, Key, TextureHandle, Vec2};
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+// Game Boy frame duration: ~16.74ms (59.7275 Hz)
+const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+// Cap to avoid spiral of death if emulation falls behind
+const MAX_CATCHUP_FRAMES: u32 = 4;
+// Audio queue: target latency of ~3 frames, room for a quarter second
+const AUDIO_TARGET_FRAMES: u32 = 3;
+
+const PLAYER1_KEYS: [(Key, JoypadButton); 8] = [
+    (Key::W, JoypadButton::Up),
+    (Key::S, JoypadButton::Down),
+    (Key::A, JoypadButton::Left),
+    (Key::D, JoypadButton::Right),
+    (Key::Space, JoypadButton::A),
+    (Key::X, JoypadButton::B),
+    (Key::Enter, JoypadButton::Start),
+    (Key::C, JoypadButton::Select),
+];
+
+// Second player in link mode
+const PLAYER2_KEYS: [(Key, JoypadButton); 8] = [
+    (Key::ArrowUp, JoypadButton::Up),
+    (Key::ArrowDown, JoypadButton::Down),
+    (Key::ArrowLeft, JoypadButton::Left),
+    (Key::ArrowRight, JoypadButton::Right),
+    (Key::L, JoypadButton::A),
+    (Key::K, JoypadButton::B),
+    (Key::O, JoypadButton::Start),
+    (Key::I, JoypadButton::Select),
+];
+
 pub struct GameBoyApp {
     gameboy: Gameboy,
     texture: Option<TextureHandle>,
@@ -18,9 +65,74 @@ pub struct GameBoyApp {
     fps: f32,
     fps_counter: u32,
     fps_timer: Instant,
-    audio_buffer: Arc<Mutex<VecDeque<(f32, f32)>>>,
-    _audio_stream: Option<cpal::Stream>,
+    audio_producer: AudioProducer,
+    rate_control: RateControl,
+    audio_stream: Option<cpal::Stream>,
+    audio_sync: bool,
     muted: bool,
+    palettes: Vec<Palette>,
+    layer_palettes: LayerPalettes,
+    post_processor: PostProcessor,
+    show_tiles: bool,
+    show_tile_maps: bool,
+    show_oam: bool,
+    show_audio: bool,
+    tile_palette: TilePalette,
+    viewer_textures: ViewerTextures,
+    recorder: Option<AudioRecorder>,
+    record_format: WavFormat,
+    record_stems: bool,
+    record_status: String,
+    gbs_player: Option<GbsPlayer>,
+    gbs_path: String,
+    gbs_status: String,
+    // Second Game Boy on the link cable, shown next to the first
+    player2: Option<Gameboy>,
+    player2_texture: Option<TextureHandle>,
+    link_rom_path: String,
+    link_status: String,
+    // Link cable to another emulator process
+    net_address: String,
+    net_pending: Option<PendingPeer>,
+    net_sync: Option<FrameSync>,
+    printer_jobs: Option<PrintQueue>,
+
+    debugger: Debugger,
+    symbols: SymbolTable,
+    symbols_path: String,
+    show_debugger: bool,
+    debugger_status: String,
+    debugger_cursor: Option<u16>,
+    breakpoint_address: String,
+    breakpoint_condition: String,
+    watch_range: String,
+    watch_kind: WatchKind,
+
+    show_memory: bool,
+    memory_goto: String,
+    memory_scroll_to: Option<u16>,
+    memory_selected: Option<u16>,
+    memory_edit: String,
+    memory_search: String,
+    memory_status: String,
+
+    show_profiler: bool,
+    // Results kept after profiling stops
+    profile: Option<Profiler>,
+    profile_path: String,
+    profile_status: String,
+
+    show_log: bool,
+    log_records: VecDeque<Record>,
+    log_path: String,
+    log_status: String,
+}
+
+#[derive(Default)]
+struct ViewerTextures {
+    tiles: Option<TextureHandle>,
+    maps: [Option<TextureHandle>; 2],
+    sprites: Vec<Option<TextureHandle>>,
 }
 
 impl GameBoyApp {
@@ -29,21 +141,45 @@ impl GameBoyApp {
 
         let mut gameboy = Gameboy::new();
 
-        if let Ok(rom_data) = std::fs::read("resources/tetris.gb") {
+        if let Ok(rom_data) = std::fs::read(DEFAULT_ROM_PATH) {
             if let Err(e) = gameboy.load_rom(&rom_data) {
                 eprintln!("Erreur lors du chargement de la ROM: {}", e);
             } else {
                 // Boot rapide
                 for _ in 0..60 {
-                    gameboy.run_frame();
+                    if let Err(reason) = gameboy.run_frame() {
+                        eprintln!("{}", reason);
+                        break;
+                    }
                 }
             }
         } else {
             eprintln!("Impossible de charger resources/tetris.gb");
         }
 
-        let audio_buffer: Arc<Mutex<VecDeque<(f32, f32)>>> = Arc::new(Mutex::new(VecDeque::new()));
-        let audio_stream = Self::init_audio_stream(Arc::clone(&audio_buffer));
+        // Labels for the debugger, when the ROM comes with a .sym file
+        let symbols_path = symbols::symbol_path(DEFAULT_ROM_PATH);
+        let symbols = if std::path::Path::new(&symbols_path).exists() {
+            SymbolTable::load(&symbols_path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                SymbolTable::new()
+            })
+        } else {
+            SymbolTable::new()
+        };
+
+        let mut palettes = Palette::presets();
+        if std::path::Path::new(PALETTE_CONFIG_PATH).exists() {
+            match Palette::load_config(PALETTE_CONFIG_PATH) {
+                Ok(custom) => palettes.extend(custom),
+                Err(e) => eprintln!("Erreur lors du chargement des palettes: {}", e),
+            }
+        }
+
+        let (audio_stream, sample_rate, audio_producer) = Self::init_audio_stream();
+        let target_fill = (sample_rate * AUDIO_TARGET_FRAMES) as usize * 1000 / 59_727;
+        gameboy.bus.apu.set_sample_rate(sample_rate);
+        gameboy.take_audio_samples(); // drop audio from the fast boot
 
         Self {
             gameboy,
@@ -56,41 +192,108 @@ impl GameBoyApp {
             fps: 0.0,
             fps_counter: 0,
             fps_timer: Instant::now(),
-            audio_buffer,
-            _audio_stream: audio_stream,
+            audio_producer,
+            rate_control: RateControl::new(sample_rate, target_fill),
+            audio_stream,
+            audio_sync: false,
             muted: false,
+            palettes,
+            layer_palettes: LayerPalettes::default(),
+            post_processor: PostProcessor::default(),
+            show_tiles: false,
+            show_tile_maps: false,
+            show_oam: false,
+            show_audio: false,
+            tile_palette: TilePalette::default(),
+            viewer_textures: ViewerTextures::default(),
+            recorder: None,
+            record_format: WavFormat::default(),
+            record_stems: false,
+            record_status: String::new(),
+            gbs_player: None,
+            gbs_path: String::new(),
+            gbs_status: String::new(),
+            player2: None,
+            player2_texture: None,
+            link_rom_path: DEFAULT_ROM_PATH.to_string(),
+            link_status: String::new(),
+            net_address: "127.0.0.1:5000".to_string(),
+            net_pending: None,
+            net_sync: None,
+            printer_jobs: None,
+            debugger: Debugger::new(),
+            symbols,
+            symbols_path,
+            show_debugger: false,
+            debugger_status: String::new(),
+            debugger_cursor: None,
+            breakpoint_address: String::new(),
+            breakpoint_condition: String::new(),
+            watch_range: String::new(),
+            watch_kind: WatchKind::default(),
+            show_memory: false,
+            memory_goto: String::new(),
+            memory_scroll_to: None,
+            memory_selected: None,
+            memory_edit: String::new(),
+            memory_search: String::new(),
+            memory_status: String::new(),
+            show_profiler: false,
+            profile: None,
+            profile_path: "profile.folded".to_string(),
+            profile_status: String::new(),
+            show_log: false,
+            log_records: VecDeque::new(),
+            log_path: "dmg-emu.log".to_string(),
+            log_status: String::new(),
         }
     }
 
-    fn init_audio_stream(audio_buffer: Arc<Mutex<VecDeque<(f32, f32)>>>) -> Option<cpal::Stream> {
+    // Returns the stream, the sample rate the host device runs at and the
+    // producer side of the queue feeding it
+    fn init_audio_stream() -> (Option<cpal::Stream>, u32, AudioProducer) {
         let host = cpal::default_host();
         let device = match host.default_output_device() {
             Some(d) => d,
             None => {
                 eprintln!("No audio output device found");
-                return None;
+                let (producer, _) = audio_ring(DEFAULT_SAMPLE_RATE as usize / 4);
+                return (None, DEFAULT_SAMPLE_RATE, producer);
+            }
+        };
+
+        let (sample_rate, channels) = match device.default_output_config() {
+            Ok(supported) => (supported.sample_rate(), supported.channels().max(1)),
+            Err(e) => {
+                eprintln!(
+                    "No default audio config ({}), using {} Hz",
+                    e, DEFAULT_SAMPLE_RATE
+                );
+                (DEFAULT_SAMPLE_RATE, 2)
             }
         };
 
         let config = cpal::StreamConfig {
-            channels: 2,
-            sample_rate: 44100,
+            channels,
+            sample_rate,
             buffer_size: cpal::BufferSize::Default,
         };
 
-        let buffer = audio_buffer;
+        let (producer, mut consumer) = audio_ring(sample_rate as usize / 4);
         let stream = device
             .build_output_stream(
                 &config,
                 move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                    let mut buf = buffer.lock().unwrap();
-                    for frame in data.chunks_mut(2) {
-                        if let Some((left, right)) = buf.pop_front() {
-                            frame[0] = left;
-                            frame[1] = right;
-                        } else {
-                            frame[0] = 0.0;
-                            frame[1] = 0.0;
+                    for frame in data.chunks_mut(channels as usize) {
+                        let (left, right) = consumer.pop().unwrap_or((0.0, 0.0));
+                        match frame {
+                            [mono] => *mono = (left + right) / 2.0,
+                            [l, r, rest @ ..] => {
+                                *l = left;
+                                *r = right;
+                                rest.fill(0.0);
+                            }
+                            [] => {}
                         }
                     }
                 },
@@ -107,27 +310,79 @@ impl GameBoyApp {
             eprintln!("Failed to start audio stream: {}", e);
         }
 
-        stream
+        (stream, sample_rate, producer)
     }
 
-    fn update_texture(&mut self, ctx: &egui::Context) {
-        let framebuffer = &self.gameboy.bus.ppu.framebuffer;
-        let mut pixels = Vec::with_capacity(160 * 144 * 4);
+    fn run_frame(&mut self) {
+        // Too far ahead of the network peer: skip the frame, don't wait
+        if let Some(sync) = &mut self.net_sync {
+            match sync.ready() {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => {
+                    self.disconnect_network();
+                    self.link_status = format!("Error: {}", e);
+                }
+            }
+        }
 
-        for row in framebuffer.iter() {
-            for &pixel in row.iter() {
-                let color = match pixel {
-                    0 => [255, 255, 255, 255], // White
-                    1 => [170, 170, 170, 255], // Light Gray
-                    2 => [85, 85, 85, 255],    // Dark Gray
-                    3 => [0, 0, 0, 255],       // Black
-                    _ => [255, 0, 255, 255],   // Magenta debug
-                };
-                pixels.extend_from_slice(&color);
+        if let Some(player) = &mut self.gbs_player {
+            if let Err(e) = player.run_frame(&mut self.gameboy) {
+                self.eject_gbs();
+                self.gbs_status = format!("Error: {}", e);
             }
+        } else if let Some(player2) = &mut self.player2 {
+            let result = link::run_frame(&mut self.gameboy, player2);
+            // Only player 1 is heard
+            player2.take_audio_samples();
+            if let Err(reason) = result {
+                self.on_stop(reason);
+            }
+        } else if self.debugger.is_active(&self.gameboy) {
+            if let Some(reason) = self.debugger.run_frame(&mut self.gameboy) {
+                self.on_stop(reason);
+            }
+        } else if let Err(reason) = self.gameboy.run_frame() {
+            self.on_stop(reason);
+        }
+        self.update_fps();
+        if let Some(history) = &mut self.gameboy.bus.write_history {
+            history.next_frame();
         }
 
-        let color_image = ColorImage::from_rgba_unmultiplied([160, 144], &pixels);
+        if let Some(sync) = &mut self.net_sync
+            && let Err(e) = sync.send_frame()
+        {
+            self.disconnect_network();
+            self.link_status = format!("Error: {}", e);
+        }
+
+        let mut samples = self.gameboy.take_audio_samples();
+        self.record_samples(&samples);
+
+        // Silence keeps the queue level, which audio pacing relies on
+        if self.muted {
+            samples.fill((0.0, 0.0));
+        }
+        self.audio_producer.push_slice(&samples);
+
+        let rate = self.rate_control.rate(self.audio_producer.len());
+        self.gameboy.bus.apu.set_sample_rate(rate);
+    }
+
+    fn update_texture(&mut self, ctx: &egui::Context, new_frame: bool) {
+        let frame = Frame::from_ppu(&self.gameboy.bus.ppu, &self.layer_palettes);
+        // Ghosting blends emulated frames, not repaints: only feed new frames
+        let frame = if new_frame {
+            self.post_processor.process(&frame)
+        } else if self.paused || self.texture.is_none() {
+            self.post_processor.filter.apply(&frame)
+        } else {
+            return;
+        };
+
+        let color_image =
+            ColorImage::from_rgba_unmultiplied([frame.width, frame.height], &frame.pixels);
 
         if let Some(texture) = &mut self.texture {
             texture.set(color_image, egui::TextureOptions::NEAREST);
@@ -138,24 +393,1092 @@ impl GameBoyApp {
                 egui::TextureOptions::NEAREST,
             ));
         }
+
+        // Player 2 gets the filter but no ghosting, which keeps per-stream state
+        if let Some(player2) = &self.player2 {
+            let frame = Frame::from_ppu(&player2.bus.ppu, &self.layer_palettes);
+            let frame = self.post_processor.filter.apply(&frame);
+            Self::upload_frame(ctx, &mut self.player2_texture, "player2_screen", &frame);
+        }
+    }
+
+    fn upload_frame(
+        ctx: &egui::Context,
+        slot: &mut Option<TextureHandle>,
+        name: &str,
+        frame: &Frame,
+    ) -> egui::TextureId {
+        let image = ColorImage::from_rgba_unmultiplied([frame.width, frame.height], &frame.pixels);
+        match slot {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => *slot = Some(ctx.load_texture(name, image, egui::TextureOptions::NEAREST)),
+        }
+        slot.as_ref().unwrap().id()
+    }
+
+    // VRAM inspection windows, rendered straight from Memory without going
+    // through the bus so they never disturb emulation
+    fn show_vram_viewers(&mut self, ctx: &egui::Context) {
+        let memory = &self.gameboy.bus.memory;
+        let ppu = &self.gameboy.bus.ppu;
+        let palette = &self.layer_palettes.bg;
+        let textures = &mut self.viewer_textures;
+
+        if self.show_tiles {
+            let frame = vram_viewer::render_tiles(memory, ppu, self.tile_palette, palette);
+            let texture = Self::upload_frame(ctx, &mut textures.tiles, "vram_tiles", &frame);
+            let size = Vec2::new(frame.width as f32 * 2.0, frame.height as f32 * 2.0);
+            let tile_palette = &mut self.tile_palette;
+
+            egui::Window::new("Tile Data")
+                .open(&mut self.show_tiles)
+                .show(ctx, |ui| {
+                    egui::ComboBox::from_label("Palette")
+                        .selected_text(tile_palette.name())
+                        .show_ui(ui, |ui| {
+                            for option in TilePalette::ALL {
+                                ui.selectable_value(tile_palette, option, option.name());
+                            }
+                        });
+                    ui.image((texture, size));
+                    ui.label("0x8000-0x97FF, 384 tiles");
+                });
+        }
+
+        if self.show_tile_maps {
+            let mut ids = Vec::new();
+            for (slot, base) in textures.maps.iter_mut().zip([0x9800u16, 0x9C00]) {
+                let frame = vram_viewer::render_tile_map(memory, ppu, base, palette);
+                let name = format!("vram_map_{:04X}", base);
+                ids.push((base, Self::upload_frame(ctx, slot, &name, &frame)));
+            }
+
+            egui::Window::new("Tile Maps")
+                .open(&mut self.show_tile_maps)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "SCX: {}  SCY: {}  WX: {}  WY: {}",
+                        ppu.scx, ppu.scy, ppu.wx, ppu.wy
+                    ));
+                    ui.horizontal(|ui| {
+                        for (base, texture) in ids {
+                            ui.vertical(|ui| {
+                                ui.label(format!("0x{:04X}", base));
+                                ui.image((texture, Vec2::new(256.0, 256.0)));
+                            });
+                        }
+                    });
+                    ui.label("Red: SCX/SCY viewport  Blue: window");
+                });
+        }
+
+        if self.show_oam {
+            let entries = vram_viewer::read_oam_entries(memory);
+            textures.sprites.resize_with(entries.len(), || None);
+
+            let mut previews = Vec::new();
+            for (entry, slot) in entries.iter().zip(textures.sprites.iter_mut()) {
+                let sprite_palette = if entry.uses_obp1() {
+                    &self.layer_palettes.obj1
+                } else {
+                    &self.layer_palettes.obj0
+                };
+                let frame = vram_viewer::render_sprite(memory, ppu, entry, sprite_palette);
+                let name = format!("oam_sprite_{}", entry.index);
+                let size = Vec2::new(frame.width as f32 * 2.0, frame.height as f32 * 2.0);
+                previews.push((Self::upload_frame(ctx, slot, &name, &frame), size));
+            }
+
+            egui::Window::new("OAM")
+                .open(&mut self.show_oam)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("oam_table").striped(true).show(ui, |ui| {
+                            for header in ["#", "Y", "X", "Tile", "Flags", "Pal", "", ""] {
+                                ui.label(header);
+                            }
+                            ui.end_row();
+
+                            for (entry, preview) in entries.iter().zip(previews) {
+                                ui.label(format!("{}", entry.index));
+                                ui.label(format!("{}", entry.y));
+                                ui.label(format!("{}", entry.x));
+                                ui.label(format!("0x{:02X}", entry.tile));
+                                ui.label(format!(
+                                    "0x{:02X} {}{}{}",
+                                    entry.flags,
+                                    if entry.behind_bg() { "P" } else { "-" },
+                                    if entry.y_flip() { "Y" } else { "-" },
+                                    if entry.x_flip() { "X" } else { "-" },
+                                ));
+                                ui.label(if entry.uses_obp1() { "OBP1" } else { "OBP0" });
+                                ui.image(preview);
+                                ui.label(if entry.is_visible() { "" } else { "hidden" });
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+        }
+    }
+
+    // Per-channel oscilloscope with mute/solo controls
+    fn show_audio_channels(&mut self, ctx: &egui::Context) {
+        const NAMES: [&str; 4] = ["CH1 Square", "CH2 Square", "CH3 Wave", "CH4 Noise"];
+        const DUTIES: [&str; 4] = ["12.5%", "25%", "50%", "75%"];
+        let apu = &mut self.gameboy.bus.apu;
+
+        egui::Window::new("Audio Channels")
+            .open(&mut self.show_audio)
+            .show(ctx, |ui| {
+                for (channel, name) in NAMES.iter().enumerate() {
+                    let info = apu.channel_info(channel);
+
+                    ui.horizontal(|ui| {
+                        ui.strong(*name);
+                        ui.checkbox(&mut apu.muted[channel], "Mute");
+                        let soloed = apu.is_soloed(channel);
+                        if ui.selectable_label(soloed, "Solo").clicked() {
+                            if soloed {
+                                apu.muted = [false; 4];
+                            } else {
+                                apu.solo(channel);
+                            }
+                        }
+                        if !info.enabled {
+                            ui.weak("off");
+                        }
+                    });
+
+                    let mut details = if channel == 3 {
+                        format!("{:.0} Hz", info.frequency_hz)
+                    } else {
+                        format!(
+                            "{:.1} Hz {}",
+                            info.frequency_hz,
+                            apu::note_name(info.frequency_hz).unwrap_or_default()
+                        )
+                    };
+                    details += &format!("  Vol: {}", info.volume);
+                    if let Some((increasing, pace)) = info.envelope {
+                        details +=
+                            &format!("  Env: {}{}", if increasing { "+" } else { "-" }, pace);
+                    }
+                    if let Some(duty) = info.duty {
+                        details += &format!("  Duty: {}", DUTIES[duty as usize]);
+                    }
+                    ui.label(details);
+
+                    let (rect, _) =
+                        ui.allocate_exact_size(Vec2::new(320.0, 40.0), egui::Sense::hover());
+                    let painter = ui.painter_at(rect);
+                    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+
+                    // DAC output is +1..-1
+                    let scope = apu.scope(channel);
+                    let step = rect.width() / (scope.len() - 1) as f32;
+                    let points = scope
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &value)| {
+                            egui::pos2(
+                                rect.left() + i as f32 * step,
+                                rect.center().y - value * rect.height() * 0.45,
+                            )
+                        })
+                        .collect();
+                    let color = if apu.muted[channel] {
+                        egui::Color32::DARK_GRAY
+                    } else {
+                        egui::Color32::LIGHT_GREEN
+                    };
+                    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, color)));
+
+                    ui.add_space(4.0);
+                }
+            });
+    }
+
+    // Breakpoints and emulation errors both pause and show the debugger
+    fn on_stop(&mut self, reason: StopReason) {
+        self.paused = true;
+        self.show_debugger = true;
+        self.debugger_status = reason.to_string();
+        if let Some(label) = self.describe_pc() {
+            self.debugger_status += &format!(" in {}", label);
+        }
+    }
+
+    fn describe_pc(&self) -> Option<String> {
+        let bank = self.gameboy.bus.memory.rom_bank();
+        self.symbols.describe(self.gameboy.cpu.pc, bank)
+    }
+
+    fn load_symbols(&mut self) {
+        match SymbolTable::load(&self.symbols_path) {
+            Ok(symbols) => {
+                self.debugger_status = format!("{} symbols loaded", symbols.len());
+                self.symbols = symbols;
+            }
+            Err(e) => self.debugger_status = format!("Error: {}", e),
+        }
+    }
+
+    fn add_breakpoint(&mut self) {
+        let address = self.symbols.parse_address(&self.breakpoint_address);
+        let result = address.and_then(|(bank, address)| {
+            let condition = match self.breakpoint_condition.trim() {
+                "" => None,
+                text => Some(Condition::parse(text)?),
+            };
+            Ok((bank, address, condition))
+        });
+        match result {
+            Ok((bank, address, condition)) => {
+                self.debugger.add_breakpoint(address, bank, condition);
+                self.breakpoint_address.clear();
+                self.breakpoint_condition.clear();
+            }
+            Err(e) => self.debugger_status = format!("Error: {}", e),
+        }
+    }
+
+    fn add_watchpoint(&mut self) {
+        // A label or "BB:ADDR" watches one byte in its bank
+        let range = match self.symbols.parse_address(&self.watch_range) {
+            Ok((bank, address)) => Ok((bank, address, address)),
+            Err(e) => debugger::parse_range(&self.watch_range)
+                .map(|(start, end)| (None, start, end))
+                .map_err(|_| e),
+        };
+        match range {
+            Ok((bank, start, end)) => {
+                let mut watchpoint = Watchpoint::new(start, end, self.watch_kind);
+                watchpoint.bank = bank;
+                self.gameboy.bus.watchpoints.push(watchpoint);
+                self.watch_range.clear();
+            }
+            Err(e) => self.debugger_status = format!("Error: {}", e),
+        }
+    }
+
+    fn show_debugger_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_debugger;
+
+        egui::Window::new("🐞 Debugger")
+            .open(&mut open)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                let bank = self.gameboy.bus.memory.rom_bank();
+                ui.label(if self.paused {
+                    let pc = self.gameboy.cpu.pc;
+                    format!("Paused at {}", self.symbols.format_address(pc, bank))
+                } else {
+                    "Running".to_string()
+                });
+                if !self.debugger_status.is_empty() {
+                    ui.label(&self.debugger_status);
+                }
+
+                // Stepping pauses first: the frame loop must not run on
+                ui.horizontal(|ui| {
+                    if ui.button("▶ Continue").clicked() {
+                        self.paused = false;
+                        self.debugger_status.clear();
+                    }
+                    let mut reason = None;
+                    if ui.button("Step Into").clicked() {
+                        reason = Some(self.debugger.step_into(&mut self.gameboy));
+                    }
+                    if ui.button("Step Over").clicked() {
+                        reason = Some(self.debugger.step_over(&mut self.gameboy));
+                    }
+                    if ui.button("Step Out").clicked() {
+                        reason = Some(self.debugger.step_out(&mut self.gameboy));
+                    }
+                    if let Some(cursor) = self.debugger_cursor
+                        && ui.button("Run to Cursor").clicked()
+                    {
+                        reason = Some(self.debugger.run_to(&mut self.gameboy, cursor));
+                    }
+                    if let Some(reason) = reason {
+                        self.on_stop(reason);
+                    }
+                });
+
+                ui.separator();
+                let pc = self.gameboy.cpu.pc;
+                egui::ScrollArea::vertical()
+                    .id_salt("disassembly")
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for instruction in
+                            disassembler::disassemble_around(&self.gameboy.bus, pc, 8, 16)
+                        {
+                            let address = instruction.address;
+                            if let Some(label) = self.symbols.label_at(address, bank) {
+                                ui.monospace(format!("{}:", label));
+                            }
+                            ui.horizontal(|ui| {
+                                // The listing shows the mapped bank
+                                let listed_bank = (0x4000..0x8000)
+                                    .contains(&address)
+                                    .then(|| u8::try_from(bank).ok())
+                                    .flatten();
+                                let marker = if self.debugger.has_breakpoint(address, listed_bank) {
+                                    "●"
+                                } else {
+                                    "○"
+                                };
+                                if ui.small_button(marker).clicked() {
+                                    self.debugger.toggle_breakpoint(address, listed_bank);
+                                }
+
+                                let bytes: Vec<String> = instruction
+                                    .bytes
+                                    .iter()
+                                    .map(|b| format!("{:02X}", b))
+                                    .collect();
+                                let line = format!(
+                                    "{} {:04X}  {:<9} {}",
+                                    if address == pc { "→" } else { " " },
+                                    address,
+                                    bytes.join(" "),
+                                    instruction.text_with_symbols(&self.symbols, bank)
+                                );
+                                let selected = self.debugger_cursor == Some(address);
+                                if ui
+                                    .selectable_label(
+                                        selected,
+                                        egui::RichText::new(line).monospace(),
+                                    )
+                                    .clicked()
+                                {
+                                    self.debugger_cursor = Some(address);
+                                }
+                            });
+                        }
+                    });
+
+                ui.separator();
+                ui.label("Call stack:");
+                let frames = self.gameboy.cpu.call_stack.frames();
+                if frames.is_empty() {
+                    ui.label("(top level)");
+                }
+                for frame in frames.iter().rev() {
+                    let name = profiler::function_name(Some(frame.function), &self.symbols);
+                    let kind = match frame.kind {
+                        FrameKind::Call => "",
+                        FrameKind::Rst => " [rst]",
+                        FrameKind::Interrupt => " [int]",
+                    };
+                    let line = format!(
+                        "{}{}  ← {}",
+                        name,
+                        kind,
+                        self.symbols.format_address(frame.return_address, bank)
+                    );
+                    // Run to Cursor on a return address finishes that frame
+                    let selected = self.debugger_cursor == Some(frame.return_address);
+                    if ui
+                        .selectable_label(selected, egui::RichText::new(line).monospace())
+                        .clicked()
+                    {
+                        self.debugger_cursor = Some(frame.return_address);
+                    }
+                }
+
+                ui.separator();
+                ui.label("Breakpoints:");
+                let mut removed = None;
+                for breakpoint in &mut self.debugger.breakpoints {
+                    ui.horizontal(|ui| {
+                        let address = match breakpoint.bank {
+                            Some(bp_bank) => format!(
+                                "{:02X}:{}",
+                                bp_bank,
+                                self.symbols
+                                    .format_address(breakpoint.address, bp_bank as usize)
+                            ),
+                            None => self.symbols.format_address(breakpoint.address, bank),
+                        };
+                        ui.checkbox(&mut breakpoint.enabled, address);
+                        if let Some(condition) = &breakpoint.condition {
+                            ui.label(format!("if {}", condition));
+                        }
+                        if ui.small_button("✖").clicked() {
+                            removed = Some((breakpoint.address, breakpoint.bank));
+                        }
+                    });
+                }
+                if let Some((address, bank)) = removed {
+                    self.debugger.remove_breakpoint(address, bank);
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.breakpoint_address)
+                            .hint_text("$0150 or label")
+                            .desired_width(100.0),
+                    );
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.breakpoint_condition)
+                            .hint_text("A == $10")
+                            .desired_width(100.0),
+                    );
+                    if ui.button("Add").clicked() {
+                        self.add_breakpoint();
+                    }
+                });
+
+                ui.separator();
+                ui.label("Watchpoints:");
+                let watchpoints = &mut self.gameboy.bus.watchpoints;
+                let mut removed = None;
+                for (index, watchpoint) in watchpoints.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if let Some(bank) = watchpoint.bank {
+                            ui.label(format!("{:02X}:${:04X}", bank, watchpoint.start));
+                        } else if watchpoint.start == watchpoint.end {
+                            ui.label(format!("${:04X}", watchpoint.start));
+                        } else {
+                            ui.label(format!("${:04X}-${:04X}", watchpoint.start, watchpoint.end));
+                        }
+                        ui.label(watchpoint.kind.name());
+                        if ui.small_button("✖").clicked() {
+                            removed = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = removed {
+                    watchpoints.remove(index);
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.watch_range)
+                            .hint_text("$C000-$C0FF")
+                            .desired_width(100.0),
+                    );
+                    egui::ComboBox::from_id_salt("watch_kind")
+                        .selected_text(self.watch_kind.name())
+                        .show_ui(ui, |ui| {
+                            for kind in WatchKind::ALL {
+                                ui.selectable_value(&mut self.watch_kind, kind, kind.name());
+                            }
+                        });
+                    if ui.button("Add").clicked() {
+                        self.add_watchpoint();
+                    }
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Symbols:");
+                    ui.text_edit_singleline(&mut self.symbols_path);
+                    if ui.button("Load").clicked() {
+                        self.load_symbols();
+                    }
+                });
+                ui.label(format!("{} labels", self.symbols.len()));
+            });
+
+        self.show_debugger = open;
+    }
+
+    fn export_profile(&mut self) {
+        let profile = self.gameboy.profiler.as_ref().or(self.profile.as_ref());
+        let Some(profile) = profile else {
+            return;
+        };
+        self.profile_status =
+            match std::fs::write(&self.profile_path, profile.folded_stacks(&self.symbols)) {
+                Ok(()) => format!("Saved {}", self.profile_path),
+                Err(e) => format!("Error: {}: {}", self.profile_path, e),
+            };
+    }
+
+    fn show_profile_node(
+        ui: &mut egui::Ui,
+        profile: &Profiler,
+        node: usize,
+        symbols: &SymbolTable,
+        total: u64,
+    ) {
+        for (child, entry) in profile.children(node) {
+            let text = format!(
+                "{}  {:.1}%  ({} calls)",
+                profiler::function_name(entry.function, symbols),
+                entry.total_cycles as f64 * 100.0 / total as f64,
+                entry.calls
+            );
+            if profile.children(child).is_empty() {
+                ui.label(text);
+            } else {
+                egui::CollapsingHeader::new(text)
+                    .id_salt(child)
+                    .show(ui, |ui| {
+                        Self::show_profile_node(ui, profile, child, symbols, total);
+                    });
+            }
+        }
+    }
+
+    // Cycles per function, flat and as a call tree. Profiling follows the
+    // CPU's shadow call stack, so it only needs the Game Boy to be running.
+    fn show_profiler_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_profiler;
+
+        egui::Window::new("📊 Profiler")
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if self.gameboy.profiler.is_some() {
+                        if ui.button("⏹ Stop").clicked() {
+                            self.profile = self.gameboy.profiler.take();
+                        }
+                    } else if ui.button("⏺ Start").clicked() {
+                        self.gameboy.profiler = Some(Profiler::start(&self.gameboy.cpu.call_stack));
+                        self.profile = None;
+                    }
+                    if ui.button("Reset").clicked() {
+                        if self.gameboy.profiler.is_some() {
+                            self.gameboy.profiler =
+                                Some(Profiler::start(&self.gameboy.cpu.call_stack));
+                        }
+                        self.profile = None;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.profile_path);
+                    if ui.button("Export folded").clicked() {
+                        self.export_profile();
+                    }
+                });
+                if !self.profile_status.is_empty() {
+                    ui.label(&self.profile_status);
+                }
+
+                let profile = self.gameboy.profiler.as_ref().or(self.profile.as_ref());
+                let Some(profile) = profile else {
+                    ui.label("Not profiling");
+                    return;
+                };
+                let total = profile.total_cycles().max(1);
+                ui.label(format!("{} cycles", profile.total_cycles()));
+
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("profile_flat")
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("profile_flat_grid")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Function");
+                                ui.strong("Self");
+                                ui.strong("Total");
+                                ui.strong("Calls");
+                                ui.end_row();
+                                for entry in profile.flat() {
+                                    ui.monospace(profiler::function_name(
+                                        entry.function,
+                                        &self.symbols,
+                                    ));
+                                    ui.monospace(format!(
+                                        "{:.1}%",
+                                        entry.self_cycles as f64 * 100.0 / total as f64
+                                    ));
+                                    ui.monospace(format!(
+                                        "{:.1}%",
+                                        entry.total_cycles as f64 * 100.0 / total as f64
+                                    ));
+                                    ui.monospace(entry.calls.to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                ui.separator();
+                ui.label("Call tree:");
+                egui::ScrollArea::vertical()
+                    .id_salt("profile_tree")
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        Self::show_profile_node(ui, profile, profile.root(), &self.symbols, total);
+                    });
+            });
+
+        self.show_profiler = open;
+    }
+
+    // Levels per subsystem and the messages logged while the window is open
+    fn show_log_window(&mut self, ctx: &egui::Context) {
+        logger::capture_console(true);
+        for record in logger::take_console_records() {
+            if self.log_records.len() == logger::CONSOLE_LINES {
+                self.log_records.pop_front();
+            }
+            self.log_records.push_back(record);
+        }
+        let mut open = self.show_log;
+
+        egui::Window::new("📜 Log")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for target in Target::ALL {
+                        let mut level = logger::level(target);
+                        egui::ComboBox::from_label(target.name())
+                            .selected_text(level.name())
+                            .show_ui(ui, |ui| {
+                                for option in Level::ALL {
+                                    ui.selectable_value(&mut level, option, option.name());
+                                }
+                            });
+                        if level != logger::level(target) {
+                            logger::set_level(target, level);
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.log_path);
+                    if ui.button("Log to file").clicked() {
+                        self.log_status = match logger::log_to_file(&self.log_path) {
+                            Ok(()) => format!("Logging to {}", self.log_path),
+                            Err(e) => format!("Error: {}", e),
+                        };
+                    }
+                    if ui.button("Stop").clicked() {
+                        logger::close_file();
+                        self.log_status.clear();
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.log_records.clear();
+                    }
+                });
+                if !self.log_status.is_empty() {
+                    ui.label(&self.log_status);
+                }
+
+                ui.separator();
+                let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+                egui::ScrollArea::vertical()
+                    .id_salt("log")
+                    .max_height(400.0)
+                    .stick_to_bottom(true)
+                    .show_rows(ui, row_height, self.log_records.len(), |ui, range| {
+                        for record in self.log_records.range(range) {
+                            let color = match record.level {
+                                Level::Error => egui::Color32::LIGHT_RED,
+                                Level::Warn => egui::Color32::YELLOW,
+                                Level::Debug | Level::Trace => egui::Color32::GRAY,
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.label(
+                                egui::RichText::new(record.to_string())
+                                    .monospace()
+                                    .color(color),
+                            );
+                        }
+                    });
+            });
+
+        self.show_log = open;
+    }
+
+    fn find_memory_pattern(&mut self) {
+        let pattern = match memory_viewer::parse_pattern(&self.memory_search) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                self.memory_status = format!("Error: {}", e);
+                return;
+            }
+        };
+        let start = self.memory_selected.map_or(0, |a| a.wrapping_add(1));
+        match memory_viewer::find_pattern(&self.gameboy.bus, &pattern, start) {
+            Some(address) => {
+                self.memory_selected = Some(address);
+                self.memory_scroll_to = Some(address);
+                self.memory_status = format!("Found at ${:04X}", address);
+            }
+            None => self.memory_status = "Not found".into(),
+        }
+    }
+
+    fn write_memory(&mut self, address: u16) {
+        match u8::from_str_radix(self.memory_edit.trim().trim_start_matches('$'), 16) {
+            Ok(value) => {
+                self.gameboy.bus.write_byte(address, value);
+                self.memory_status = format!("Wrote ${:02X} to ${:04X}", value, address);
+            }
+            Err(_) => self.memory_status = format!("Error: invalid byte '{}'", self.memory_edit),
+        }
+    }
+
+    // Hex view of the whole address space. Bytes written in the last second
+    // are highlighted, edits go through the bus like CPU writes.
+    fn show_memory_editor(&mut self, ctx: &egui::Context) {
+        const ROW_BYTES: u16 = 16;
+        const HIGHLIGHT_FRAMES: u32 = 60;
+
+        if self.gameboy.bus.write_history.is_none() {
+            self.gameboy.bus.write_history = Some(WriteHistory::new());
+        }
+        let mut open = self.show_memory;
+
+        egui::Window::new("🧮 Memory Editor")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.memory_goto)
+                            .hint_text("$C000")
+                            .desired_width(60.0),
+                    );
+                    if ui.button("Go to").clicked() {
+                        match debugger::parse_number(&self.memory_goto) {
+                            Ok(address) => {
+                                self.memory_selected = Some(address);
+                                self.memory_scroll_to = Some(address);
+                            }
+                            Err(e) => self.memory_status = format!("Error: {}", e),
+                        }
+                    }
+                    ui.separator();
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.memory_search)
+                            .hint_text("3E ?? CD")
+                            .desired_width(120.0),
+                    );
+                    if ui.button("Find next").clicked() {
+                        self.find_memory_pattern();
+                    }
+                });
+                if !self.memory_status.is_empty() {
+                    ui.label(&self.memory_status);
+                }
+
+                if let Some(address) = self.memory_selected {
+                    let value = self.gameboy.bus.peek(address);
+                    let region = memory_viewer::region(&self.gameboy.bus.memory, address);
+                    let mut details = format!(
+                        "${:04X} ({})  ${:02X}  {}",
+                        address,
+                        region.name(),
+                        value,
+                        value
+                    );
+                    if let Some(name) = memory_viewer::io_register_name(address) {
+                        details += &format!("  {}", name);
+                    }
+                    ui.label(details);
+                    if let Some(decoded) = memory_viewer::decode_io(address, value) {
+                        ui.label(decoded);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.memory_edit)
+                                .hint_text("FF")
+                                .desired_width(40.0),
+                        );
+                        if ui.button("Write").clicked() {
+                            self.write_memory(address);
+                        }
+                    });
+                }
+                ui.separator();
+
+                let row_height =
+                    ui.text_style_height(&egui::TextStyle::Monospace) + ui.spacing().item_spacing.y;
+                let mut scroll = egui::ScrollArea::vertical()
+                    .id_salt("memory")
+                    .max_height(400.0);
+                if let Some(address) = self.memory_scroll_to.take() {
+                    scroll =
+                        scroll.vertical_scroll_offset((address / ROW_BYTES) as f32 * row_height);
+                }
+
+                let rows = 0x10000 / ROW_BYTES as usize;
+                scroll.show_rows(ui, row_height, rows, |ui, range| {
+                    let bus = &self.gameboy.bus;
+                    for row in range {
+                        let base = row as u16 * ROW_BYTES;
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = 4.0;
+                            let region = memory_viewer::region(&bus.memory, base);
+                            ui.monospace(format!("{:<5}{:04X}", region.short_name(), base));
+
+                            let mut text = String::new();
+                            for address in base..base + ROW_BYTES {
+                                let value = bus.peek(address);
+                                text.push(if value.is_ascii_graphic() {
+                                    value as char
+                                } else {
+                                    '.'
+                                });
+
+                                let age = bus.write_history.as_ref().and_then(|h| h.age(address));
+                                let mut label =
+                                    egui::RichText::new(format!("{:02X}", value)).monospace();
+                                if let Some(age) = age.filter(|&age| age < HIGHLIGHT_FRAMES) {
+                                    let fade = 255 - (age * 160 / HIGHLIGHT_FRAMES) as u8;
+                                    label = label.color(egui::Color32::from_rgb(fade, fade, 0));
+                                }
+                                let selected = self.memory_selected == Some(address);
+                                let response = ui.selectable_label(selected, label);
+                                if response.clicked() {
+                                    self.memory_selected = Some(address);
+                                    self.memory_edit = format!("{:02X}", value);
+                                }
+                                if let Some(name) = memory_viewer::io_register_name(address) {
+                                    response.on_hover_text(name);
+                                }
+                            }
+                            ui.monospace(text);
+                        });
+                    }
+                });
+            });
+
+        self.show_memory = open;
+        if !open {
+            self.gameboy.bus.write_history = None;
+        }
+    }
+
+    fn palette_selector(
+        ui: &mut egui::Ui,
+        label: &str,
+        palettes: &[Palette],
+        selected: &mut Palette,
+    ) {
+        egui::ComboBox::from_label(label)
+            .selected_text(selected.name.clone())
+            .show_ui(ui, |ui| {
+                for palette in palettes {
+                    ui.selectable_value(selected, palette.clone(), palette.name.clone());
+                }
+            });
+    }
+
+    fn start_recording(&mut self) {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = format!("recording-{}.wav", timestamp);
+        let apu = &mut self.gameboy.bus.apu;
+
+        // Nominal rate: samples are resampled to it while rate control nudges
+        // the APU around it
+        match AudioRecorder::create(
+            &path,
+            self.rate_control.nominal_rate,
+            self.record_format,
+            self.record_stems,
+        ) {
+            Ok(recorder) => {
+                apu.set_stems_enabled(self.record_stems);
+                self.recorder = Some(recorder);
+                self.record_status.clear();
+            }
+            Err(e) => self.record_status = format!("Error: {}", e),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        self.gameboy.bus.apu.set_stems_enabled(false);
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().to_string();
+            self.record_status = match recorder.finish() {
+                Ok(()) => format!("Saved {}", path),
+                Err(e) => format!("Error: {}", e),
+            };
+        }
+    }
+
+    fn record_samples(&mut self, samples: &[(f32, f32)]) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+
+        recorder.set_input_rate(self.gameboy.bus.apu.sample_rate());
+        let mut result = recorder.write_samples(samples);
+        if let Some(stems) = self.gameboy.bus.apu.take_stem_samples() {
+            result = result.and_then(|_| recorder.write_stems(&stems));
+        }
+        if let Err(e) = result {
+            self.stop_recording();
+            self.record_status = format!("Error: {}", e);
+        }
+    }
+
+    fn connect_player2(&mut self) {
+        let rom_data = match std::fs::read(&self.link_rom_path) {
+            Ok(data) => data,
+            Err(e) => {
+                self.link_status = format!("Error: {}: {}", self.link_rom_path, e);
+                return;
+            }
+        };
+
+        let mut player2 = Gameboy::new();
+        if let Err(e) = player2.load_rom(&rom_data) {
+            self.link_status = format!("Error: {}", e);
+            return;
+        }
+        link::connect(&mut self.gameboy, &mut player2);
+        self.player2 = Some(player2);
+        self.link_status.clear();
+    }
+
+    fn disconnect_player2(&mut self) {
+        link::disconnect(&mut self.gameboy);
+        self.player2 = None;
+        self.player2_texture = None;
+    }
+
+    // Accepts in the background so the window stays responsive
+    fn host_network(&mut self) {
+        let listener = match NetListener::bind(&self.net_address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                self.link_status = format!("Error: {}", e);
+                return;
+            }
+        };
+
+        let address = listener.local_address();
+        match listener.accept_in_background() {
+            Ok(pending) => {
+                self.link_status = format!("Waiting for peer on {}", address);
+                self.net_pending = Some(pending);
+            }
+            Err(e) => self.link_status = format!("Error: {}", e),
+        }
+    }
+
+    fn join_network(&mut self) {
+        match NetLink::connect(&self.net_address) {
+            Ok(link) => self.attach_net_link(link),
+            Err(e) => self.link_status = format!("Error: {}", e),
+        }
+    }
+
+    fn poll_network(&mut self) {
+        let Some(pending) = &self.net_pending else {
+            return;
+        };
+        match pending.poll() {
+            Some(Ok(link)) => {
+                self.net_pending = None;
+                self.attach_net_link(link);
+            }
+            Some(Err(e)) => {
+                self.net_pending = None;
+                self.link_status = format!("Error: {}", e);
+            }
+            None => {}
+        }
+
+        // The peer waits on us without timing out while we are paused
+        if let Some(sync) = &mut self.net_sync {
+            sync.set_paused(self.paused);
+        }
+    }
+
+    fn attach_net_link(&mut self, link: NetLink) {
+        self.net_sync = Some(link.frame_sync());
+        self.gameboy.bus.set_serial_link(Box::new(link));
+        self.link_status = "Connected".to_string();
+    }
+
+    fn disconnect_network(&mut self) {
+        link::disconnect(&mut self.gameboy);
+        self.net_sync = None;
+        self.net_pending = None;
+        self.link_status.clear();
+    }
+
+    fn connect_printer(&mut self) {
+        let printer = Printer::new();
+        self.printer_jobs = Some(printer.jobs());
+        self.gameboy.bus.set_serial_link(Box::new(printer));
+        self.link_status.clear();
+    }
+
+    // Prints are saved next to recordings, in the working directory. The
+    // panel shows how saving went.
+    fn save_prints(&mut self) {
+        if let Some(jobs) = &self.printer_jobs {
+            save_new_jobs(jobs, ".");
+        }
+    }
+
+    fn disconnect_printer(&mut self) {
+        link::disconnect(&mut self.gameboy);
+        self.printer_jobs = None;
+    }
+
+    fn load_gbs(&mut self) {
+        self.disconnect_player2();
+        self.disconnect_network();
+        self.disconnect_printer();
+        match GbsFile::load(&self.gbs_path) {
+            Ok(file) => {
+                let mut player = GbsPlayer::new(file);
+                let song = player.song();
+                match player.start_song(&mut self.gameboy, song) {
+                    Ok(()) => {
+                        self.gbs_player = Some(player);
+                        self.gbs_status.clear();
+                    }
+                    Err(e) => self.gbs_status = format!("Error: {}", e),
+                }
+            }
+            Err(e) => self.gbs_status = format!("Error: {}", e),
+        }
+    }
+
+    fn select_gbs_song(&mut self, song: u8) {
+        let Some(player) = &mut self.gbs_player else {
+            return;
+        };
+        if let Err(e) = player.start_song(&mut self.gameboy, song) {
+            self.gbs_status = format!("Error: {}", e);
+        }
+    }
+
+    // Back to the cartridge, cold booted
+    fn eject_gbs(&mut self) {
+        self.gbs_player = None;
+        self.gbs_status.clear();
+
+        let mut gameboy = Gameboy::new();
+        if let Ok(rom_data) = std::fs::read(DEFAULT_ROM_PATH)
+            && let Err(e) = gameboy.load_rom(&rom_data)
+        {
+            self.gbs_status = format!("Error: {}", e);
+        }
+        gameboy
+            .bus
+            .apu
+            .set_sample_rate(self.rate_control.nominal_rate);
+        gameboy
+            .bus
+            .apu
+            .set_stems_enabled(self.recorder.as_ref().is_some_and(|r| r.records_stems()));
+        self.gameboy = gameboy;
     }
 
     fn handle_input(&mut self, ctx: &egui::Context) {
         let input = ctx.input(|i| i.clone());
 
         // Mapping des touches
-        let keys = [
-            (Key::W, JoypadButton::Up),
-            (Key::S, JoypadButton::Down),
-            (Key::A, JoypadButton::Left),
-            (Key::D, JoypadButton::Right),
-            (Key::Space, JoypadButton::A),
-            (Key::X, JoypadButton::B),
-            (Key::Enter, JoypadButton::Start),
-            (Key::C, JoypadButton::Select),
-        ];
-
-        for (key, button) in keys.iter() {
+        for (key, button) in PLAYER1_KEYS.iter() {
             if input.key_pressed(*key) {
                 self.gameboy.handle_input(*button, true);
             }
@@ -164,6 +1487,17 @@ impl GameBoyApp {
             }
         }
 
+        if let Some(player2) = &mut self.player2 {
+            for (key, button) in PLAYER2_KEYS.iter() {
+                if input.key_pressed(*key) {
+                    player2.handle_input(*button, true);
+                }
+                if input.key_released(*key) {
+                    player2.handle_input(*button, false);
+                }
+            }
+        }
+
         // Touches de contrôle
         if input.key_pressed(Key::P) {
             self.paused = !self.paused;
@@ -187,47 +1521,50 @@ impl GameBoyApp {
 }
 
 impl eframe::App for GameBoyApp {
+    // Finalize WAV headers of an ongoing recording
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.stop_recording();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.handle_input(ctx);
+        self.poll_network();
 
+        let mut new_frame = false;
         if !self.paused {
-            // Game Boy frame duration: ~16.74ms (59.7275 Hz)
-            const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
-            // Cap to avoid spiral of death if emulation falls behind
-            const MAX_CATCHUP_FRAMES: u32 = 4;
-
             let now = Instant::now();
             self.frame_accumulator += now - self.last_update;
             self.last_update = now;
 
             let mut frames_run = 0u32;
-            while self.frame_accumulator >= FRAME_DURATION && frames_run < MAX_CATCHUP_FRAMES {
-                self.gameboy.run_frame();
-                self.frame_accumulator -= FRAME_DURATION;
-                frames_run += 1;
-                self.update_fps();
-            }
-
-            // If still behind after max catchup, reset to avoid permanent lag
-            if self.frame_accumulator >= FRAME_DURATION {
+            if self.audio_sync && self.audio_stream.is_some() {
+                // The audio device clock paces emulation: run frames until
+                // the queue holds the target latency again
+                while self.audio_producer.len() < self.rate_control.target_fill
+                    && frames_run < MAX_CATCHUP_FRAMES
+                {
+                    self.run_frame();
+                    frames_run += 1;
+                }
                 self.frame_accumulator = Duration::ZERO;
-            }
+            } else {
+                while self.frame_accumulator >= FRAME_DURATION && frames_run < MAX_CATCHUP_FRAMES {
+                    self.run_frame();
+                    self.frame_accumulator -= FRAME_DURATION;
+                    frames_run += 1;
+                }
 
-            // Drain APU samples into shared audio buffer
-            if frames_run > 0 {
-                let samples = self.gameboy.take_audio_samples();
-                if !self.muted
-                    && !samples.is_empty()
-                    && let Ok(mut buf) = self.audio_buffer.lock()
-                {
-                    const MAX_BUFFER: usize = 44100;
-                    let available = MAX_BUFFER.saturating_sub(buf.len());
-                    buf.extend(samples.into_iter().take(available));
+                // If still behind after max catchup, reset to avoid permanent lag
+                if self.frame_accumulator >= FRAME_DURATION {
+                    self.frame_accumulator = Duration::ZERO;
                 }
             }
+
+            new_frame = frames_run > 0;
         }
+        self.save_prints();
 
-        self.update_texture(ctx);
+        self.update_texture(ctx, new_frame);
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
@@ -256,6 +1593,8 @@ impl eframe::App for GameBoyApp {
                     self.muted = !self.muted;
                 }
 
+                ui.checkbox(&mut self.audio_sync, "Sync to audio");
+
                 ui.separator();
                 ui.checkbox(&mut self.show_debug, "Debug");
             });
@@ -267,6 +1606,9 @@ impl eframe::App for GameBoyApp {
                 ui.horizontal(|ui| {
                     ui.vertical(|ui| {
                         ui.image((texture.id(), screen_size));
+                        if self.player2.is_some() {
+                            ui.label("Player 1: WASD, Space/X, Enter/C");
+                        }
 
                         // Contrôles de zoom
                         ui.horizontal(|ui| {
@@ -286,12 +1628,24 @@ impl eframe::App for GameBoyApp {
                         });
                     });
 
+                    if let Some(texture) = &self.player2_texture {
+                        ui.separator();
+                        ui.vertical(|ui| {
+                            ui.image((texture.id(), screen_size));
+                            ui.label("Player 2: arrows, L/K, O/I");
+                        });
+                    }
+
                     if self.show_debug {
                         ui.separator();
                         ui.vertical(|ui| {
                             ui.heading("Debug Info");
 
                             ui.label(format!("PC: 0x{:04X}", self.gameboy.cpu.pc));
+                            let bank = self.gameboy.bus.memory.rom_bank();
+                            if let Some(label) = self.symbols.describe(self.gameboy.cpu.pc, bank) {
+                                ui.label(label);
+                            }
                             ui.label(format!("SP: 0x{:04X}", self.gameboy.cpu.sp));
                             ui.separator();
 
@@ -328,6 +1682,7 @@ impl eframe::App for GameBoyApp {
                                     "OFF"
                                 }
                             ));
+                            ui.checkbox(&mut self.gameboy.bus.access_locking, "VRAM/OAM locking");
 
                             ui.separator();
                             ui.label("APU:");
@@ -363,6 +1718,26 @@ impl eframe::App for GameBoyApp {
                                     "OFF"
                                 },
                             ));
+                            ui.checkbox(
+                                &mut self.gameboy.bus.apu.high_pass_enabled,
+                                "High-pass filter",
+                            );
+                            ui.checkbox(&mut self.show_audio, "Channels");
+
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.show_debugger, "Debugger");
+                                ui.checkbox(&mut self.show_memory, "Memory");
+                                ui.checkbox(&mut self.show_profiler, "Profiler");
+                                ui.checkbox(&mut self.show_log, "Log");
+                            });
+
+                            ui.separator();
+                            ui.label("VRAM:");
+                            ui.horizontal(|ui| {
+                                ui.checkbox(&mut self.show_tiles, "Tiles");
+                                ui.checkbox(&mut self.show_tile_maps, "Maps");
+                                ui.checkbox(&mut self.show_oam, "OAM");
+                            });
 
                             ui.separator();
                             if ui.button("Print Terminal Screen").clicked() {
@@ -388,6 +1763,191 @@ impl eframe::App for GameBoyApp {
                 ui.label("• F1: Toggle Debug");
             });
 
+            ui.collapsing("🎨 Palette", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("All layers:");
+                    for palette in &self.palettes {
+                        if ui.button(&palette.name).clicked() {
+                            self.layer_palettes = LayerPalettes::uniform(palette);
+                        }
+                    }
+                });
+                Self::palette_selector(ui, "BG", &self.palettes, &mut self.layer_palettes.bg);
+                Self::palette_selector(ui, "OBJ0", &self.palettes, &mut self.layer_palettes.obj0);
+                Self::palette_selector(ui, "OBJ1", &self.palettes, &mut self.layer_palettes.obj1);
+                ui.label(format!("Custom palettes: {}", PALETTE_CONFIG_PATH));
+            });
+
+            ui.collapsing("🖥 Display", |ui| {
+                ui.add(
+                    egui::Slider::new(&mut self.post_processor.ghosting, 0.0..=0.9)
+                        .text("LCD ghosting"),
+                );
+                egui::ComboBox::from_label("Filter")
+                    .selected_text(self.post_processor.filter.name())
+                    .show_ui(ui, |ui| {
+                        for filter in Filter::ALL {
+                            ui.selectable_value(
+                                &mut self.post_processor.filter,
+                                filter,
+                                filter.name(),
+                            );
+                        }
+                    });
+            });
+
+            ui.collapsing("⏺ Recording", |ui| {
+                let recording = self.recorder.is_some();
+                ui.add_enabled_ui(!recording, |ui| {
+                    egui::ComboBox::from_label("Format")
+                        .selected_text(self.record_format.name())
+                        .show_ui(ui, |ui| {
+                            for format in WavFormat::ALL {
+                                ui.selectable_value(&mut self.record_format, format, format.name());
+                            }
+                        });
+                    ui.checkbox(&mut self.record_stems, "Per-channel stems");
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(if recording { "⏹ Stop" } else { "⏺ Record" })
+                        .clicked()
+                    {
+                        if recording {
+                            self.stop_recording();
+                        } else {
+                            self.start_recording();
+                        }
+                    }
+                    if let Some(recorder) = &self.recorder {
+                        ui.label(format!("{} ({:.1} s)", recorder.path(), recorder.seconds()));
+                    } else {
+                        ui.label(&self.record_status);
+                    }
+                });
+            });
+
+            ui.collapsing("🔗 Link Cable", |ui| {
+                if self.player2.is_some() {
+                    ui.label("Two Game Boys linked, running in lockstep");
+                    if ui.button("Disconnect").clicked() {
+                        self.disconnect_player2();
+                    }
+                } else if self.net_sync.is_some() || self.net_pending.is_some() {
+                    if ui.button("Disconnect").clicked() {
+                        self.disconnect_network();
+                    }
+                } else if let Some(jobs) = &self.printer_jobs {
+                    let jobs = jobs.lock().unwrap();
+                    ui.label(format!(
+                        "Game Boy Printer connected, {} print(s)",
+                        jobs.len()
+                    ));
+                    match jobs.last().and_then(|job| job.saved.as_ref()) {
+                        Some(Ok(path)) => {
+                            ui.label(format!("Last print: {}", path));
+                        }
+                        Some(Err(e)) => {
+                            ui.label(format!("Last print not saved: {}", e));
+                        }
+                        None => {}
+                    }
+                    drop(jobs);
+                    if ui.button("Disconnect").clicked() {
+                        self.disconnect_printer();
+                    }
+                } else {
+                    let gbs_loaded = self.gbs_player.is_some();
+                    ui.horizontal(|ui| {
+                        ui.label("Player 2 ROM");
+                        ui.text_edit_singleline(&mut self.link_rom_path);
+                    });
+                    if ui
+                        .add_enabled(!gbs_loaded, egui::Button::new("Connect second Game Boy"))
+                        .clicked()
+                    {
+                        self.connect_player2();
+                    }
+
+                    if ui
+                        .add_enabled(!gbs_loaded, egui::Button::new("Connect Game Boy Printer"))
+                        .clicked()
+                    {
+                        self.connect_printer();
+                    }
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("Network");
+                        ui.text_edit_singleline(&mut self.net_address)
+                            .on_hover_text("host:port or unix:/path/to/socket");
+                    });
+                    ui.add_enabled_ui(!gbs_loaded, |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.button("Host").clicked() {
+                                self.host_network();
+                            }
+                            if ui.button("Join").clicked() {
+                                self.join_network();
+                            }
+                        });
+                    });
+                }
+                if !self.link_status.is_empty() {
+                    ui.label(&self.link_status);
+                }
+            });
+
+            ui.collapsing("🎵 GBS Player", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.gbs_path);
+                    if ui.button("Load").clicked() {
+                        self.load_gbs();
+                    }
+                });
+
+                let mut selected = None;
+                let mut eject = false;
+                if let Some(player) = &self.gbs_player {
+                    let header = &player.file.header;
+                    ui.label(format!("Title: {}", header.title));
+                    ui.label(format!("Author: {}", header.author));
+                    ui.label(format!("Copyright: {}", header.copyright));
+                    ui.label(format!("Play rate: {:.2} Hz", player.file.play_rate_hz()));
+
+                    let song = player.song();
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(song > 0, egui::Button::new("⏮")).clicked() {
+                            selected = Some(song - 1);
+                        }
+                        ui.label(format!("Track {}/{}", song + 1, player.song_count()));
+                        if ui
+                            .add_enabled(song + 1 < player.song_count(), egui::Button::new("⏭"))
+                            .clicked()
+                        {
+                            selected = Some(song + 1);
+                        }
+                        if ui.button("↺ Restart").clicked() {
+                            selected = Some(song);
+                        }
+                        if ui.button("⏏ Eject").clicked() {
+                            eject = true;
+                        }
+                    });
+                }
+                if let Some(song) = selected {
+                    self.select_gbs_song(song);
+                }
+                if eject {
+                    self.eject_gbs();
+                }
+                if !self.gbs_status.is_empty() {
+                    ui.label(&self.gbs_status);
+                }
+            });
+
             ui.collapsing("🔧 Debug Actions", |ui| {
                 if ui.button("Print Full Screen to Terminal").clicked() {
                     println!("\n=== FULL SCREEN DEBUG ===");
@@ -435,7 +1995,10 @@ impl eframe::App for GameBoyApp {
                 if ui.button("Force 60 Frames").clicked() {
                     println!("🔄 Forcing 60 frames...");
                     for i in 0..60 {
-                        self.gameboy.run_frame();
+                        if let Err(reason) = self.gameboy.run_frame() {
+                            self.on_stop(reason);
+                            break;
+                        }
                         if i % 10 == 0 {
                             println!("Frame {}/60", i);
                         }
@@ -445,6 +2008,28 @@ impl eframe::App for GameBoyApp {
             });
         });
 
+        self.show_vram_viewers(ctx);
+        if self.show_audio {
+            self.show_audio_channels(ctx);
+        }
+        if self.show_debugger {
+            self.show_debugger_window(ctx);
+        }
+        if self.show_profiler {
+            self.show_profiler_window(ctx);
+        }
+        if self.show_log {
+            self.show_log_window(ctx);
+        } else {
+            logger::capture_console(false);
+        }
+        logger::flush();
+        if self.show_memory {
+            self.show_memory_editor(ctx);
+        } else if self.gameboy.bus.write_history.is_some() {
+            self.gameboy.bus.write_history = None;
+        }
+
         ctx.request_repaint();
     }
 }
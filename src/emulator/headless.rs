@@ -0,0 +1,464 @@
+use crate::emulator::{
+    gameboy::Gameboy,
+    gbs::{GbsFile, GbsPlayer},
+    gdb_stub::{GdbServer, SessionEnd},
+    net_link::{FrameSync, NetLink, NetListener},
+    palette::{LayerPalettes, PALETTE_CONFIG_PATH, Palette},
+    postprocess::{Filter, Frame, PostProcessor},
+    printer::{PrintQueue, Printer, save_new_jobs},
+    profiler::{self, Profiler},
+    recorder::{AudioRecorder, WavFormat},
+    symbols::{self, SymbolTable},
+    trace::TraceLog,
+};
+use crate::logger;
+
+pub const DEFAULT_ROM_PATH: &str = "resources/tetris.gb";
+
+const USAGE: &str = "\
+Usage: dmg-emu [--rom PATH] [--frames N] [--screenshot OUT.png]
+               [--filter none|dotmatrix|scanlines|scale2x|scale3x]
+               [--ghosting 0.0-1.0] [--palette NAME]
+               [--record-audio OUT.wav] [--audio-format pcm16|float] [--stems]
+               [--link-listen ADDR | --link-connect ADDR | --printer DIR]
+               [--gdb ADDR] [--trace OUT.txt] [--symbols FILE.sym]
+               [--profile OUT.folded] [--log FILTER] [--log-file OUT.log]
+       dmg-emu --gbs FILE.gbs [--track N] [--frames N] --record-audio OUT.wav
+               [--audio-format pcm16|float] [--stems]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessOptions {
+    pub rom_path: String,
+    pub frames: u32,
+    pub screenshot: Option<String>,
+    pub filter: Filter,
+    pub ghosting: f32,
+    pub palette: Option<String>,
+    pub record_audio: Option<String>,
+    pub audio_format: WavFormat,
+    pub stems: bool,
+    pub gbs: Option<String>,
+    pub track: Option<u8>, // 1-based, the file's first song when unset
+    // Serial link to another process: "host:port" or "unix:/path"
+    pub link_listen: Option<String>,
+    pub link_connect: Option<String>,
+    // Game Boy Printer on the serial port, prints saved into this directory
+    pub printer: Option<String>,
+    // Waits for a GDB client on this TCP address before running
+    pub gdb: Option<String>,
+    // Instruction trace; labels come from --symbols or the ROM's .sym file
+    pub trace: Option<String>,
+    pub symbols: Option<String>,
+    // Cycle profile as folded stacks, for flamegraph tools
+    pub profile: Option<String>,
+    // Log levels such as "warn,ppu=debug", and a file to log to instead of
+    // stderr
+    pub log: Option<String>,
+    pub log_file: Option<String>,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            rom_path: DEFAULT_ROM_PATH.to_string(),
+            frames: 60,
+            screenshot: None,
+            filter: Filter::None,
+            ghosting: 0.0,
+            palette: None,
+            record_audio: None,
+            audio_format: WavFormat::Pcm16,
+            stems: false,
+            gbs: None,
+            track: None,
+            link_listen: None,
+            link_connect: None,
+            printer: None,
+            gdb: None,
+            trace: None,
+            symbols: None,
+            profile: None,
+            log: None,
+            log_file: None,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    // Returns None when no headless output is requested, so the GUI starts
+    pub fn parse(args: &[String]) -> Result<Option<Self>, String> {
+        if args.is_empty() {
+            return Ok(None);
+        }
+
+        let mut options = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or(format!("missing value for {}\n{}", arg, USAGE))
+            };
+
+            match arg.as_str() {
+                "--rom" => options.rom_path = value()?,
+                "--frames" => {
+                    options.frames = value()?
+                        .parse()
+                        .map_err(|_| format!("invalid frame count\n{}", USAGE))?
+                }
+                "--screenshot" => options.screenshot = Some(value()?),
+                "--filter" => {
+                    let name = value()?;
+                    options.filter = Filter::from_name(&name)
+                        .ok_or(format!("unknown filter '{}'\n{}", name, USAGE))?;
+                }
+                "--ghosting" => {
+                    options.ghosting = value()?
+                        .parse()
+                        .map_err(|_| format!("invalid ghosting amount\n{}", USAGE))?
+                }
+                "--palette" => options.palette = Some(value()?),
+                "--record-audio" => options.record_audio = Some(value()?),
+                "--audio-format" => {
+                    let name = value()?;
+                    options.audio_format = WavFormat::from_name(&name)
+                        .ok_or(format!("unknown audio format '{}'\n{}", name, USAGE))?;
+                }
+                "--stems" => options.stems = true,
+                "--gbs" => options.gbs = Some(value()?),
+                "--link-listen" => options.link_listen = Some(value()?),
+                "--link-connect" => options.link_connect = Some(value()?),
+                "--printer" => options.printer = Some(value()?),
+                "--gdb" => options.gdb = Some(value()?),
+                "--trace" => options.trace = Some(value()?),
+                "--symbols" => options.symbols = Some(value()?),
+                "--profile" => options.profile = Some(value()?),
+                "--log" => {
+                    let filter = value()?;
+                    logger::parse_filter(&filter).map_err(|e| format!("{}\n{}", e, USAGE))?;
+                    options.log = Some(filter);
+                }
+                "--log-file" => options.log_file = Some(value()?),
+                "--track" => {
+                    let track: u8 = value()?
+                        .parse()
+                        .map_err(|_| format!("invalid track number\n{}", USAGE))?;
+                    if track == 0 {
+                        return Err(format!("track numbers start at 1\n{}", USAGE));
+                    }
+                    options.track = Some(track);
+                }
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("unknown argument '{}'\n{}", arg, USAGE)),
+            }
+        }
+
+        if options.screenshot.is_none()
+            && options.record_audio.is_none()
+            && options.printer.is_none()
+            && options.gdb.is_none()
+            && options.trace.is_none()
+            && options.profile.is_none()
+        {
+            return Err(format!("nothing to output\n{}", USAGE));
+        }
+        if options.gbs.is_some() && options.screenshot.is_some() {
+            return Err(format!(
+                "--screenshot is not available with --gbs\n{}",
+                USAGE
+            ));
+        }
+        let serial_devices = [
+            &options.link_listen,
+            &options.link_connect,
+            &options.printer,
+        ];
+        let linked = serial_devices.iter().any(|device| device.is_some());
+        if serial_devices
+            .iter()
+            .filter(|device| device.is_some())
+            .count()
+            > 1
+        {
+            return Err(format!(
+                "--link-listen, --link-connect and --printer are exclusive\n{}",
+                USAGE
+            ));
+        }
+        if linked && options.gbs.is_some() {
+            return Err(format!(
+                "the link cable is not available with --gbs\n{}",
+                USAGE
+            ));
+        }
+        if options.gbs.is_some()
+            && (options.gdb.is_some() || options.trace.is_some() || options.profile.is_some())
+        {
+            return Err(format!(
+                "--gdb, --trace and --profile are not available with --gbs\n{}",
+                USAGE
+            ));
+        }
+        if options.gbs.is_none() && options.track.is_some() {
+            return Err(format!("--track needs --gbs\n{}", USAGE));
+        }
+
+        Ok(Some(options))
+    }
+
+    // An explicit --symbols file must load, the one next to the ROM is optional
+    fn symbols(&self) -> Result<SymbolTable, String> {
+        if let Some(path) = &self.symbols {
+            return SymbolTable::load(path);
+        }
+        let path = symbols::symbol_path(&self.rom_path);
+        if std::path::Path::new(&path).exists() {
+            SymbolTable::load(&path)
+        } else {
+            Ok(SymbolTable::new())
+        }
+    }
+
+    fn layer_palettes(&self) -> Result<LayerPalettes, String> {
+        let Some(name) = &self.palette else {
+            return Ok(LayerPalettes::default());
+        };
+
+        let mut palettes = Palette::presets();
+        if std::path::Path::new(PALETTE_CONFIG_PATH).exists() {
+            palettes.extend(Palette::load_config(PALETTE_CONFIG_PATH)?);
+        }
+
+        palettes
+            .iter()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+            .map(LayerPalettes::uniform)
+            .ok_or(format!("unknown palette '{}'", name))
+    }
+}
+
+fn create_recorder(
+    options: &HeadlessOptions,
+    gameboy: &mut Gameboy,
+) -> Result<Option<AudioRecorder>, String> {
+    let Some(path) = &options.record_audio else {
+        return Ok(None);
+    };
+
+    gameboy.bus.apu.set_stems_enabled(options.stems);
+    AudioRecorder::create(
+        path,
+        gameboy.bus.apu.sample_rate(),
+        options.audio_format,
+        options.stems,
+    )
+    .map(Some)
+}
+
+fn record_frame(recorder: &mut Option<AudioRecorder>, gameboy: &mut Gameboy) -> Result<(), String> {
+    let samples = gameboy.take_audio_samples();
+    if let Some(recorder) = recorder {
+        recorder.write_samples(&samples)?;
+        if let Some(stems) = gameboy.bus.apu.take_stem_samples() {
+            recorder.write_stems(&stems)?;
+        }
+    }
+    Ok(())
+}
+
+fn finish_recording(recorder: Option<AudioRecorder>) -> Result<(), String> {
+    if let Some(recorder) = recorder {
+        let path = recorder.path().to_string();
+        let seconds = recorder.seconds();
+        recorder.finish()?;
+        println!("Audio ({:.1} s) saved to {}", seconds, path);
+    }
+    Ok(())
+}
+
+// Plugs the printer in, if asked, and returns its job list
+fn connect_printer(
+    options: &HeadlessOptions,
+    gameboy: &mut Gameboy,
+) -> Result<Option<PrintQueue>, String> {
+    let Some(dir) = &options.printer else {
+        return Ok(None);
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    let printer = Printer::new();
+    let jobs = printer.jobs();
+    gameboy.bus.set_serial_link(Box::new(printer));
+    Ok(Some(jobs))
+}
+
+fn save_prints(jobs: &PrintQueue, dir: &str) -> Result<(), String> {
+    for result in save_new_jobs(jobs, dir) {
+        let path = result.map_err(|e| format!("Failed to save print: {}", e))?;
+        println!("Print saved to {}", path);
+    }
+    Ok(())
+}
+
+// Plugs the network link in, if any, and returns its frame sync
+fn connect_link(
+    options: &HeadlessOptions,
+    gameboy: &mut Gameboy,
+) -> Result<Option<FrameSync>, String> {
+    let link = if let Some(address) = &options.link_listen {
+        let listener = NetListener::bind(address)?;
+        println!("Waiting for link peer on {}", listener.local_address());
+        listener.accept()?
+    } else if let Some(address) = &options.link_connect {
+        NetLink::connect(address)?
+    } else {
+        return Ok(None);
+    };
+
+    println!("Link cable connected");
+    let sync = link.frame_sync();
+    gameboy.bus.set_serial_link(Box::new(link));
+    Ok(Some(sync))
+}
+
+fn run_gbs(options: &HeadlessOptions, path: &str) -> Result<(), String> {
+    let mut player = GbsPlayer::new(GbsFile::load(path)?);
+    let song = match options.track {
+        Some(track) => track - 1,
+        None => player.song(),
+    };
+
+    let mut gameboy = Gameboy::new();
+    let mut recorder = create_recorder(options, &mut gameboy)?;
+    player.start_song(&mut gameboy, song)?;
+
+    let header = &player.file.header;
+    println!(
+        "Playing \"{}\" by {}, track {}/{}",
+        header.title,
+        header.author,
+        song + 1,
+        header.song_count
+    );
+
+    for _ in 0..options.frames {
+        player.run_frame(&mut gameboy)?;
+        record_frame(&mut recorder, &mut gameboy)?;
+    }
+
+    finish_recording(recorder)
+}
+
+fn print_profile(profile: &Profiler, symbols: &SymbolTable) {
+    const TOP_FUNCTIONS: usize = 10;
+
+    let total = profile.total_cycles().max(1) as f64;
+    println!("{:>7} {:>7} {:>8}  Function", "Self", "Total", "Calls");
+    for entry in profile.flat().iter().take(TOP_FUNCTIONS) {
+        println!(
+            "{:>6.1}% {:>6.1}% {:>8}  {}",
+            entry.self_cycles as f64 * 100.0 / total,
+            entry.total_cycles as f64 * 100.0 / total,
+            entry.calls,
+            profiler::function_name(entry.function, symbols)
+        );
+    }
+}
+
+pub fn run(options: &HeadlessOptions) -> Result<(), String> {
+    if let Some(filter) = &options.log {
+        logger::set_filter(filter)?;
+    }
+    if let Some(path) = &options.log_file {
+        logger::log_to_file(path)?;
+        logger::log_to_stderr(false);
+    }
+
+    let result = match &options.gbs {
+        Some(path) => run_gbs(options, path),
+        None => run_rom(options),
+    };
+    logger::close_file();
+    result
+}
+
+fn run_rom(options: &HeadlessOptions) -> Result<(), String> {
+    let rom_data =
+        std::fs::read(&options.rom_path).map_err(|e| format!("{}: {}", options.rom_path, e))?;
+
+    let mut gameboy = Gameboy::new();
+    gameboy.load_rom(&rom_data)?;
+
+    let palettes = options.layer_palettes()?;
+    let mut post_processor = PostProcessor::new(options.ghosting, options.filter);
+    let mut output = None;
+
+    let mut recorder = create_recorder(options, &mut gameboy)?;
+    let print_jobs = connect_printer(options, &mut gameboy)?;
+    let mut frame_sync = connect_link(options, &mut gameboy)?;
+    let symbols = options.symbols()?;
+    let mut trace = options.trace.as_deref().map(TraceLog::create).transpose()?;
+
+    if let Some(address) = &options.gdb {
+        let server = GdbServer::bind(address)?;
+        println!("Waiting for GDB on {}", server.local_address());
+        let end = server.accept()?.serve(&mut gameboy)?;
+        println!("GDB session ended");
+        if end == SessionEnd::Killed {
+            return Ok(());
+        }
+    }
+
+    if options.profile.is_some() {
+        gameboy.profiler = Some(Profiler::start(&gameboy.cpu.call_stack));
+    }
+
+    for _ in 0..options.frames {
+        let result = match &mut trace {
+            Some(trace) => trace.run_frame(&mut gameboy, &symbols)?,
+            None => gameboy.run_frame().map(|_| ()),
+        };
+        // Outputs are still written, with the state it stopped in
+        if let Err(reason) = result {
+            eprintln!("Emulation stopped: {}", reason);
+            break;
+        }
+        if let Some(sync) = &mut frame_sync {
+            sync.end_frame()?;
+        }
+        if let (Some(jobs), Some(dir)) = (&print_jobs, &options.printer) {
+            save_prints(jobs, dir)?;
+        }
+        record_frame(&mut recorder, &mut gameboy)?;
+        output = Some(post_processor.process(&Frame::from_ppu(&gameboy.bus.ppu, &palettes)));
+    }
+
+    finish_recording(recorder)?;
+
+    if let (Some(trace), Some(path)) = (trace, &options.trace) {
+        let lines = trace.lines();
+        trace.finish()?;
+        println!("Trace ({} instructions) saved to {}", lines, path);
+    }
+
+    if let (Some(profile), Some(path)) = (&gameboy.profiler, &options.profile) {
+        std::fs::write(path, profile.folded_stacks(&symbols))
+            .map_err(|e| format!("{}: {}", path, e))?;
+        println!("Profile saved to {}", path);
+        print_profile(profile, &symbols);
+    }
+
+    if let Some(path) = &options.screenshot {
+        let frame = match output {
+            Some(frame) => frame,
+            None => options
+                .filter
+                .apply(&Frame::from_ppu(&gameboy.bus.ppu, &palettes)),
+        };
+        frame.save_png(path)?;
+        println!("Screenshot saved to {}", path);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use crate::emulator::{
+    gameboy::{Gameboy, MAX_FRAME_STEPS, StopReason},
+    serial::{Disconnected, SerialLink},
+};
+use std::sync::{Arc, Mutex};
+
+// State of the cable between the two ports
+#[derive(Debug, Default)]
+struct Wire {
+    // SB of a side waiting on the external clock
+    waiting: [Option<u8>; 2],
+    // Byte clocked in by the master, not yet picked up by the waiting side
+    delivered: [Option<u8>; 2],
+}
+
+// One end of a cable connecting two serial ports in the same process
+#[derive(Debug)]
+pub struct LinkCable {
+    wire: Arc<Mutex<Wire>>,
+    side: usize,
+}
+
+pub fn link_cable() -> (LinkCable, LinkCable) {
+    let wire = Arc::new(Mutex::new(Wire::default()));
+    (
+        LinkCable {
+            wire: Arc::clone(&wire),
+            side: 0,
+        },
+        LinkCable { wire, side: 1 },
+    )
+}
+
+impl SerialLink for LinkCable {
+    fn transfer(&mut self, outgoing: u8) -> u8 {
+        let mut wire = self.wire.lock().unwrap();
+        let other = 1 - self.side;
+
+        // Nobody listening: the line stays high
+        match wire.waiting[other].take() {
+            Some(incoming) => {
+                wire.delivered[other] = Some(outgoing);
+                incoming
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn receive(&mut self, outgoing: u8) -> Option<u8> {
+        let mut wire = self.wire.lock().unwrap();
+        let incoming = wire.delivered[self.side].take();
+        wire.waiting[self.side] = if incoming.is_some() {
+            None
+        } else {
+            Some(outgoing)
+        };
+        incoming
+    }
+
+    fn cancel(&mut self) {
+        self.wire.lock().unwrap().waiting[self.side] = None;
+    }
+}
+
+pub fn connect(first: &mut Gameboy, second: &mut Gameboy) {
+    let (a, b) = link_cable();
+    first.bus.set_serial_link(Box::new(a));
+    second.bus.set_serial_link(Box::new(b));
+}
+
+pub fn disconnect(gameboy: &mut Gameboy) {
+    gameboy.bus.set_serial_link(Box::new(Disconnected));
+}
+
+// Runs both Gameboys until each has reached VBlank. The one whose clock is
+// behind always executes next, so a transfer started by either side sees the
+// other one within an instruction of the same time.
+pub fn run_frame(first: &mut Gameboy, second: &mut Gameboy) -> Result<(), StopReason> {
+    let mut done = [false; 2];
+
+    // Gameboy::run_frame's cap for each of them
+    for _ in 0..2 * MAX_FRAME_STEPS {
+        let first_next = !done[0] && (done[1] || first.total_cycles <= second.total_cycles);
+        if first_next {
+            done[0] = first.step()?;
+        } else {
+            done[1] = second.step()?;
+        }
+
+        if done[0] && done[1] {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,26 @@
+use std::fmt::Debug;
+
+// Bank switching hardware answering writes to 0x0000-0x7FFF. Without one
+// the ROM area is plain writable memory.
+pub trait Mapper: Debug + Send {
+    // Contents of the 16 KiB bank to show at 0x4000 when the write switches it
+    fn write(&mut self, address: u16, value: u8) -> Option<&[u8]>;
+    fn rom_bank(&self) -> usize;
+    fn clone_box(&self) -> Box<dyn Mapper>;
+}
+
+impl Clone for Box<dyn Mapper> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Memory {
     data: [u8; 0x10000],
     vram: [u8; 0x2000],
     oam: [u8; 0xA0],
+    mapper: Option<Box<dyn Mapper>>,
 }
 
 impl Memory {
@@ -11,9 +29,20 @@ impl Memory {
             data: [0x00; 0x10000],
             vram: [0; 0x2000],
             oam: [0; 0xA0],
+            mapper: None,
         }
     }
 
+    // Bank mapped at 0x4000, 1 without a mapper
+    pub fn rom_bank(&self) -> usize {
+        self.mapper.as_ref().map_or(1, |mapper| mapper.rom_bank())
+    }
+
+    // Removed by the next load_rom
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) {
+        self.mapper = Some(mapper);
+    }
+
     pub fn read_byte(&self, address: u16) -> u8 {
         match address {
             0x8000..=0x9FFF => {
@@ -38,6 +67,13 @@ impl Memory {
                 let oam_addr = (address - 0xFE00) as usize;
                 self.oam[oam_addr] = value;
             }
+            0x0000..=0x7FFF if self.mapper.is_some() => {
+                if let Some(mapper) = &mut self.mapper
+                    && let Some(bank) = mapper.write(address, value)
+                {
+                    self.data[0x4000..0x8000].copy_from_slice(bank);
+                }
+            }
             _ => self.data[address as usize] = value,
         }
     }
@@ -70,6 +106,7 @@ impl Memory {
         let copy_size = std::cmp::min(rom_data.len(), 0x8000);
         // self.data[0x0000..0x8000].fill(0xFF);
         self.data[0x0000..copy_size].copy_from_slice(&rom_data[0..copy_size]);
+        self.mapper = None;
 
         Ok(())
     }
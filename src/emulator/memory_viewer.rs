@@ -0,0 +1,346 @@
+use crate::emulator::{bus::Bus, memory::Memory};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    RomBank(usize), // 0 at 0x0000-0x3FFF, the switchable bank above
+    Vram,
+    ExternalRam,
+    Wram,
+    Echo, // mirror of 0xC000-0xDDFF
+    Oam,
+    Unusable,
+    Io,
+    Hram,
+    InterruptEnable,
+}
+
+impl Region {
+    pub fn name(&self) -> String {
+        match self {
+            Region::RomBank(bank) => format!("ROM bank {}", bank),
+            Region::Vram => "VRAM".into(),
+            Region::ExternalRam => "External RAM".into(),
+            Region::Wram => "WRAM".into(),
+            Region::Echo => "Echo RAM".into(),
+            Region::Oam => "OAM".into(),
+            Region::Unusable => "Unusable".into(),
+            Region::Io => "I/O".into(),
+            Region::Hram => "HRAM".into(),
+            Region::InterruptEnable => "IE".into(),
+        }
+    }
+
+    // Fits the address column of the hex view
+    pub fn short_name(&self) -> String {
+        match self {
+            Region::RomBank(bank) => format!("ROM{}", bank),
+            Region::ExternalRam => "SRAM".into(),
+            Region::Echo => "ECHO".into(),
+            Region::Unusable => "----".into(),
+            _ => self.name(),
+        }
+    }
+}
+
+pub fn region(memory: &Memory, address: u16) -> Region {
+    match address {
+        0x0000..=0x3FFF => Region::RomBank(0),
+        0x4000..=0x7FFF => Region::RomBank(memory.rom_bank()),
+        0x8000..=0x9FFF => Region::Vram,
+        0xA000..=0xBFFF => Region::ExternalRam,
+        0xC000..=0xDFFF => Region::Wram,
+        0xE000..=0xFDFF => Region::Echo,
+        0xFE00..=0xFE9F => Region::Oam,
+        0xFEA0..=0xFEFF => Region::Unusable,
+        0xFF00..=0xFF7F => Region::Io,
+        0xFF80..=0xFFFE => Region::Hram,
+        0xFFFF => Region::InterruptEnable,
+    }
+}
+
+pub fn io_register_name(address: u16) -> Option<&'static str> {
+    let name = match address {
+        0xFF00 => "P1",
+        0xFF01 => "SB",
+        0xFF02 => "SC",
+        0xFF04 => "DIV",
+        0xFF05 => "TIMA",
+        0xFF06 => "TMA",
+        0xFF07 => "TAC",
+        0xFF0F => "IF",
+        0xFF10 => "NR10",
+        0xFF11 => "NR11",
+        0xFF12 => "NR12",
+        0xFF13 => "NR13",
+        0xFF14 => "NR14",
+        0xFF16 => "NR21",
+        0xFF17 => "NR22",
+        0xFF18 => "NR23",
+        0xFF19 => "NR24",
+        0xFF1A => "NR30",
+        0xFF1B => "NR31",
+        0xFF1C => "NR32",
+        0xFF1D => "NR33",
+        0xFF1E => "NR34",
+        0xFF20 => "NR41",
+        0xFF21 => "NR42",
+        0xFF22 => "NR43",
+        0xFF23 => "NR44",
+        0xFF24 => "NR50",
+        0xFF25 => "NR51",
+        0xFF26 => "NR52",
+        0xFF30..=0xFF3F => "WAVE",
+        0xFF40 => "LCDC",
+        0xFF41 => "STAT",
+        0xFF42 => "SCY",
+        0xFF43 => "SCX",
+        0xFF44 => "LY",
+        0xFF45 => "LYC",
+        0xFF46 => "DMA",
+        0xFF47 => "BGP",
+        0xFF48 => "OBP0",
+        0xFF49 => "OBP1",
+        0xFF4A => "WY",
+        0xFF4B => "WX",
+        0xFF50 => "BOOT",
+        0xFFFF => "IE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn flags(value: u8, names: &[(u8, &str)]) -> String {
+    let set: Vec<&str> = names
+        .iter()
+        .filter(|(bit, _)| value & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    if set.is_empty() {
+        "none".into()
+    } else {
+        set.join(" ")
+    }
+}
+
+fn on_off(value: u8, bit: u8) -> &'static str {
+    if value & (1 << bit) != 0 { "on" } else { "off" }
+}
+
+fn envelope(value: u8) -> String {
+    format!(
+        "volume {}, {}, pace {}",
+        value >> 4,
+        if value & 0x08 != 0 {
+            "increase"
+        } else {
+            "decrease"
+        },
+        value & 0x07
+    )
+}
+
+fn length_duty(value: u8) -> String {
+    const DUTIES: [&str; 4] = ["12.5%", "25%", "50%", "75%"];
+    format!(
+        "duty {}, length {}",
+        DUTIES[(value >> 6) as usize],
+        value & 0x3F
+    )
+}
+
+fn period_high(value: u8) -> String {
+    format!(
+        "{}length {}, period high {}",
+        if value & 0x80 != 0 { "trigger, " } else { "" },
+        on_off(value, 6),
+        value & 0x07
+    )
+}
+
+// What the bits of an I/O register currently mean
+pub fn decode_io(address: u16, value: u8) -> Option<String> {
+    const INTERRUPTS: [(u8, &str); 5] = [
+        (0, "VBlank"),
+        (1, "STAT"),
+        (2, "Timer"),
+        (3, "Serial"),
+        (4, "Joypad"),
+    ];
+
+    let text = match address {
+        0xFF00 => {
+            // Low bits read 0 when pressed, for whichever group is selected
+            let (group, names) = match value & 0x30 {
+                0x10 => ("buttons", [(0, "A"), (1, "B"), (2, "Select"), (3, "Start")]),
+                0x20 => ("d-pad", [(0, "Right"), (1, "Left"), (2, "Up"), (3, "Down")]),
+                0x00 => ("both", [(0, "0"), (1, "1"), (2, "2"), (3, "3")]),
+                _ => ("none", [(0, "0"), (1, "1"), (2, "2"), (3, "3")]),
+            };
+            format!(
+                "select {}, pressed: {}",
+                group,
+                flags(!value & 0x0F, &names)
+            )
+        }
+        0xFF02 => format!(
+            "transfer {}, {} clock",
+            on_off(value, 7),
+            if value & 0x01 != 0 {
+                "internal"
+            } else {
+                "external"
+            }
+        ),
+        0xFF07 => {
+            const FREQUENCIES: [u32; 4] = [4096, 262144, 65536, 16384];
+            format!(
+                "timer {}, {} Hz",
+                on_off(value, 2),
+                FREQUENCIES[(value & 0x03) as usize]
+            )
+        }
+        0xFF0F | 0xFFFF => flags(value, &INTERRUPTS),
+        0xFF10 => format!(
+            "pace {}, {}, step {}",
+            (value >> 4) & 0x07,
+            if value & 0x08 != 0 {
+                "decrease"
+            } else {
+                "increase"
+            },
+            value & 0x07
+        ),
+        0xFF11 | 0xFF16 => length_duty(value),
+        0xFF12 | 0xFF17 | 0xFF21 => envelope(value),
+        0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => period_high(value),
+        0xFF1A => format!("DAC {}", on_off(value, 7)),
+        0xFF1C => {
+            const LEVELS: [&str; 4] = ["mute", "100%", "50%", "25%"];
+            format!("level {}", LEVELS[((value >> 5) & 0x03) as usize])
+        }
+        0xFF22 => format!(
+            "shift {}, {}-bit, divider {}",
+            value >> 4,
+            if value & 0x08 != 0 { 7 } else { 15 },
+            value & 0x07
+        ),
+        0xFF24 => format!("left {}, right {}", (value >> 4) & 0x07, value & 0x07),
+        0xFF25 => format!(
+            "left: {}, right: {}",
+            flags(value >> 4, &[(0, "1"), (1, "2"), (2, "3"), (3, "4")]),
+            flags(value & 0x0F, &[(0, "1"), (1, "2"), (2, "3"), (3, "4")])
+        ),
+        0xFF26 => format!(
+            "power {}, active: {}",
+            on_off(value, 7),
+            flags(value & 0x0F, &[(0, "1"), (1, "2"), (2, "3"), (3, "4")])
+        ),
+        0xFF40 => format!(
+            "LCD {}, window map {:04X}, window {}, tiles {:04X}, BG map {:04X}, OBJ 8x{}, OBJ {}, BG {}",
+            on_off(value, 7),
+            if value & 0x40 != 0 { 0x9C00 } else { 0x9800 },
+            on_off(value, 5),
+            if value & 0x10 != 0 { 0x8000 } else { 0x8800 },
+            if value & 0x08 != 0 { 0x9C00 } else { 0x9800 },
+            if value & 0x04 != 0 { 16 } else { 8 },
+            on_off(value, 1),
+            on_off(value, 0)
+        ),
+        0xFF41 => {
+            const MODES: [&str; 4] = ["HBlank", "VBlank", "OAM scan", "drawing"];
+            format!(
+                "mode {} ({}), LYC=LY {}, interrupts: {}",
+                value & 0x03,
+                MODES[(value & 0x03) as usize],
+                on_off(value, 2),
+                flags(
+                    value,
+                    &[(3, "HBlank"), (4, "VBlank"), (5, "OAM"), (6, "LYC")]
+                )
+            )
+        }
+        0xFF46 => format!("source {:02X}00", value),
+        0xFF47..=0xFF49 => format!(
+            "shades {} {} {} {}",
+            value & 0x03,
+            (value >> 2) & 0x03,
+            (value >> 4) & 0x03,
+            value >> 6
+        ),
+        _ => return None,
+    };
+    Some(text)
+}
+
+// "3E ?? CD", spaces optional; "??" matches any byte
+pub fn parse_pattern(text: &str) -> Result<Vec<Option<u8>>, String> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return Err(format!("invalid pattern '{}'", text));
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            if pair == "??" {
+                Ok(None)
+            } else {
+                u8::from_str_radix(&pair, 16)
+                    .map(Some)
+                    .map_err(|_| format!("invalid byte '{}'", pair))
+            }
+        })
+        .collect()
+}
+
+// First match at or after `start`, wrapping around the address space
+pub fn find_pattern(bus: &Bus, pattern: &[Option<u8>], start: u16) -> Option<u16> {
+    (0..=0xFFFFu16)
+        .map(|offset| start.wrapping_add(offset))
+        .find(|&address| {
+            pattern
+                .iter()
+                .enumerate()
+                .all(|(i, byte)| byte.is_none_or(|b| bus.peek(address.wrapping_add(i as u16)) == b))
+        })
+}
+
+// Frame of the last write to each address, kept by the Bus while the memory
+// editor is open
+#[derive(Debug, Clone)]
+pub struct WriteHistory {
+    stamps: Vec<u32>, // 0: never written
+    frame: u32,
+}
+
+impl Default for WriteHistory {
+    fn default() -> Self {
+        Self {
+            stamps: vec![0; 0x10000],
+            frame: 1,
+        }
+    }
+}
+
+impl WriteHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, address: u16) {
+        self.stamps[address as usize] = self.frame;
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    // Frames since the last write
+    pub fn age(&self, address: u16) -> Option<u32> {
+        match self.stamps[address as usize] {
+            0 => None,
+            stamp => Some(self.frame - stamp),
+        }
+    }
+}
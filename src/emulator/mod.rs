@@ -1,9 +1,30 @@
 pub mod apu;
+pub mod audio_buffer;
+pub mod blip;
 pub mod bus;
+pub mod call_stack;
 pub mod cpu;
+pub mod debugger;
+pub mod disassembler;
+pub mod dma;
 pub mod gameboy;
+pub mod gbs;
+pub mod gdb_stub;
 pub mod gui;
+pub mod headless;
 pub mod joypad;
+pub mod link;
 pub mod memory;
+pub mod memory_viewer;
+pub mod net_link;
+pub mod palette;
+pub mod postprocess;
 pub mod ppu;
+pub mod printer;
+pub mod profiler;
+pub mod recorder;
+pub mod serial;
+pub mod symbols;
 pub mod timer;
+pub mod trace;
+pub mod vram_viewer;
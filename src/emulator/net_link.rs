@@ -0,0 +1,471 @@
+use crate::emulator::serial::SerialLink;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, mpsc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const PROTOCOL_VERSION: u8 = 2;
+
+// Every message is two bytes: kind, then payload
+const MSG_HELLO: u8 = 0x00; // payload: protocol version
+const MSG_TRANSFER: u8 = 0x01; // sender is the master, payload: its SB
+const MSG_REPLY: u8 = 0x02; // answer to a transfer, payload: our SB or 0xFF
+const MSG_FRAME: u8 = 0x03; // sender finished a frame
+const MSG_PAUSE: u8 = 0x04; // payload: 1 when the sender paused, 0 when it resumed
+
+// How many frames one side may run ahead of the other
+pub const MAX_FRAME_LEAD: u64 = 2;
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+// How often a background accept checks whether it was cancelled
+const ACCEPT_POLL: Duration = Duration::from_millis(20);
+
+// "unix:/path/to/socket" or a TCP "host:port"
+enum Socket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Socket {
+    fn connect(address: &str) -> std::io::Result<Self> {
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            return UnixStream::connect(path).map(Socket::Unix);
+        }
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        Ok(Socket::Tcp(stream))
+    }
+
+    fn try_clone(&self) -> std::io::Result<Self> {
+        match self {
+            Socket::Tcp(s) => s.try_clone().map(Socket::Tcp),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.try_clone().map(Socket::Unix),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        match self {
+            Socket::Tcp(s) => s.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.set_read_timeout(timeout),
+        }
+    }
+
+    fn shutdown(&self) {
+        let _ = match self {
+            Socket::Tcp(s) => s.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.shutdown(Shutdown::Both),
+        };
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Socket::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            Socket::Unix(s) => s.flush(),
+        }
+    }
+}
+
+pub enum NetListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, String),
+}
+
+impl NetListener {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        #[cfg(unix)]
+        if let Some(path) = address.strip_prefix("unix:") {
+            // A stale socket file from an earlier run would make bind fail
+            let _ = std::fs::remove_file(path);
+            return UnixListener::bind(path)
+                .map(|l| NetListener::Unix(l, path.to_string()))
+                .map_err(|e| format!("{}: {}", address, e));
+        }
+        TcpListener::bind(address)
+            .map(NetListener::Tcp)
+            .map_err(|e| format!("{}: {}", address, e))
+    }
+
+    pub fn local_address(&self) -> String {
+        match self {
+            NetListener::Tcp(l) => l.local_addr().map(|a| a.to_string()).unwrap_or_default(),
+            #[cfg(unix)]
+            NetListener::Unix(_, path) => format!("unix:{}", path),
+        }
+    }
+
+    // Blocks until the peer connects
+    pub fn accept(&self) -> Result<NetLink, String> {
+        let socket = self.accept_socket().map_err(|e| e.to_string())?;
+        NetLink::handshake(socket)
+    }
+
+    // Waits for the peer on a background thread, dropping the result
+    // stops listening
+    pub fn accept_in_background(self) -> Result<PendingPeer, String> {
+        match &self {
+            NetListener::Tcp(l) => l.set_nonblocking(true),
+            #[cfg(unix)]
+            NetListener::Unix(l, _) => l.set_nonblocking(true),
+        }
+        .map_err(|e| e.to_string())?;
+
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let thread = std::thread::spawn(move || {
+            let result = loop {
+                if thread_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                match self.accept_socket() {
+                    Ok(socket) => break NetLink::handshake(socket),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(ACCEPT_POLL)
+                    }
+                    Err(e) => break Err(e.to_string()),
+                }
+            };
+            let _ = sender.send(result);
+        });
+
+        Ok(PendingPeer {
+            receiver,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn accept_socket(&self) -> std::io::Result<Socket> {
+        // Accepted sockets may inherit non-blocking mode from the listener
+        match self {
+            NetListener::Tcp(l) => {
+                let (stream, _) = l.accept()?;
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+                Ok(Socket::Tcp(stream))
+            }
+            #[cfg(unix)]
+            NetListener::Unix(l, _) => {
+                let (stream, _) = l.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Socket::Unix(stream))
+            }
+        }
+    }
+}
+
+// Connection being accepted by `NetListener::accept_in_background`
+pub struct PendingPeer {
+    receiver: mpsc::Receiver<Result<NetLink, String>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PendingPeer {
+    // The link, or why accepting failed, once the peer has connected
+    pub fn poll(&self) -> Option<Result<NetLink, String>> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for PendingPeer {
+    // Releases the address before returning
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    connected: bool,
+    // SB while waiting on the external clock, as last seen by `receive`
+    waiting: Option<u8>,
+    // Byte a remote master clocked in, not yet picked up
+    delivered: Option<u8>,
+    // Answer to our own transfer
+    reply: Option<u8>,
+    frames_sent: u64,
+    peer_frames: u64,
+    peer_paused: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    // Waits until `ready` holds, the peer is gone or the timeout expires.
+    // A paused peer is waited on without timeout.
+    fn wait_for(&self, mut ready: impl FnMut(&State) -> bool) -> MutexGuard<'_, State> {
+        let mut state = self.lock();
+        let mut deadline = Instant::now() + PEER_TIMEOUT;
+        while state.connected && !ready(&state) {
+            if state.peer_paused {
+                state = self.changed.wait(state).unwrap();
+                deadline = Instant::now() + PEER_TIMEOUT;
+                continue;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self.changed.wait_timeout(state, deadline - now).unwrap().0;
+        }
+        state
+    }
+}
+
+type Writer = Arc<Mutex<Socket>>;
+
+fn send(writer: &Writer, kind: u8, payload: u8) -> bool {
+    writer.lock().unwrap().write_all(&[kind, payload]).is_ok()
+}
+
+// Serial link to another emulator process. A background thread answers the
+// peer's transfers at once, so a master never waits on the other side's
+// emulation, only on the round trip.
+pub struct NetLink {
+    shared: Arc<Shared>,
+    writer: Writer,
+}
+
+impl std::fmt::Debug for NetLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NetLink")
+            .field("state", &*self.shared.lock())
+            .finish()
+    }
+}
+
+impl NetLink {
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let socket = Socket::connect(address).map_err(|e| format!("{}: {}", address, e))?;
+        Self::handshake(socket)
+    }
+
+    fn handshake(mut socket: Socket) -> Result<Self, String> {
+        socket
+            .write_all(&[MSG_HELLO, PROTOCOL_VERSION])
+            .map_err(|e| e.to_string())?;
+        let mut hello = [0u8; 2];
+        socket
+            .set_read_timeout(Some(PEER_TIMEOUT))
+            .and_then(|_| socket.read_exact(&mut hello))
+            .and_then(|_| socket.set_read_timeout(None))
+            .map_err(|e| e.to_string())?;
+        if hello != [MSG_HELLO, PROTOCOL_VERSION] {
+            return Err(format!(
+                "peer speaks link protocol {:02X}:{:02X}, expected 00:{:02X}",
+                hello[0], hello[1], PROTOCOL_VERSION
+            ));
+        }
+
+        let shared = Arc::new(Shared::default());
+        shared.lock().connected = true;
+        let reader = socket.try_clone().map_err(|e| e.to_string())?;
+        let writer = Arc::new(Mutex::new(socket));
+
+        let thread_shared = Arc::clone(&shared);
+        let thread_writer = Arc::clone(&writer);
+        std::thread::spawn(move || serve(reader, thread_shared, thread_writer));
+
+        Ok(Self { shared, writer })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.shared.lock().connected
+    }
+
+    pub fn frame_sync(&self) -> FrameSync {
+        FrameSync {
+            shared: Arc::clone(&self.shared),
+            writer: Arc::clone(&self.writer),
+            paused: false,
+            stalled_since: None,
+        }
+    }
+}
+
+impl Drop for NetLink {
+    fn drop(&mut self) {
+        self.writer.lock().unwrap().shutdown();
+    }
+}
+
+fn serve(mut reader: Socket, shared: Arc<Shared>, writer: Writer) {
+    let mut message = [0u8; 2];
+    while reader.read_exact(&mut message).is_ok() {
+        let [kind, payload] = message;
+        let mut state = shared.lock();
+        match kind {
+            MSG_TRANSFER => {
+                let incoming = state.waiting.take();
+                if incoming.is_some() {
+                    state.delivered = Some(payload);
+                }
+                drop(state);
+                send(&writer, MSG_REPLY, incoming.unwrap_or(0xFF));
+                continue;
+            }
+            MSG_REPLY => state.reply = Some(payload),
+            MSG_FRAME => state.peer_frames += 1,
+            MSG_PAUSE => state.peer_paused = payload != 0,
+            _ => break,
+        }
+        drop(state);
+        shared.changed.notify_all();
+    }
+
+    shared.lock().connected = false;
+    shared.changed.notify_all();
+}
+
+impl SerialLink for NetLink {
+    fn transfer(&mut self, outgoing: u8) -> u8 {
+        if self.start_transfer(outgoing).is_some() {
+            return 0xFF;
+        }
+        self.shared
+            .wait_for(|state| state.reply.is_some())
+            .reply
+            .take()
+            .unwrap_or(0xFF)
+    }
+
+    // The reply comes after a round trip, the serial clock waits for it
+    fn start_transfer(&mut self, outgoing: u8) -> Option<u8> {
+        self.shared.lock().reply = None;
+        if !send(&self.writer, MSG_TRANSFER, outgoing) {
+            return Some(0xFF);
+        }
+        None
+    }
+
+    fn poll_transfer(&mut self) -> Option<u8> {
+        let mut state = self.shared.lock();
+        match state.reply.take() {
+            Some(reply) => Some(reply),
+            None if !state.connected => Some(0xFF),
+            None => None,
+        }
+    }
+
+    fn receive(&mut self, outgoing: u8) -> Option<u8> {
+        let mut state = self.shared.lock();
+        let incoming = state.delivered.take();
+        state.waiting = if incoming.is_some() {
+            None
+        } else {
+            Some(outgoing)
+        };
+        incoming
+    }
+
+    fn cancel(&mut self) {
+        self.shared.lock().waiting = None;
+    }
+}
+
+// Keeps the two processes within MAX_FRAME_LEAD frames of each other
+pub struct FrameSync {
+    shared: Arc<Shared>,
+    writer: Writer,
+    paused: bool,
+    // When `ready` started returning false for a running peer
+    stalled_since: Option<Instant>,
+}
+
+impl FrameSync {
+    // Whether a frame can run without getting too far ahead of the peer.
+    // Never blocks; fails once the peer is gone or has neither sent a frame
+    // nor paused for PEER_TIMEOUT.
+    pub fn ready(&mut self) -> Result<bool, String> {
+        let state = self.shared.lock();
+        if !state.connected {
+            return Err("link peer disconnected".to_string());
+        }
+        if state.frames_sent <= state.peer_frames + MAX_FRAME_LEAD || state.peer_paused {
+            self.stalled_since = None;
+            return Ok(state.frames_sent <= state.peer_frames + MAX_FRAME_LEAD);
+        }
+        let stalled_since = *self.stalled_since.get_or_insert_with(Instant::now);
+        if stalled_since.elapsed() >= PEER_TIMEOUT {
+            return Err("link peer stopped responding".to_string());
+        }
+        Ok(false)
+    }
+
+    // Call after each emulated frame, with `ready` checked before it
+    pub fn send_frame(&mut self) -> Result<(), String> {
+        self.shared.lock().frames_sent += 1;
+        if !send(&self.writer, MSG_FRAME, 0) {
+            return Err("link peer disconnected".to_string());
+        }
+        Ok(())
+    }
+
+    // Lets the peer wait on us without timing out while we are paused
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused != self.paused {
+            self.paused = paused;
+            self.stalled_since = None;
+            send(&self.writer, MSG_PAUSE, paused as u8);
+        }
+    }
+
+    // Blocking `send_frame`, waits while too far ahead of the peer
+    pub fn end_frame(&mut self) -> Result<(), String> {
+        self.send_frame()?;
+
+        let state = self
+            .shared
+            .wait_for(|state| state.frames_sent <= state.peer_frames + MAX_FRAME_LEAD);
+        if state.frames_sent > state.peer_frames + MAX_FRAME_LEAD {
+            return Err(if state.connected {
+                "link peer stopped responding".to_string()
+            } else {
+                "link peer disconnected".to_string()
+            });
+        }
+        Ok(())
+    }
+}
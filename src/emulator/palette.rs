@@ -0,0 +1,172 @@
+pub const PALETTE_CONFIG_PATH: &str = "resources/palettes.cfg";
+
+const INVALID_SHADE_COLOR: [u8; 4] = [255, 0, 255, 255]; // Magenta debug
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    pub colors: [[u8; 3]; 4], // RGB for shades 0 (lightest) to 3 (darkest)
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::grey()
+    }
+}
+
+impl Palette {
+    pub fn new(name: &str, colors: [[u8; 3]; 4]) -> Self {
+        Self {
+            name: name.to_string(),
+            colors,
+        }
+    }
+
+    pub fn grey() -> Self {
+        Self::new(
+            "Grey",
+            [[255, 255, 255], [170, 170, 170], [85, 85, 85], [0, 0, 0]],
+        )
+    }
+
+    pub fn classic_green() -> Self {
+        Self::new(
+            "DMG Green",
+            [[155, 188, 15], [139, 172, 15], [48, 98, 48], [15, 56, 15]],
+        )
+    }
+
+    pub fn pocket() -> Self {
+        Self::new(
+            "Pocket",
+            [[196, 207, 161], [139, 149, 109], [77, 83, 60], [31, 31, 31]],
+        )
+    }
+
+    pub fn light() -> Self {
+        Self::new(
+            "Light",
+            [[0, 181, 129], [0, 154, 113], [0, 105, 74], [0, 79, 59]],
+        )
+    }
+
+    pub fn high_contrast() -> Self {
+        Self::new(
+            "High Contrast",
+            [[255, 255, 255], [200, 200, 200], [60, 60, 60], [0, 0, 0]],
+        )
+    }
+
+    pub fn presets() -> Vec<Palette> {
+        vec![
+            Self::grey(),
+            Self::classic_green(),
+            Self::pocket(),
+            Self::light(),
+            Self::high_contrast(),
+        ]
+    }
+
+    pub fn rgba(&self, shade: u8) -> [u8; 4] {
+        match self.colors.get(shade as usize) {
+            Some([r, g, b]) => [*r, *g, *b, 255],
+            None => INVALID_SHADE_COLOR,
+        }
+    }
+
+    // One palette per line: `name = #RRGGBB #RRGGBB #RRGGBB #RRGGBB`,
+    // lightest shade first. Blank lines and `#` comments are ignored.
+    pub fn parse_config(text: &str) -> Result<Vec<Palette>, String> {
+        let mut palettes = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let line_number = index + 1;
+            let (name, colors) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected `name = colors`", line_number))?;
+
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(format!("line {}: palette name is empty", line_number));
+            }
+
+            let colors: Vec<[u8; 3]> = colors
+                .split_whitespace()
+                .map(parse_hex_color)
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("line {}: {}", line_number, e))?;
+
+            let colors: [[u8; 3]; 4] = colors.try_into().map_err(|_| {
+                format!(
+                    "line {}: expected 4 colors for palette '{}'",
+                    line_number, name
+                )
+            })?;
+
+            palettes.push(Palette::new(name, colors));
+        }
+
+        Ok(palettes)
+    }
+
+    pub fn load_config(path: &str) -> Result<Vec<Palette>, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse_config(&text)
+    }
+}
+
+fn parse_hex_color(text: &str) -> Result<[u8; 3], String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(format!("invalid color '{}'", text));
+    }
+
+    let channel = |range: std::ops::Range<usize>| {
+        u8::from_str_radix(&hex[range], 16).map_err(|_| format!("invalid color '{}'", text))
+    };
+
+    Ok([channel(0..2)?, channel(2..4)?, channel(4..6)?])
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LayerPalettes {
+    pub bg: Palette,
+    pub obj0: Palette,
+    pub obj1: Palette,
+}
+
+impl LayerPalettes {
+    pub fn uniform(palette: &Palette) -> Self {
+        Self {
+            bg: palette.clone(),
+            obj0: palette.clone(),
+            obj1: palette.clone(),
+        }
+    }
+
+    pub fn for_layer(&self, layer: Layer) -> &Palette {
+        match layer {
+            Layer::Background => &self.bg,
+            Layer::Object0 => &self.obj0,
+            Layer::Object1 => &self.obj1,
+        }
+    }
+
+    pub fn rgba(&self, shade: u8, layer: Layer) -> [u8; 4] {
+        self.for_layer(layer).rgba(shade)
+    }
+}
+
+// Which layer produced a framebuffer pixel, so each can be colored separately
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Layer {
+    #[default]
+    Background,
+    Object0,
+    Object1,
+}
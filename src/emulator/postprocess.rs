@@ -0,0 +1,298 @@
+use crate::emulator::{palette::LayerPalettes, ppu::PPU};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // RGBA, row-major
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn from_ppu(ppu: &PPU, palettes: &LayerPalettes) -> Self {
+        let mut pixels = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+
+        for (row, layers) in ppu.framebuffer.iter().zip(ppu.layers.iter()) {
+            for (&pixel, &layer) in row.iter().zip(layers.iter()) {
+                pixels.extend_from_slice(&palettes.rgba(pixel, layer));
+            }
+        }
+
+        Self {
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: [u8; 4]) {
+        let i = (y * self.width + x) * 4;
+        self.pixels[i..i + 4].copy_from_slice(&color);
+    }
+
+    // Neighbour lookup clamped to the frame edges
+    fn pixel_clamped(&self, x: isize, y: isize) -> [u8; 4] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.pixel(x, y)
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        let file = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut encoder = png::Encoder::new(
+            std::io::BufWriter::new(file),
+            self.width as u32,
+            self.height as u32,
+        );
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer
+            .write_image_data(&self.pixels)
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Filter {
+    #[default]
+    None,
+    DotMatrix,
+    Scanlines,
+    Scale2x,
+    Scale3x,
+}
+
+impl Filter {
+    pub const ALL: [Filter; 5] = [
+        Filter::None,
+        Filter::DotMatrix,
+        Filter::Scanlines,
+        Filter::Scale2x,
+        Filter::Scale3x,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::None => "None",
+            Filter::DotMatrix => "Dot matrix",
+            Filter::Scanlines => "Scanlines",
+            Filter::Scale2x => "Scale2x",
+            Filter::Scale3x => "Scale3x",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Filter> {
+        let name = name.to_ascii_lowercase().replace(['-', '_', ' '], "");
+        Self::ALL
+            .into_iter()
+            .find(|f| f.name().to_ascii_lowercase().replace(' ', "") == name)
+    }
+
+    pub fn scale(&self) -> usize {
+        match self {
+            Filter::None => 1,
+            Filter::Scale2x => 2,
+            Filter::DotMatrix | Filter::Scanlines | Filter::Scale3x => 3,
+        }
+    }
+
+    pub fn apply(&self, frame: &Frame) -> Frame {
+        match self {
+            Filter::None => frame.clone(),
+            Filter::DotMatrix => grid(frame, 3, true),
+            Filter::Scanlines => grid(frame, 3, false),
+            Filter::Scale2x => scale2x(frame),
+            Filter::Scale3x => scale3x(frame),
+        }
+    }
+}
+
+// Slow LCD response: each output frame keeps part of the previous one, which
+// is what makes flickering sprites look transparent on a real DMG.
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessor {
+    pub ghosting: f32, // 0.0 = off, weight of the previous frame
+    pub filter: Filter,
+    previous: Option<Frame>,
+}
+
+impl PostProcessor {
+    pub fn new(ghosting: f32, filter: Filter) -> Self {
+        Self {
+            ghosting,
+            filter,
+            previous: None,
+        }
+    }
+
+    pub fn process(&mut self, frame: &Frame) -> Frame {
+        let blended = self.blend(frame);
+        let output = self.filter.apply(&blended);
+        self.previous = Some(blended);
+        output
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    fn blend(&self, frame: &Frame) -> Frame {
+        let weight = self.ghosting.clamp(0.0, 1.0);
+        let previous = match &self.previous {
+            Some(previous) if weight > 0.0 && previous.pixels.len() == frame.pixels.len() => {
+                previous
+            }
+            _ => return frame.clone(),
+        };
+
+        let pixels = frame
+            .pixels
+            .iter()
+            .zip(previous.pixels.iter())
+            .map(|(&current, &old)| {
+                (current as f32 * (1.0 - weight) + old as f32 * weight).round() as u8
+            })
+            .collect();
+
+        Frame {
+            width: frame.width,
+            height: frame.height,
+            pixels,
+        }
+    }
+}
+
+fn darken(color: [u8; 4], factor: f32) -> [u8; 4] {
+    [
+        (color[0] as f32 * factor) as u8,
+        (color[1] as f32 * factor) as u8,
+        (color[2] as f32 * factor) as u8,
+        color[3],
+    ]
+}
+
+// Integer upscale with the gap between LCD dots darkened: the last row of each
+// cell for scanlines, the last row and column for the dot-matrix grid.
+fn grid(frame: &Frame, scale: usize, columns: bool) -> Frame {
+    let mut output = Frame::new(frame.width * scale, frame.height * scale);
+
+    for y in 0..output.height {
+        for x in 0..output.width {
+            let color = frame.pixel(x / scale, y / scale);
+            let gap = y % scale == scale - 1 || (columns && x % scale == scale - 1);
+            let color = if gap { darken(color, 0.7) } else { color };
+            output.set_pixel(x, y, color);
+        }
+    }
+
+    output
+}
+
+// EPX / Scale2x: each pixel becomes 2x2, corners take a neighbour's color when
+// two adjacent neighbours agree, smoothing diagonal edges.
+fn scale2x(frame: &Frame) -> Frame {
+    let mut output = Frame::new(frame.width * 2, frame.height * 2);
+
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let (xi, yi) = (x as isize, y as isize);
+            let p = frame.pixel(x, y);
+            let a = frame.pixel_clamped(xi, yi - 1);
+            let b = frame.pixel_clamped(xi + 1, yi);
+            let c = frame.pixel_clamped(xi - 1, yi);
+            let d = frame.pixel_clamped(xi, yi + 1);
+
+            let e0 = if c == a && c != d && a != b { a } else { p };
+            let e1 = if a == b && a != c && b != d { b } else { p };
+            let e2 = if d == c && d != b && c != a { c } else { p };
+            let e3 = if b == d && b != a && d != c { d } else { p };
+
+            output.set_pixel(x * 2, y * 2, e0);
+            output.set_pixel(x * 2 + 1, y * 2, e1);
+            output.set_pixel(x * 2, y * 2 + 1, e2);
+            output.set_pixel(x * 2 + 1, y * 2 + 1, e3);
+        }
+    }
+
+    output
+}
+
+// AdvMAME3x / Scale3x, same idea as Scale2x on a 3x3 block
+fn scale3x(frame: &Frame) -> Frame {
+    let mut output = Frame::new(frame.width * 3, frame.height * 3);
+
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let (xi, yi) = (x as isize, y as isize);
+            let a = frame.pixel_clamped(xi - 1, yi - 1);
+            let b = frame.pixel_clamped(xi, yi - 1);
+            let c = frame.pixel_clamped(xi + 1, yi - 1);
+            let d = frame.pixel_clamped(xi - 1, yi);
+            let e = frame.pixel(x, y);
+            let f = frame.pixel_clamped(xi + 1, yi);
+            let g = frame.pixel_clamped(xi - 1, yi + 1);
+            let h = frame.pixel_clamped(xi, yi + 1);
+            let i = frame.pixel_clamped(xi + 1, yi + 1);
+
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+
+            for (n, color) in block.iter().enumerate() {
+                output.set_pixel(x * 3 + n % 3, y * 3 + n / 3, *color);
+            }
+        }
+    }
+
+    output
+}
@@ -1,4 +1,8 @@
-use crate::{debug, emulator::memory::Memory, error};
+use crate::{
+    debug,
+    emulator::{memory::Memory, palette::Layer},
+    error, trace,
+};
 
 #[derive(Debug, Clone)]
 pub struct PPU {
@@ -22,6 +26,7 @@ pub struct PPU {
 
     // Framebuffer
     pub framebuffer: [[u8; 160]; 144], // 160x144px
+    pub layers: [[Layer; 160]; 144],   // layer that drew each pixel
 }
 
 impl Default for PPU {
@@ -55,6 +60,7 @@ impl PPU {
             cycles: 0,
             mode: PPUMode::OAMScan,
             framebuffer: [[0; 160]; 144],
+            layers: [[Layer::Background; 160]; 144],
         }
     }
 
@@ -67,11 +73,71 @@ impl PPU {
     }
 
     pub fn can_access_vram(&self) -> bool {
-        (self.lcdc & 0x80) == 0 || self.mode != PPUMode::Drawing
+        self.can_access_vram_after(0)
     }
 
     pub fn can_access_oam(&self) -> bool {
-        (self.lcdc & 0x80) == 0 || !matches!(self.mode, PPUMode::OAMScan | PPUMode::Drawing)
+        self.can_access_oam_after(0)
+    }
+
+    // The PPU is only stepped once per instruction, so the bus asks where it
+    // will be `dots` T-cycles into the instruction being executed.
+    pub fn can_access_vram_after(&self, dots: u32) -> bool {
+        (self.lcdc & 0x80) == 0 || self.mode_after(dots) != PPUMode::Drawing
+    }
+
+    pub fn can_access_oam_after(&self, dots: u32) -> bool {
+        (self.lcdc & 0x80) == 0
+            || !matches!(self.mode_after(dots), PPUMode::OAMScan | PPUMode::Drawing)
+    }
+
+    // OAM row (8 bytes) read by the PPU during mode 2, one row per M-cycle
+    pub fn oam_scan_row_after(&self, dots: u32) -> Option<u16> {
+        if (self.lcdc & 0x80) == 0 {
+            return None;
+        }
+
+        let (mode, cycles) = self.position_after(dots);
+        if mode == PPUMode::OAMScan {
+            Some((cycles / 4) as u16)
+        } else {
+            None
+        }
+    }
+
+    pub fn mode_after(&self, dots: u32) -> PPUMode {
+        self.position_after(dots).0
+    }
+
+    fn position_after(&self, dots: u32) -> (PPUMode, u32) {
+        let mut mode = self.mode;
+        let mut cycles = self.cycles + dots;
+        let mut ly = self.ly;
+
+        if (self.lcdc & 0x80) == 0 {
+            return (mode, self.cycles);
+        }
+
+        loop {
+            let (duration, next) = match mode {
+                PPUMode::OAMScan => (80, PPUMode::Drawing),
+                PPUMode::Drawing => (172, PPUMode::HBLank),
+                PPUMode::HBLank if ly + 1 >= 144 => (204, PPUMode::VBlank),
+                PPUMode::HBLank => (204, PPUMode::OAMScan),
+                PPUMode::VBlank if ly + 1 >= 154 => (456, PPUMode::OAMScan),
+                PPUMode::VBlank => (456, PPUMode::VBlank),
+            };
+
+            if cycles < duration {
+                return (mode, cycles);
+            }
+
+            cycles -= duration;
+            if matches!(mode, PPUMode::HBLank | PPUMode::VBlank) {
+                ly = (ly + 1) % 154;
+            }
+            mode = next;
+        }
     }
 
     pub fn read_register(&self, address: u16) -> u8 {
@@ -88,7 +154,7 @@ impl PPU {
             0xFF48 => self.obp0,
             0xFF49 => self.obp1,
             _ => {
-                error!("Register not implemented: 0x{:04X}", address);
+                error!(Ppu, "Register not implemented: 0x{:04X}", address);
                 0xFF
             }
         }
@@ -125,8 +191,8 @@ impl PPU {
             }
             _ => {
                 error!(
-                    "Register not implemented: 0x{:04X} = 0x{:02X}",
-                    address, value
+                    Ppu,
+                    "Register not implemented: 0x{:04X} = 0x{:02X}", address, value
                 );
             }
         }
@@ -144,20 +210,20 @@ impl PPU {
             PPUMode::OAMScan => {
                 if self.cycles >= 80 {
                     debug!(
-                        "OAMScan complete! {} -> Drawing (LY={})",
-                        self.cycles, self.ly
+                        Ppu,
+                        "OAMScan complete! {} -> Drawing (LY={})", self.cycles, self.ly
                     );
                     self.cycles -= 80;
                     self.mode = PPUMode::Drawing;
                 } else {
-                    debug!("OAMScan: {} / 80 cycles", self.cycles);
+                    trace!(Ppu, "OAMScan: {} / 80 cycles", self.cycles);
                 }
             }
             PPUMode::Drawing => {
                 if self.cycles >= 172 {
                     debug!(
-                        "Drawing complete! {} -> HBlank (LY={})",
-                        self.cycles, self.ly
+                        Ppu,
+                        "Drawing complete! {} -> HBlank (LY={})", self.cycles, self.ly
                     );
                     self.cycles -= 172;
 
@@ -165,12 +231,12 @@ impl PPU {
 
                     self.mode = PPUMode::HBLank;
                 } else {
-                    debug!("Drawing: {} / 172 cycles", self.cycles);
+                    trace!(Ppu, "Drawing: {} / 172 cycles", self.cycles);
                 }
             }
             PPUMode::HBLank => {
                 if self.cycles >= 204 {
-                    debug!("HBlank complete! LY {} -> {}", self.ly, self.ly + 1);
+                    debug!(Ppu, "HBlank complete! LY {} -> {}", self.ly, self.ly + 1);
                     self.cycles -= 204;
                     self.ly += 1;
 
@@ -181,7 +247,8 @@ impl PPU {
                         self.mode = PPUMode::OAMScan;
                     }
                 } else {
-                    debug!(
+                    trace!(
+                        Ppu,
                         "HBlank: {} / 204 cycles (need {} more)",
                         self.cycles,
                         204 - self.cycles
@@ -189,14 +256,22 @@ impl PPU {
                 }
             }
             PPUMode::VBlank => {
-                debug!("VBlank processing: LY={}, cycles={}", self.ly, self.cycles);
+                trace!(
+                    Ppu,
+                    "VBlank processing: LY={}, cycles={}", self.ly, self.cycles
+                );
                 if self.cycles >= 456 {
-                    debug!("VBlank line complete! LY {} → {}", self.ly, self.ly + 1);
+                    debug!(
+                        Ppu,
+                        "VBlank line complete! LY {} → {}",
+                        self.ly,
+                        self.ly + 1
+                    );
                     self.cycles -= 456;
                     self.ly += 1;
 
                     if self.ly >= 154 {
-                        debug!("VBlank finished! Resetting to LY=0, OAMScan");
+                        debug!(Ppu, "VBlank finished! Resetting to LY=0, OAMScan");
                         self.ly = 0;
                         self.mode = PPUMode::OAMScan;
                     }
@@ -226,6 +301,7 @@ impl PPU {
         // Effacer la ligne
         for x in 0..160 {
             self.framebuffer[line][x] = 0;
+            self.layers[line][x] = Layer::Background;
         }
 
         // 1. RENDU DU BACKGROUND (existant)
@@ -337,6 +413,11 @@ impl PPU {
                         let behind_bg = (attributes & 0x80) != 0;
                         if !behind_bg || self.framebuffer[line][screen_x as usize] == 0 {
                             self.framebuffer[line][screen_x as usize] = final_color;
+                            self.layers[line][screen_x as usize] = if (attributes & 0x10) != 0 {
+                                Layer::Object1
+                            } else {
+                                Layer::Object0
+                            };
                         }
                     }
                 }
@@ -0,0 +1,324 @@
+use crate::emulator::{postprocess::Frame, serial::SerialLink};
+use std::sync::{Arc, Mutex};
+
+pub const PRINT_WIDTH: usize = 160;
+const TILES_PER_ROW: usize = PRINT_WIDTH / 8;
+const BAND_BYTES: usize = TILES_PER_ROW * 2 * 16; // one DATA packet: 160x16 pixels
+const BUFFER_SIZE: usize = 0x2000; // printer RAM
+const MARGIN_HEIGHT: usize = 16; // pixels per line feed of margin
+
+// Paper shades for colors 0-3
+const SHADES: [[u8; 4]; 4] = [
+    [255, 255, 255, 255],
+    [170, 170, 170, 255],
+    [85, 85, 85, 255],
+    [0, 0, 0, 255],
+];
+
+const COMMAND_INIT: u8 = 0x01;
+const COMMAND_PRINT: u8 = 0x02;
+const COMMAND_DATA: u8 = 0x04;
+const COMMAND_BREAK: u8 = 0x08;
+const COMMAND_STATUS: u8 = 0x0F;
+
+const DEVICE_ID: u8 = 0x81;
+
+// Status byte bits
+const STATUS_CHECKSUM_ERROR: u8 = 0x01;
+const STATUS_BUSY: u8 = 0x02;
+const STATUS_FULL: u8 = 0x04;
+const STATUS_UNPROCESSED: u8 = 0x08;
+
+// The game polls STATUS while printing, report busy for this many polls
+const BUSY_POLLS: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    DeviceId,
+    Status,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintJob {
+    pub image: Frame,
+    // Saved PNG path or why saving failed, None until `save_new_jobs` ran
+    pub saved: Option<Result<String, String>>,
+}
+
+pub type PrintQueue = Arc<Mutex<Vec<PrintJob>>>;
+
+// Saves jobs not saved yet into `dir` as print-NNN.png, never overwriting
+// prints from an earlier session. Frontends call this outside emulation
+// and report the results, which are also kept in the jobs.
+pub fn save_new_jobs(jobs: &PrintQueue, dir: &str) -> Vec<Result<String, String>> {
+    let mut jobs = jobs.lock().unwrap();
+    let mut results = Vec::new();
+    for index in 0..jobs.len() {
+        if jobs[index].saved.is_some() {
+            continue;
+        }
+        let path = (index + 1..)
+            .map(|n| format!("{}/print-{:03}.png", dir, n))
+            .find(|path| !std::path::Path::new(path).exists())
+            .unwrap();
+        let result = jobs[index].image.save_png(&path).map(|()| path);
+        jobs[index].saved = Some(result.clone());
+        results.push(result);
+    }
+    results
+}
+
+// Game Boy Printer on the serial port. Packets are
+// 88 33 | command | compression | length (LE) | data | checksum (LE) | 00 00,
+// the printer answering 0x81 then its status to the last two bytes.
+// Consecutive PRINTs with no feed after them form one job, i.e. one image.
+#[derive(Debug)]
+pub struct Printer {
+    jobs: PrintQueue,
+
+    stage: Stage,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    packet: Vec<u8>,
+    checksum: u16,
+
+    status: u8,
+    busy_polls: u8,
+    buffer: Vec<u8>,
+    // Shade rows of the job being printed
+    rows: Vec<[u8; PRINT_WIDTH]>,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self {
+            jobs: PrintQueue::default(),
+            stage: Stage::Magic1,
+            command: 0,
+            compressed: false,
+            length: 0,
+            packet: Vec::new(),
+            checksum: 0,
+            status: 0,
+            busy_polls: 0,
+            buffer: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    // Shared with the printer, stays readable once it is plugged in
+    pub fn jobs(&self) -> PrintQueue {
+        Arc::clone(&self.jobs)
+    }
+
+    fn receive_byte(&mut self, byte: u8) {
+        self.stage = match self.stage {
+            Stage::Magic1 if byte == 0x88 => Stage::Magic2,
+            Stage::Magic1 => Stage::Magic1,
+            Stage::Magic2 if byte == 0x33 => Stage::Command,
+            Stage::Magic2 => Stage::Magic1,
+            Stage::Command => {
+                self.command = byte;
+                self.checksum = byte as u16;
+                Stage::Compression
+            }
+            Stage::Compression => {
+                self.compressed = byte & 0x01 != 0;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                Stage::LengthLow
+            }
+            Stage::LengthLow => {
+                self.length = byte as u16;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                Stage::LengthHigh
+            }
+            Stage::LengthHigh => {
+                self.length |= (byte as u16) << 8;
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                self.packet.clear();
+                if self.length == 0 {
+                    Stage::ChecksumLow
+                } else {
+                    Stage::Data
+                }
+            }
+            Stage::Data => {
+                self.packet.push(byte);
+                self.checksum = self.checksum.wrapping_add(byte as u16);
+                if self.packet.len() == self.length as usize {
+                    Stage::ChecksumLow
+                } else {
+                    Stage::Data
+                }
+            }
+            Stage::ChecksumLow => {
+                self.checksum ^= byte as u16;
+                Stage::ChecksumHigh
+            }
+            Stage::ChecksumHigh => {
+                self.checksum ^= (byte as u16) << 8;
+                if self.checksum == 0 {
+                    self.status &= !STATUS_CHECKSUM_ERROR;
+                    self.run_command();
+                } else {
+                    self.status |= STATUS_CHECKSUM_ERROR;
+                }
+                Stage::DeviceId
+            }
+            Stage::DeviceId => Stage::Status,
+            Stage::Status => Stage::Magic1,
+        };
+    }
+
+    fn run_command(&mut self) {
+        match self.command {
+            COMMAND_INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            }
+            COMMAND_DATA => {
+                let data = if self.compressed {
+                    decompress(&self.packet)
+                } else {
+                    self.packet.clone()
+                };
+                let room = BUFFER_SIZE - self.buffer.len();
+                self.buffer.extend_from_slice(&data[..data.len().min(room)]);
+                if !self.buffer.is_empty() {
+                    self.status |= STATUS_UNPROCESSED;
+                }
+                if self.buffer.len() >= BUFFER_SIZE {
+                    self.status |= STATUS_FULL;
+                }
+            }
+            COMMAND_PRINT if self.packet.len() >= 4 => {
+                let margins = self.packet[1];
+                let palette = self.packet[2];
+                self.print(margins >> 4, margins & 0x0F, palette);
+                self.status = (self.status & !(STATUS_UNPROCESSED | STATUS_FULL)) | STATUS_BUSY;
+                self.busy_polls = BUSY_POLLS;
+            }
+            COMMAND_BREAK => {
+                self.buffer.clear();
+                self.rows.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            }
+            COMMAND_STATUS if self.busy_polls > 0 => {
+                self.busy_polls -= 1;
+                if self.busy_polls == 0 {
+                    self.status &= !STATUS_BUSY;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, margin_before: u8, margin_after: u8, palette: u8) {
+        // Palette 0 is sent by some games meaning the default mapping
+        let palette = if palette == 0 { 0xE4 } else { palette };
+
+        self.feed(margin_before);
+        for band in self.buffer.chunks(BAND_BYTES) {
+            let tile_rows = band.len() / (TILES_PER_ROW * 16);
+            for tile_row in 0..tile_rows {
+                for y in 0..8 {
+                    let mut row = [0u8; PRINT_WIDTH];
+                    for (x, shade) in row.iter_mut().enumerate() {
+                        let tile = (tile_row * TILES_PER_ROW + x / 8) * 16;
+                        let low = band[tile + y * 2];
+                        let high = band[tile + y * 2 + 1];
+                        let bit = 7 - (x % 8);
+                        let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                        *shade = (palette >> (color * 2)) & 0x03;
+                    }
+                    self.rows.push(row);
+                }
+            }
+        }
+        self.buffer.clear();
+
+        // No feed after: the next print continues the same sheet
+        if margin_after > 0 {
+            self.feed(margin_after);
+            self.finish_job();
+        }
+    }
+
+    // Blank paper
+    fn feed(&mut self, lines: u8) {
+        let height = lines as usize * MARGIN_HEIGHT;
+        self.rows
+            .extend(std::iter::repeat_n([0u8; PRINT_WIDTH], height));
+    }
+
+    fn finish_job(&mut self) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        let mut image = Frame::new(PRINT_WIDTH, self.rows.len());
+        for (y, row) in self.rows.iter().enumerate() {
+            for (x, &shade) in row.iter().enumerate() {
+                image.set_pixel(x, y, SHADES[shade as usize]);
+            }
+        }
+        self.rows.clear();
+
+        self.jobs
+            .lock()
+            .unwrap()
+            .push(PrintJob { image, saved: None });
+    }
+}
+
+impl SerialLink for Printer {
+    fn transfer(&mut self, outgoing: u8) -> u8 {
+        // The answer is shifted out while the byte comes in, so it only
+        // depends on where we are in the packet
+        let response = match self.stage {
+            Stage::DeviceId => DEVICE_ID,
+            Stage::Status => self.status,
+            _ => 0x00,
+        };
+        self.receive_byte(outgoing);
+        response
+    }
+}
+
+// Printer RLE: a control byte with bit 7 set repeats the next byte
+// (control & 0x7F) + 2 times, otherwise (control + 1) literal bytes follow
+pub fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(BAND_BYTES);
+    let mut bytes = data.iter();
+
+    while let Some(&control) = bytes.next() {
+        if control & 0x80 != 0 {
+            let Some(&value) = bytes.next() else {
+                break;
+            };
+            let count = (control & 0x7F) as usize + 2;
+            output.extend(std::iter::repeat_n(value, count));
+        } else {
+            output.extend(bytes.by_ref().take(control as usize + 1));
+        }
+    }
+    output
+}
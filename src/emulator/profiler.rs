@@ -0,0 +1,241 @@
+use crate::emulator::{
+    call_stack::{CallStack, Function},
+    symbols::SymbolTable,
+};
+use std::collections::HashMap;
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+struct Node {
+    function: Option<Function>, // None for code outside any call
+    parent: usize,
+    children: HashMap<Function, usize>,
+    calls: u64,
+    self_cycles: u64,
+}
+
+impl Node {
+    fn new(function: Option<Function>, parent: usize) -> Self {
+        Self {
+            function,
+            parent,
+            children: HashMap::new(),
+            calls: 0,
+            self_cycles: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileEntry {
+    pub function: Option<Function>,
+    pub calls: u64,
+    pub self_cycles: u64,
+    pub total_cycles: u64, // including callees
+}
+
+// Cycles per call path, fed by Gameboy::step from the CPU's shadow call
+// stack: each instruction's cycles go to the path it executed in
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    nodes: Vec<Node>,
+    // The top frame's serial number identifies the whole stack
+    current_entry: Option<u64>,
+    current_node: usize,
+    last_entry: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node::new(None, ROOT)],
+            current_entry: None,
+            current_node: ROOT,
+            last_entry: 0,
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts counting calls made from now on, whatever the stack holds
+    pub fn start(stack: &CallStack) -> Self {
+        Self {
+            last_entry: stack.entries(),
+            ..Self::default()
+        }
+    }
+
+    // Call-tree node of the stack's current path
+    pub fn node(&mut self, stack: &CallStack) -> usize {
+        let entry = stack.top().map(|frame| frame.entry);
+        if entry == self.current_entry {
+            return self.current_node;
+        }
+
+        let mut node = ROOT;
+        for frame in stack.frames() {
+            node = match self.nodes[node].children.get(&frame.function) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new(Some(frame.function), node));
+                    self.nodes[node].children.insert(frame.function, child);
+                    child
+                }
+            };
+        }
+
+        if let Some(entry) = entry
+            && entry > self.last_entry
+        {
+            self.nodes[node].calls += 1;
+            self.last_entry = entry;
+        }
+        self.current_entry = entry;
+        self.current_node = node;
+        node
+    }
+
+    pub fn add_cycles(&mut self, node: usize, cycles: u64) {
+        self.nodes[node].self_cycles += cycles;
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.nodes.iter().map(|n| n.self_cycles).sum()
+    }
+
+    // Children are created after their parents, so one backwards pass
+    // accumulates subtree totals
+    fn subtree_totals(&self) -> Vec<u64> {
+        let mut totals: Vec<u64> = self.nodes.iter().map(|n| n.self_cycles).collect();
+        for index in (1..self.nodes.len()).rev() {
+            totals[self.nodes[index].parent] += totals[index];
+        }
+        totals
+    }
+
+    fn has_ancestor(&self, node: usize, function: Function) -> bool {
+        let mut index = self.nodes[node].parent;
+        while index != ROOT {
+            if self.nodes[index].function == Some(function) {
+                return true;
+            }
+            index = self.nodes[index].parent;
+        }
+        false
+    }
+
+    // One entry per function, most self cycles first. Recursive calls are
+    // counted once in the total.
+    pub fn flat(&self) -> Vec<ProfileEntry> {
+        let totals = self.subtree_totals();
+        let mut entries: HashMap<Option<Function>, ProfileEntry> = HashMap::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let entry = entries.entry(node.function).or_insert(ProfileEntry {
+                function: node.function,
+                calls: 0,
+                self_cycles: 0,
+                total_cycles: 0,
+            });
+            entry.calls += node.calls;
+            entry.self_cycles += node.self_cycles;
+            let recursive = node
+                .function
+                .is_some_and(|function| self.has_ancestor(index, function));
+            if !recursive {
+                entry.total_cycles += totals[index];
+            }
+        }
+
+        let mut entries: Vec<ProfileEntry> = entries.into_values().collect();
+        entries.sort_by(|a, b| {
+            b.self_cycles
+                .cmp(&a.self_cycles)
+                .then(a.function.cmp(&b.function))
+        });
+        entries
+    }
+
+    pub fn root(&self) -> usize {
+        ROOT
+    }
+
+    pub fn entry(&self, node: usize) -> ProfileEntry {
+        let n = &self.nodes[node];
+        ProfileEntry {
+            function: n.function,
+            calls: n.calls,
+            self_cycles: n.self_cycles,
+            total_cycles: self.subtree_totals()[node],
+        }
+    }
+
+    // Callees of a call-tree node, heaviest first
+    pub fn children(&self, node: usize) -> Vec<(usize, ProfileEntry)> {
+        let totals = self.subtree_totals();
+        let mut children: Vec<(usize, ProfileEntry)> = self.nodes[node]
+            .children
+            .values()
+            .map(|&child| {
+                let n = &self.nodes[child];
+                let entry = ProfileEntry {
+                    function: n.function,
+                    calls: n.calls,
+                    self_cycles: n.self_cycles,
+                    total_cycles: totals[child],
+                };
+                (child, entry)
+            })
+            .collect();
+        children.sort_by(|a, b| {
+            b.1.total_cycles
+                .cmp(&a.1.total_cycles)
+                .then(a.1.function.cmp(&b.1.function))
+        });
+        children
+    }
+
+    // Flamegraph input: "(top level);Main;DrawSprites 1234" per call path
+    // with self cycles, for flamegraph.pl, inferno or speedscope
+    pub fn folded_stacks(&self, symbols: &SymbolTable) -> String {
+        let mut lines = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.self_cycles == 0 {
+                continue;
+            }
+            let mut names = Vec::new();
+            let mut current = index;
+            loop {
+                names.push(function_name(self.nodes[current].function, symbols));
+                if current == ROOT {
+                    break;
+                }
+                current = self.nodes[current].parent;
+            }
+            names.reverse();
+            lines.push(format!("{} {}", names.join(";"), node.self_cycles));
+        }
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+// Label, or "$0150" / "03:$4000" for banked code
+pub fn function_name(function: Option<Function>, symbols: &SymbolTable) -> String {
+    let Some(function) = function else {
+        return "(top level)".into();
+    };
+    match symbols.label_at(function.address, function.bank) {
+        Some(label) => label.to_string(),
+        None if (0x4000..0x8000).contains(&function.address) => {
+            format!("{:02X}:${:04X}", function.bank, function.address)
+        }
+        None => format!("${:04X}", function.address),
+    }
+}
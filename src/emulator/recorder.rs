@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+const CHANNELS: u16 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WavFormat {
+    #[default]
+    Pcm16,
+    Float32,
+}
+
+impl WavFormat {
+    pub const ALL: [WavFormat; 2] = [WavFormat::Pcm16, WavFormat::Float32];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WavFormat::Pcm16 => "16-bit PCM",
+            WavFormat::Float32 => "32-bit float",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<WavFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "pcm16" | "16" | "s16" => Some(WavFormat::Pcm16),
+            "float" | "float32" | "f32" => Some(WavFormat::Float32),
+            _ => None,
+        }
+    }
+
+    fn bytes_per_sample(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 2,
+            WavFormat::Float32 => 4,
+        }
+    }
+}
+
+// Stereo WAV stream. Sizes in the header are placeholders until `finish`
+// patches them, which needs a seekable output.
+pub struct WavWriter<W: Write + Seek> {
+    output: W,
+    sample_rate: u32,
+    format: WavFormat,
+    frames: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut output: W, sample_rate: u32, format: WavFormat) -> Result<Self, String> {
+        write_header(&mut output, sample_rate, format, 0).map_err(|e| e.to_string())?;
+        Ok(Self {
+            output,
+            sample_rate,
+            format,
+            frames: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[(f32, f32)]) -> Result<(), String> {
+        let mut bytes =
+            Vec::with_capacity(samples.len() * 2 * self.format.bytes_per_sample() as usize);
+
+        for &(left, right) in samples {
+            for sample in [left, right] {
+                match self.format {
+                    WavFormat::Pcm16 => {
+                        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+                        bytes.extend_from_slice(&value.to_le_bytes());
+                    }
+                    WavFormat::Float32 => bytes.extend_from_slice(&sample.to_le_bytes()),
+                }
+            }
+        }
+
+        self.output.write_all(&bytes).map_err(|e| e.to_string())?;
+        self.frames += samples.len() as u32;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Result<W, String> {
+        let result = self
+            .output
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| {
+                write_header(&mut self.output, self.sample_rate, self.format, self.frames)
+            })
+            .and_then(|_| self.output.seek(SeekFrom::End(0)))
+            .and_then(|_| self.output.flush());
+        result.map_err(|e| e.to_string())?;
+        Ok(self.output)
+    }
+}
+
+// Linear interpolation from the rate samples come in at to the file's rate.
+// The position carries over between chunks, so the stream stays continuous.
+#[derive(Debug, Clone)]
+struct Resampler {
+    // Next output, in input samples counted from the previous chunk's last one
+    position: f64,
+    previous: (f32, f32),
+}
+
+impl Resampler {
+    fn new() -> Self {
+        Self {
+            position: 1.0,
+            previous: (0.0, 0.0),
+        }
+    }
+
+    // `step`: input samples per output sample
+    fn process(&mut self, samples: &[(f32, f32)], step: f64) -> Vec<(f32, f32)> {
+        let Some(&last) = samples.last() else {
+            return Vec::new();
+        };
+
+        let mut output = Vec::with_capacity((samples.len() as f64 / step) as usize + 1);
+        let input = |index: usize| {
+            if index == 0 {
+                self.previous
+            } else {
+                samples[index - 1]
+            }
+        };
+        while self.position <= samples.len() as f64 {
+            let index = self.position.floor() as usize;
+            let fraction = self.position.fract() as f32;
+            let (left, right) = input(index);
+            let sample = if fraction > 0.0 {
+                let (next_left, next_right) = input(index + 1);
+                (
+                    left + (next_left - left) * fraction,
+                    right + (next_right - right) * fraction,
+                )
+            } else {
+                (left, right)
+            };
+            output.push(sample);
+            self.position += step;
+        }
+        self.position -= samples.len() as f64;
+        self.previous = last;
+        output
+    }
+}
+
+// Main mix plus optional per-channel stems next to it: `out.wav` gives
+// `out.ch1.wav` .. `out.ch4.wav`
+pub struct AudioRecorder {
+    path: String,
+    mix: WavWriter<BufWriter<File>>,
+    stems: Option<Vec<WavWriter<BufWriter<File>>>>,
+    // Samples may come in at another rate than the header's
+    input_rate: u32,
+    resamplers: [Resampler; 5], // mix, then stems
+}
+
+impl AudioRecorder {
+    pub fn create(
+        path: &str,
+        sample_rate: u32,
+        format: WavFormat,
+        stems: bool,
+    ) -> Result<Self, String> {
+        let open = |path: &str| -> Result<WavWriter<BufWriter<File>>, String> {
+            let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+            WavWriter::new(BufWriter::new(file), sample_rate, format)
+        };
+
+        let stems = if stems {
+            Some(
+                stem_paths(path)
+                    .iter()
+                    .map(|p| open(p))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            path: path.to_string(),
+            mix: open(path)?,
+            stems,
+            input_rate: sample_rate,
+            resamplers: std::array::from_fn(|_| Resampler::new()),
+        })
+    }
+
+    // Rate the following samples were synthesized at, e.g. when rate control
+    // nudges the APU around the recording rate
+    pub fn set_input_rate(&mut self, rate: u32) {
+        self.input_rate = rate;
+    }
+
+    fn resample_step(&self) -> f64 {
+        self.input_rate as f64 / self.mix.sample_rate as f64
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn records_stems(&self) -> bool {
+        self.stems.is_some()
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.mix.frames() as f32 / self.mix.sample_rate as f32
+    }
+
+    pub fn write_samples(&mut self, samples: &[(f32, f32)]) -> Result<(), String> {
+        let samples = self.resamplers[0].process(samples, self.resample_step());
+        self.mix.write_samples(&samples)
+    }
+
+    pub fn write_stems(&mut self, stems: &[Vec<(f32, f32)>; 4]) -> Result<(), String> {
+        let step = self.resample_step();
+        if let Some(writers) = &mut self.stems {
+            let resamplers = self.resamplers[1..].iter_mut();
+            for ((writer, resampler), samples) in writers.iter_mut().zip(resamplers).zip(stems) {
+                writer.write_samples(&resampler.process(samples, step))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        self.mix.finish()?;
+        for writer in self.stems.into_iter().flatten() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn stem_paths(path: &str) -> [String; 4] {
+    let stem = path.strip_suffix(".wav").unwrap_or(path);
+    std::array::from_fn(|channel| format!("{}.ch{}.wav", stem, channel + 1))
+}
+
+fn write_header<W: Write>(
+    output: &mut W,
+    sample_rate: u32,
+    format: WavFormat,
+    frames: u32,
+) -> std::io::Result<()> {
+    let bytes_per_sample = format.bytes_per_sample();
+    let block_align = CHANNELS * bytes_per_sample;
+    let data_size = frames * block_align as u32;
+
+    // Non-PCM formats carry an extension size and a fact chunk
+    let (format_tag, fmt_size, fact): (u16, u32, bool) = match format {
+        WavFormat::Pcm16 => (1, 16, false),
+        WavFormat::Float32 => (3, 18, true),
+    };
+    let riff_size = 4 + (8 + fmt_size) + if fact { 12 } else { 0 } + 8 + data_size;
+
+    output.write_all(b"RIFF")?;
+    output.write_all(&riff_size.to_le_bytes())?;
+    output.write_all(b"WAVE")?;
+
+    output.write_all(b"fmt ")?;
+    output.write_all(&fmt_size.to_le_bytes())?;
+    output.write_all(&format_tag.to_le_bytes())?;
+    output.write_all(&CHANNELS.to_le_bytes())?;
+    output.write_all(&sample_rate.to_le_bytes())?;
+    output.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+    output.write_all(&block_align.to_le_bytes())?;
+    output.write_all(&(bytes_per_sample * 8).to_le_bytes())?;
+    if fact {
+        output.write_all(&0u16.to_le_bytes())?;
+        output.write_all(b"fact")?;
+        output.write_all(&4u32.to_le_bytes())?;
+        output.write_all(&frames.to_le_bytes())?;
+    }
+
+    output.write_all(b"data")?;
+    output.write_all(&data_size.to_le_bytes())
+}
@@ -0,0 +1,242 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+// 8192 Hz internal clock: one bit every 128 M-cycles
+const CYCLES_PER_BIT: u16 = 128;
+
+// What sits at the other end of the link port. Transfers are exchanged a
+// byte at a time, the controller then shifts the bits in at the serial clock.
+pub trait SerialLink: Debug + Send {
+    // We are the master (internal clock) and send `outgoing`; returns the
+    // byte shifted in from the other side
+    fn transfer(&mut self, outgoing: u8) -> u8;
+
+    // Non-blocking `transfer`: None when the answer is not known yet, the
+    // controller then polls `poll_transfer` before shifting any bit in
+    fn start_transfer(&mut self, outgoing: u8) -> Option<u8> {
+        Some(self.transfer(outgoing))
+    }
+
+    fn poll_transfer(&mut self) -> Option<u8> {
+        Some(0xFF)
+    }
+
+    // We wait on an external clock with `outgoing` in SB; returns the byte
+    // received once the other side has clocked a transfer
+    fn receive(&mut self, _outgoing: u8) -> Option<u8> {
+        None
+    }
+
+    // We stopped waiting on the external clock before a byte came in
+    fn cancel(&mut self) {}
+
+    // Copy for a cloned console. Links tied to another peer or device
+    // can't be duplicated, the copy is left unplugged
+    fn box_clone(&self) -> Box<dyn SerialLink> {
+        Box::new(Disconnected)
+    }
+}
+
+impl Clone for Box<dyn SerialLink> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+// No cable: the input line floats high, external clock never comes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Disconnected;
+
+impl SerialLink for Disconnected {
+    fn transfer(&mut self, _outgoing: u8) -> u8 {
+        0xFF
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialLink> {
+        Box::new(*self)
+    }
+}
+
+pub type SerialBuffer = Arc<Mutex<Vec<u8>>>;
+
+// Records every byte sent, e.g. the text test ROMs print over serial
+#[derive(Debug, Clone, Default)]
+pub struct CaptureLink {
+    buffer: SerialBuffer,
+}
+
+impl CaptureLink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Shared with the link, stays readable once it is plugged in
+    pub fn buffer(&self) -> SerialBuffer {
+        Arc::clone(&self.buffer)
+    }
+}
+
+impl SerialLink for CaptureLink {
+    fn transfer(&mut self, outgoing: u8) -> u8 {
+        self.buffer.lock().unwrap().push(outgoing);
+        0xFF
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialLink> {
+        Box::new(self.clone())
+    }
+}
+
+// Output wired to input: every byte comes back
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Loopback;
+
+impl SerialLink for Loopback {
+    fn transfer(&mut self, outgoing: u8) -> u8 {
+        outgoing
+    }
+
+    fn box_clone(&self) -> Box<dyn SerialLink> {
+        Box::new(*self)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Serial {
+    pub sb: u8,
+    pub sc: u8,
+
+    link: Box<dyn SerialLink>,
+
+    // Byte being shifted in and bits left, while a transfer runs
+    incoming: u8,
+    bits_remaining: u8,
+    bit_counter: u16,
+    // Waiting on the link for `incoming`, the clock is held meanwhile
+    reply_pending: bool,
+
+    pub interrupt_requested: bool,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Self {
+        Self {
+            sb: 0,
+            sc: 0,
+            link: Box::new(Disconnected),
+            incoming: 0xFF,
+            bits_remaining: 0,
+            bit_counter: 0,
+            reply_pending: false,
+            interrupt_requested: false,
+        }
+    }
+
+    pub fn set_link(&mut self, link: Box<dyn SerialLink>) {
+        self.link = link;
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+        match address {
+            0xFF01 => self.sb,
+            0xFF02 => self.sc | 0x7E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0xFF01 => self.sb = value,
+            0xFF02 => {
+                let was_waiting = self.waiting_on_external_clock();
+                self.sc = value & 0x81;
+                if was_waiting && !self.waiting_on_external_clock() {
+                    self.link.cancel();
+                }
+                self.bits_remaining = 0;
+                self.reply_pending = false;
+                if self.transfer_requested() && self.internal_clock() {
+                    match self.link.start_transfer(self.sb) {
+                        Some(incoming) => self.incoming = incoming,
+                        None => self.reply_pending = true,
+                    }
+                    self.bits_remaining = 8;
+                    self.bit_counter = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn transfer_requested(&self) -> bool {
+        self.sc & 0x80 != 0
+    }
+
+    fn internal_clock(&self) -> bool {
+        self.sc & 0x01 != 0
+    }
+
+    fn waiting_on_external_clock(&self) -> bool {
+        self.transfer_requested() && !self.internal_clock()
+    }
+
+    // Advances by `cpu_cycles` M-cycles
+    pub fn step(&mut self, cpu_cycles: u8) {
+        if !self.transfer_requested() {
+            return;
+        }
+
+        if !self.internal_clock() {
+            if let Some(incoming) = self.link.receive(self.sb) {
+                self.sb = incoming;
+                self.complete_transfer();
+            }
+            return;
+        }
+
+        if self.reply_pending {
+            match self.link.poll_transfer() {
+                Some(incoming) => {
+                    self.incoming = incoming;
+                    self.reply_pending = false;
+                }
+                None => return,
+            }
+        }
+
+        for _ in 0..cpu_cycles {
+            self.bit_counter += 1;
+            if self.bit_counter < CYCLES_PER_BIT {
+                continue;
+            }
+            self.bit_counter = 0;
+
+            // MSB first out, the other side's bit in at the bottom
+            self.bits_remaining -= 1;
+            let bit = (self.incoming >> self.bits_remaining) & 0x01;
+            self.sb = (self.sb << 1) | bit;
+
+            if self.bits_remaining == 0 {
+                self.complete_transfer();
+                return;
+            }
+        }
+    }
+
+    fn complete_transfer(&mut self) {
+        self.sc &= 0x7F;
+        self.interrupt_requested = true;
+    }
+
+    pub fn take_interrupt(&mut self) -> bool {
+        let interrupt = self.interrupt_requested;
+        self.interrupt_requested = false;
+        interrupt
+    }
+}
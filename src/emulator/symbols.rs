@@ -0,0 +1,156 @@
+use crate::emulator::debugger::parse_number;
+use std::collections::{BTreeMap, HashMap};
+
+// Labels from an RGBDS (rgblink -n) or no$gmb symbol file: one
+// "bank:address name" per line, ';' starting a comment. Addresses in the
+// switchable ROM area only match while their bank is mapped.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    labels: BTreeMap<(usize, u16), String>,
+    addresses: HashMap<String, (usize, u16)>,
+}
+
+// Only 0x4000-0x7FFF is banked here, everything else is bank 0
+fn bank_key(address: u16, rom_bank: usize) -> usize {
+    if (0x4000..0x8000).contains(&address) {
+        rom_bank
+    } else {
+        0
+    }
+}
+
+// A label only covers addresses up to the end of its memory area
+fn area_start(address: u16) -> u16 {
+    match address {
+        0x0000..=0x3FFF => 0x0000,
+        0x4000..=0x7FFF => 0x4000,
+        0x8000..=0x9FFF => 0x8000,
+        0xA000..=0xBFFF => 0xA000,
+        0xC000..=0xDFFF => 0xC000,
+        0xFF80..=0xFFFE => 0xFF80,
+        _ => address,
+    }
+}
+
+pub fn symbol_path(rom_path: &str) -> String {
+    let stem = match rom_path.rfind('.') {
+        Some(dot) if !rom_path[dot..].contains('/') => &rom_path[..dot],
+        _ => rom_path,
+    };
+    format!("{}.sym", stem)
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut table = Self::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = || format!("line {}: invalid symbol '{}'", number + 1, line);
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(error)?;
+            let (bank, address) = location.split_once(':').ok_or_else(error)?;
+            let bank = usize::from_str_radix(bank, 16).map_err(|_| error())?;
+            let address = u16::from_str_radix(address, 16).map_err(|_| error())?;
+            table.insert(bank, address, name.trim());
+        }
+
+        Ok(table)
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn insert(&mut self, bank: usize, address: u16, name: &str) {
+        let key = (bank_key(address, bank), address);
+        self.addresses.insert(name.to_string(), key);
+
+        // Global labels win over local ones (".loop") at the same address
+        let replace = match self.labels.get(&key) {
+            None => true,
+            Some(existing) => existing.contains('.') && !name.contains('.'),
+        };
+        if replace {
+            self.labels.insert(key, name.to_string());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    // Bank and address of a label
+    pub fn lookup(&self, name: &str) -> Option<(usize, u16)> {
+        self.addresses.get(name).copied()
+    }
+
+    pub fn label_at(&self, address: u16, rom_bank: usize) -> Option<&str> {
+        self.labels
+            .get(&(bank_key(address, rom_bank), address))
+            .map(String::as_str)
+    }
+
+    // Nearest label at or before `address` in the same memory area, with
+    // the offset from it
+    pub fn resolve(&self, address: u16, rom_bank: usize) -> Option<(&str, u16)> {
+        let bank = bank_key(address, rom_bank);
+        self.labels
+            .range((bank, area_start(address))..=(bank, address))
+            .next_back()
+            .map(|(&(_, start), name)| (name.as_str(), address - start))
+    }
+
+    // "Main", "Main+$12", or None without a label before it
+    pub fn describe(&self, address: u16, rom_bank: usize) -> Option<String> {
+        self.resolve(address, rom_bank)
+            .map(|(name, offset)| match offset {
+                0 => name.to_string(),
+                _ => format!("{}+${:X}", name, offset),
+            })
+    }
+
+    // "$0150" or "$0150 (Main)"
+    pub fn format_address(&self, address: u16, rom_bank: usize) -> String {
+        match self.describe(address, rom_bank) {
+            Some(label) => format!("${:04X} ({})", address, label),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    // A label name, "BB:ADDR" or a number as accepted by the debugger. The
+    // ROM bank is only kept for the switchable area, 0x4000-0x7FFF.
+    pub fn parse_address(&self, text: &str) -> Result<(Option<u8>, u16), String> {
+        let text = text.trim();
+        let unknown = || format!("unknown label or address '{}'", text);
+        let (bank, address) = match self.lookup(text) {
+            Some((bank, address)) => (Some(bank), address),
+            None => match text.split_once(':') {
+                Some((bank, address)) => (
+                    Some(usize::from_str_radix(bank, 16).map_err(|_| unknown())?),
+                    parse_number(address).map_err(|_| unknown())?,
+                ),
+                None => (None, parse_number(text).map_err(|_| unknown())?),
+            },
+        };
+        if !(0x4000..0x8000).contains(&address) {
+            return Ok((None, address));
+        }
+        match bank.map(u8::try_from).transpose() {
+            Ok(bank) => Ok((bank, address)),
+            Err(_) => Err(format!("ROM bank out of range in '{}'", text)),
+        }
+    }
+}
@@ -1,12 +1,23 @@
+use crate::{debug, trace};
+
+// TIMA overflow: TIMA reads 0x00 for one M-cycle, then TMA is loaded and the
+// interrupt requested. Writes during those two cycles interact with the reload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reload {
+    Idle,
+    Pending,   // cycle after the overflow, a TIMA write cancels the reload
+    Reloading, // cycle of the reload, TIMA writes are ignored, TMA writes go through
+}
+
 #[derive(Debug, Clone)]
 pub struct Timer {
-    pub div: u8,
     pub tima: u8,
     pub tma: u8,
     pub tac: u8,
 
-    div_counter: u16,
-    tima_counter: u16,
+    // Internal 16-bit divider counting T-cycles, DIV being its upper byte
+    divider: u16,
+    reload: Reload,
 
     pub interrupt_requested: bool,
 }
@@ -20,19 +31,18 @@ impl Default for Timer {
 impl Timer {
     pub fn new() -> Self {
         Self {
-            div: 0,
             tima: 0,
             tma: 0,
             tac: 0,
-            div_counter: 0,
-            tima_counter: 0,
+            divider: 0,
+            reload: Reload::Idle,
             interrupt_requested: false,
         }
     }
 
     pub fn read_register(&self, address: u16) -> u8 {
         match address {
-            0xFF04 => self.div,
+            0xFF04 => self.div(),
             0xFF05 => self.tima,
             0xFF06 => self.tma,
             0xFF07 => self.tac | 0xF8,
@@ -41,43 +51,80 @@ impl Timer {
     }
 
     pub fn write_register(&mut self, address: u16, value: u8) {
+        let input = self.timer_input();
+
         match address {
-            0xFF04 => {
-                self.div = 0;
-                self.div_counter = 0;
+            // Resetting the divider can make the selected bit fall
+            0xFF04 => self.divider = 0,
+            0xFF05 => match self.reload {
+                Reload::Pending => {
+                    self.tima = value;
+                    self.reload = Reload::Idle;
+                }
+                Reload::Reloading => {}
+                Reload::Idle => self.tima = value,
+            },
+            0xFF06 => {
+                self.tma = value;
+                if self.reload == Reload::Reloading {
+                    self.tima = value;
+                }
+            }
+            // Disabling the timer or switching to a cleared bit is an edge too
+            0xFF07 => {
+                debug!(Timer, "TAC = ${:02X}", value & 0x07);
+                self.tac = value & 0x07
             }
-            0xFF05 => self.tima = value,
-            0xFF06 => self.tma = value,
-            0xFF07 => self.tac = value & 0x07,
             _ => {}
         }
+
+        self.detect_edge(input);
     }
 
+    // Advances by `cpu_cycles` M-cycles
     pub fn step(&mut self, cpu_cycles: u8) {
-        let timer_cycles = cpu_cycles as u16 * 4;
+        for _ in 0..cpu_cycles {
+            self.reload = match self.reload {
+                Reload::Pending => {
+                    self.tima = self.tma;
+                    self.interrupt_requested = true;
+                    Reload::Reloading
+                }
+                Reload::Reloading | Reload::Idle => Reload::Idle,
+            };
 
-        self.div_counter = self.div_counter.wrapping_add(timer_cycles);
-        while self.div_counter >= 256 {
-            self.div = self.div.wrapping_add(1);
-            self.div_counter = self.div_counter.wrapping_sub(256);
+            let input = self.timer_input();
+            self.divider = self.divider.wrapping_add(4);
+            self.detect_edge(input);
         }
+    }
 
-        if self.is_timer_enabled() {
-            let tima_increment_rate = self.get_tima_frequency();
+    pub fn div(&self) -> u8 {
+        (self.divider >> 8) as u8
+    }
 
-            self.tima_counter = self.tima_counter.wrapping_add(timer_cycles);
+    pub fn divider(&self) -> u16 {
+        self.divider
+    }
 
-            while self.tima_counter >= tima_increment_rate {
-                self.tima_counter = self.tima_counter.wrapping_sub(tima_increment_rate);
+    // TIMA is clocked by the falling edge of the divider bit selected by TAC,
+    // ANDed with the enable bit
+    fn timer_input(&self) -> bool {
+        self.is_timer_enabled() && self.divider & self.input_bit_mask() != 0
+    }
 
-                // Increment TIMA
-                if self.tima == 0xFF {
-                    self.tima = self.tma;
-                    self.interrupt_requested = true;
-                } else {
-                    self.tima = self.tima.wrapping_add(1);
-                }
-            }
+    fn detect_edge(&mut self, previous_input: bool) {
+        if previous_input && !self.timer_input() {
+            self.increment_tima();
+        }
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        if overflow {
+            trace!(Timer, "TIMA overflow, reloading ${:02X}", self.tma);
+            self.reload = Reload::Pending;
         }
     }
 
@@ -85,12 +132,12 @@ impl Timer {
         (self.tac & 0x04) != 0
     }
 
-    fn get_tima_frequency(&self) -> u16 {
+    fn input_bit_mask(&self) -> u16 {
         match self.tac & 0x03 {
-            0 => 1024, // 4096 Hz   (4194304 / 4096 = 1024 cycles)
-            1 => 16,   // 262144 Hz (4194304 / 262144 = 16 cycles)
-            2 => 64,   // 65536 Hz  (4194304 / 65536 = 64 cycles)
-            3 => 256,  // 16384 Hz  (4194304 / 16384 = 256 cycles)
+            0 => 1 << 9, // 4096 Hz   (1024 cycles)
+            1 => 1 << 3, // 262144 Hz (16 cycles)
+            2 => 1 << 5, // 65536 Hz  (64 cycles)
+            3 => 1 << 7, // 16384 Hz  (256 cycles)
             _ => unreachable!(),
         }
     }
@@ -0,0 +1,86 @@
+use crate::emulator::{
+    disassembler::disassemble,
+    gameboy::{Gameboy, MAX_FRAME_STEPS, StopReason},
+    symbols::SymbolTable,
+};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// One line per executed instruction, before it runs:
+// "01:4123 Main+$3              LD A,($C000)         AF=01B0 BC=0013 ..."
+pub struct TraceLog<W: Write> {
+    output: W,
+    lines: u64,
+}
+
+impl TraceLog<BufWriter<File>> {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> TraceLog<W> {
+    pub fn new(output: W) -> Self {
+        Self { output, lines: 0 }
+    }
+
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn log_instruction(
+        &mut self,
+        gameboy: &Gameboy,
+        symbols: &SymbolTable,
+    ) -> Result<(), String> {
+        let cpu = &gameboy.cpu;
+        let rom_bank = gameboy.bus.memory.rom_bank();
+        let bank = if (0x4000..0x8000).contains(&cpu.pc) {
+            rom_bank
+        } else {
+            0
+        };
+        let instruction = disassemble(&gameboy.bus, cpu.pc);
+
+        writeln!(
+            self.output,
+            "{:02X}:{:04X} {:<24} {:<20} AF={:04X} BC={:04X} DE={:04X} HL={:04X} SP={:04X}",
+            bank,
+            cpu.pc,
+            symbols.describe(cpu.pc, rom_bank).unwrap_or_default(),
+            instruction.text_with_symbols(symbols, rom_bank),
+            cpu.af(),
+            cpu.bc(),
+            cpu.de(),
+            cpu.hl(),
+            cpu.sp
+        )
+        .map_err(|e| e.to_string())?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    // Gameboy::run_frame, logging every instruction. The inner result is
+    // the emulation's, the outer one the log file's.
+    pub fn run_frame(
+        &mut self,
+        gameboy: &mut Gameboy,
+        symbols: &SymbolTable,
+    ) -> Result<Result<(), StopReason>, String> {
+        for _ in 0..MAX_FRAME_STEPS {
+            self.log_instruction(gameboy, symbols)?;
+            match gameboy.step() {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(reason) => return Ok(Err(reason)),
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    pub fn finish(mut self) -> Result<W, String> {
+        self.output.flush().map_err(|e| e.to_string())?;
+        Ok(self.output)
+    }
+}
@@ -0,0 +1,253 @@
+use crate::emulator::{memory::Memory, palette::Palette, postprocess::Frame, ppu::PPU};
+
+pub const TILE_COUNT: usize = 384;
+pub const TILES_PER_ROW: usize = 16;
+pub const MAP_SIZE: usize = 256; // 32x32 tiles of 8x8 pixels
+
+const VIEWPORT_COLOR: [u8; 4] = [255, 0, 0, 255];
+const WINDOW_COLOR: [u8; 4] = [0, 128, 255, 255];
+
+// Which DMG palette register maps tile color ids to shades in the viewers
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TilePalette {
+    #[default]
+    Bgp,
+    Obp0,
+    Obp1,
+    Raw, // color id used as shade directly
+}
+
+impl TilePalette {
+    pub const ALL: [TilePalette; 4] = [
+        TilePalette::Bgp,
+        TilePalette::Obp0,
+        TilePalette::Obp1,
+        TilePalette::Raw,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TilePalette::Bgp => "BGP",
+            TilePalette::Obp0 => "OBP0",
+            TilePalette::Obp1 => "OBP1",
+            TilePalette::Raw => "Raw",
+        }
+    }
+
+    fn register(&self, ppu: &PPU) -> u8 {
+        match self {
+            TilePalette::Bgp => ppu.bgp,
+            TilePalette::Obp0 => ppu.obp0,
+            TilePalette::Obp1 => ppu.obp1,
+            TilePalette::Raw => 0xE4, // 3-2-1-0, identity mapping
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OamEntry {
+    pub index: u8,
+    pub y: u8,
+    pub x: u8,
+    pub tile: u8,
+    pub flags: u8,
+}
+
+impl OamEntry {
+    pub fn behind_bg(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+
+    pub fn y_flip(&self) -> bool {
+        self.flags & 0x40 != 0
+    }
+
+    pub fn x_flip(&self) -> bool {
+        self.flags & 0x20 != 0
+    }
+
+    pub fn uses_obp1(&self) -> bool {
+        self.flags & 0x10 != 0
+    }
+
+    // Hidden when fully off-screen
+    pub fn is_visible(&self) -> bool {
+        self.x > 0 && self.x < 168 && self.y > 0 && self.y < 160
+    }
+}
+
+pub fn read_oam_entries(memory: &Memory) -> Vec<OamEntry> {
+    (0..40u8)
+        .map(|index| {
+            let base = 0xFE00 + index as u16 * 4;
+            OamEntry {
+                index,
+                y: memory.read_oam(base),
+                x: memory.read_oam(base + 1),
+                tile: memory.read_oam(base + 2),
+                flags: memory.read_oam(base + 3),
+            }
+        })
+        .collect()
+}
+
+fn tile_color_id(memory: &Memory, tile_addr: u16, x: usize, y: usize) -> u8 {
+    let byte1 = memory.read_vram(tile_addr + y as u16 * 2);
+    let byte2 = memory.read_vram(tile_addr + y as u16 * 2 + 1);
+    let bit_pos = 7 - x;
+    (((byte2 >> bit_pos) & 1) << 1) | ((byte1 >> bit_pos) & 1)
+}
+
+fn shade(register: u8, color_id: u8) -> u8 {
+    (register >> (color_id * 2)) & 0x03
+}
+
+// All 384 tiles of 0x8000-0x97FF, 16 per row
+pub fn render_tiles(
+    memory: &Memory,
+    ppu: &PPU,
+    tile_palette: TilePalette,
+    palette: &Palette,
+) -> Frame {
+    let rows = TILE_COUNT / TILES_PER_ROW;
+    let mut frame = Frame::new(TILES_PER_ROW * 8, rows * 8);
+    let register = tile_palette.register(ppu);
+
+    for tile in 0..TILE_COUNT {
+        let tile_addr = 0x8000 + tile as u16 * 16;
+        let origin_x = (tile % TILES_PER_ROW) * 8;
+        let origin_y = (tile / TILES_PER_ROW) * 8;
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let color_id = tile_color_id(memory, tile_addr, x, y);
+                frame.set_pixel(
+                    origin_x + x,
+                    origin_y + y,
+                    palette.rgba(shade(register, color_id)),
+                );
+            }
+        }
+    }
+
+    frame
+}
+
+fn map_tile_address(ppu: &PPU, tile_id: u8) -> u16 {
+    if (ppu.lcdc & 0x10) != 0 {
+        0x8000 + tile_id as u16 * 16
+    } else {
+        0x9000_u16.wrapping_add(((tile_id as i8 as i16) * 16) as u16)
+    }
+}
+
+fn draw_rect(
+    frame: &mut Frame,
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    color: [u8; 4],
+) {
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    // Wraps around like the background does
+    let w = frame.width;
+    let h = frame.height;
+    for dx in 0..width {
+        frame.set_pixel((left + dx) % w, top % h, color);
+        frame.set_pixel((left + dx) % w, (top + height - 1) % h, color);
+    }
+    for dy in 0..height {
+        frame.set_pixel(left % w, (top + dy) % h, color);
+        frame.set_pixel((left + width - 1) % w, (top + dy) % h, color);
+    }
+}
+
+// 32x32 tile map at `map_base` (0x9800 or 0x9C00), with the SCX/SCY viewport
+// drawn when it is the BG map and the window area when it is the window map
+pub fn render_tile_map(memory: &Memory, ppu: &PPU, map_base: u16, palette: &Palette) -> Frame {
+    let mut frame = Frame::new(MAP_SIZE, MAP_SIZE);
+
+    for map_y in 0..32 {
+        for map_x in 0..32 {
+            let tile_id = memory.read_vram(map_base + (map_y * 32 + map_x) as u16);
+            let tile_addr = map_tile_address(ppu, tile_id);
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let color_id = tile_color_id(memory, tile_addr, x, y);
+                    frame.set_pixel(
+                        map_x * 8 + x,
+                        map_y * 8 + y,
+                        palette.rgba(shade(ppu.bgp, color_id)),
+                    );
+                }
+            }
+        }
+    }
+
+    let bg_map = if (ppu.lcdc & 0x08) != 0 {
+        0x9C00
+    } else {
+        0x9800
+    };
+    let window_map = if (ppu.lcdc & 0x40) != 0 {
+        0x9C00
+    } else {
+        0x9800
+    };
+    let window_enabled = (ppu.lcdc & 0x20) != 0;
+
+    if map_base == window_map && window_enabled && ppu.wx <= 166 && ppu.wy <= 143 {
+        let width = 160 - (ppu.wx as usize).saturating_sub(7);
+        let height = 144 - ppu.wy as usize;
+        draw_rect(&mut frame, 0, 0, width, height, WINDOW_COLOR);
+    }
+
+    if map_base == bg_map {
+        draw_rect(
+            &mut frame,
+            ppu.scx as usize,
+            ppu.scy as usize,
+            160,
+            144,
+            VIEWPORT_COLOR,
+        );
+    }
+
+    frame
+}
+
+// Sprite preview, 8x8 or 8x16 depending on LCDC bit 2. Color 0 is transparent.
+pub fn render_sprite(memory: &Memory, ppu: &PPU, entry: &OamEntry, palette: &Palette) -> Frame {
+    let height = if (ppu.lcdc & 0x04) != 0 { 16 } else { 8 };
+    let tile = if height == 16 {
+        entry.tile & 0xFE
+    } else {
+        entry.tile
+    };
+    let register = if entry.uses_obp1() {
+        ppu.obp1
+    } else {
+        ppu.obp0
+    };
+    let mut frame = Frame::new(8, height);
+
+    for y in 0..height {
+        let row = if entry.y_flip() { height - 1 - y } else { y };
+        let tile_addr = 0x8000 + (tile as u16 + (row / 8) as u16) * 16;
+
+        for x in 0..8 {
+            let column = if entry.x_flip() { 7 - x } else { x };
+            let color_id = tile_color_id(memory, tile_addr, column, row % 8);
+            if color_id != 0 {
+                frame.set_pixel(x, y, palette.rgba(shade(register, color_id)));
+            }
+        }
+    }
+
+    frame
+}
@@ -1,29 +1,294 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Lines kept for the GUI log console, oldest dropped first
+pub const CONSOLE_LINES: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub const ALL: [Level; 6] = [
+        Level::Off,
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+// Subsystem a message comes from, each with its own level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Cpu,
+    Ppu,
+    Apu,
+    Bus,
+    Timer,
+}
+
+impl Target {
+    pub const ALL: [Target; 5] = [
+        Target::Cpu,
+        Target::Ppu,
+        Target::Apu,
+        Target::Bus,
+        Target::Timer,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::Cpu => "cpu",
+            Target::Ppu => "ppu",
+            Target::Apu => "apu",
+            Target::Bus => "bus",
+            Target::Timer => "timer",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|target| target.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub level: Level,
+    pub target: Target,
+    pub message: String,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{} {}] {}",
+            self.level.name().to_uppercase(),
+            self.target.name(),
+            self.message
+        )
+    }
+}
+
+struct Output {
+    stderr: bool,
+    file: Option<BufWriter<File>>,
+    console: Option<VecDeque<Record>>,
+}
+
+// Warnings and errors only until configured
+static LEVELS: [AtomicU8; Target::ALL.len()] =
+    [const { AtomicU8::new(Level::Warn as u8) }; Target::ALL.len()];
+static OUTPUT: Mutex<Output> = Mutex::new(Output {
+    stderr: true,
+    file: None,
+    console: None,
+});
+
+fn output() -> std::sync::MutexGuard<'static, Output> {
+    OUTPUT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn level(target: Target) -> Level {
+    Level::ALL[LEVELS[target as usize].load(Ordering::Relaxed) as usize]
+}
+
+pub fn set_level(target: Target, level: Level) {
+    LEVELS[target as usize].store(level as u8, Ordering::Relaxed);
+}
+
+pub fn set_all_levels(level: Level) {
+    for target in Target::ALL {
+        set_level(target, level);
+    }
+}
+
+// What the macros check before formatting anything, so a disabled message
+// costs one atomic load
+#[inline]
+pub fn enabled(target: Target, level: Level) -> bool {
+    level as u8 <= LEVELS[target as usize].load(Ordering::Relaxed)
+}
+
+// "info", "ppu=debug" or a mix: "warn,cpu=info,ppu=trace". Entries apply in
+// order, a bare level (no target) sets every target.
+pub fn parse_filter(filter: &str) -> Result<Vec<(Option<Target>, Level)>, String> {
+    filter
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (target, name) = match entry.split_once('=') {
+                Some((target, name)) => (Some(target.trim()), name.trim()),
+                None => (None, entry),
+            };
+            let level =
+                Level::from_name(name).ok_or_else(|| format!("unknown log level '{}'", name))?;
+            let target = match target {
+                Some(name) => Some(
+                    Target::from_name(name)
+                        .ok_or_else(|| format!("unknown log target '{}'", name))?,
+                ),
+                None => None,
+            };
+            Ok((target, level))
+        })
+        .collect()
+}
+
+pub fn set_filter(filter: &str) -> Result<(), String> {
+    for (target, level) in parse_filter(filter)? {
+        match target {
+            Some(target) => set_level(target, level),
+            None => set_all_levels(level),
+        }
+    }
+    Ok(())
+}
+
+pub fn log_to_stderr(enabled: bool) {
+    output().stderr = enabled;
+}
+
+// Appends to `path`, replacing any previous log file
+pub fn log_to_file(path: &str) -> Result<(), String> {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    output().file = Some(BufWriter::new(file));
+    Ok(())
+}
+
+pub fn close_file() {
+    if let Some(mut file) = output().file.take() {
+        let _ = file.flush();
+    }
+}
+
+// Keeps records for the GUI console while enabled
+pub fn capture_console(enabled: bool) {
+    let mut output = output();
+    if enabled {
+        output.console.get_or_insert_with(VecDeque::new);
+    } else {
+        output.console = None;
+    }
+}
+
+pub fn take_console_records() -> Vec<Record> {
+    match &mut output().console {
+        Some(console) => console.drain(..).collect(),
+        None => Vec::new(),
+    }
+}
+
+pub fn flush() {
+    if let Some(file) = &mut output().file {
+        let _ = file.flush();
+    }
+}
+
+pub fn log(target: Target, level: Level, args: fmt::Arguments) {
+    let record = Record {
+        level,
+        target,
+        message: args.to_string(),
+    };
+
+    let mut output = output();
+    if output.stderr {
+        eprintln!("{}", record);
+    }
+    if let Some(file) = &mut output.file {
+        let _ = writeln!(file, "{}", record);
+    }
+    if let Some(console) = &mut output.console {
+        if console.len() == CONSOLE_LINES {
+            console.pop_front();
+        }
+        console.push_back(record);
+    }
+}
+
+// Usage: `debug!(Ppu, "LY={}", ly)`. Arguments are only evaluated when the
+// target's level lets the message through.
 #[macro_export]
-macro_rules! debug {
-    ($($arg:tt)*) => {
-        #[cfg(debug_assertions)]
-        println!("[DEBUG] {}", format!($($arg)*));
+macro_rules! log {
+    ($level:ident, $target:ident, $($arg:tt)*) => {
+        if $crate::logger::enabled(
+            $crate::logger::Target::$target,
+            $crate::logger::Level::$level,
+        ) {
+            $crate::logger::log(
+                $crate::logger::Target::$target,
+                $crate::logger::Level::$level,
+                format_args!($($arg)*),
+            );
+        }
     };
 }
 
 #[macro_export]
-macro_rules! info {
-    ($($arg:tt)*) => {
-        println!("[INFO] {}", format!($($arg)*));
+macro_rules! error {
+    ($target:ident, $($arg:tt)*) => {
+        $crate::log!(Error, $target, $($arg)*)
     };
 }
 
 #[macro_export]
 macro_rules! warn {
-    ($($arg:tt)*) => {
-        println!("[WARN] {}", format!($($arg)*));
+    ($target:ident, $($arg:tt)*) => {
+        $crate::log!(Warn, $target, $($arg)*)
     };
 }
 
 #[macro_export]
-macro_rules! error {
-    ($($arg:tt)*) => {
-        println!("[ERROR] {}", format!($($arg)*));
+macro_rules! info {
+    ($target:ident, $($arg:tt)*) => {
+        $crate::log!(Info, $target, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($target:ident, $($arg:tt)*) => {
+        $crate::log!(Debug, $target, $($arg)*)
+    };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($target:ident, $($arg:tt)*) => {
+        $crate::log!(Trace, $target, $($arg)*)
     };
 }
 
@@ -1,6 +1,26 @@
 use emulator::gui::GameBoyApp;
+use emulator::headless::{self, HeadlessOptions};
+use emulator::logger;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Ok(filter) = std::env::var("DMG_LOG")
+        && let Err(e) = logger::set_filter(&filter)
+    {
+        exit_with_error(&format!("DMG_LOG: {}", e));
+    }
+
+    match HeadlessOptions::parse(&args) {
+        Ok(Some(options)) => {
+            if let Err(e) = headless::run(&options) {
+                exit_with_error(&e);
+            }
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(e) => exit_with_error(&e),
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "DMG Emu",
@@ -9,3 +29,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     )
     .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
 }
+
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use emulator::apu::APU;
+    use emulator::apu::{APU, SCOPE_LENGTH, note_name};
 
     #[test]
     fn test_apu_new() {
@@ -92,12 +92,9 @@ mod tests {
 
         assert!(apu.channel1.enabled);
 
-        // Step enough to trigger frame sequencer step 0 (length counter)
-        // Frame sequencer ticks every 8192 T-cycles
-        apu.step(255); // 255 * 4 = 1020 T-cycles
-        for _ in 0..8 {
-            apu.step(255); // 8 * 1020 = 8160 more
-        }
+        // Frame sequencer step 0 clocks the length counter (the timer's
+        // divider drives it through the bus)
+        apu.clock_frame_sequencer();
 
         // After enough steps, the length counter should have expired
         // (length was 1, one length clock sets it to 0 and disables channel)
@@ -154,8 +151,19 @@ mod tests {
         assert_eq!(apu.read_register(0xFF30), 0xAB);
 
         // Length counters (NRx1) can be written when off on DMG
-        apu.write_register(0xFF11, 0x80); // NR11
-        assert_eq!(apu.channel1.nr1, 0x80);
+        apu.write_register(0xFF11, 0x81); // NR11
+        assert_eq!(apu.channel1.nr1, 0x01);
+    }
+
+    #[test]
+    fn test_power_off_length_write_keeps_duty() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF11, 0xFF);
+        apu.write_register(0xFF16, 0xFF);
+
+        apu.write_register(0xFF26, 0x80);
+        assert_eq!(apu.read_register(0xFF11), 0x3F);
+        assert_eq!(apu.read_register(0xFF16), 0x3F);
     }
 
     #[test]
@@ -225,4 +233,374 @@ mod tests {
         let has_nonzero = samples.iter().any(|(l, r)| *l != 0.0 || *r != 0.0);
         assert!(has_nonzero, "Expected some non-zero audio samples");
     }
+
+    #[test]
+    fn test_configurable_sample_rate() {
+        let mut apu = APU::new();
+        assert_eq!(apu.sample_rate(), 44100);
+
+        apu.set_sample_rate(48000);
+        assert_eq!(apu.sample_rate(), 48000);
+
+        // 1/8 s of M-cycles (1048576 per second); time keeps running while
+        // the APU is powered off
+        for _ in 0..(131072 / 128) {
+            apu.step(128);
+        }
+
+        assert_eq!(apu.take_samples().len(), 6000);
+    }
+
+    #[test]
+    fn test_high_pitched_square_is_band_limited() {
+        let mut apu = APU::new();
+
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF24, 0x77);
+        apu.write_register(0xFF25, 0x11); // channel 1 on both sides
+        apu.write_register(0xFF11, 0x80); // 50% duty
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF13, 0xF8); // 2047 - 7: ~16 kHz square
+        apu.write_register(0xFF14, 0x87);
+
+        apu.high_pass_enabled = false;
+        for _ in 0..100 {
+            apu.step(100);
+        }
+
+        // Point sampling only ever produces the two square levels; a
+        // band-limited square above Nyquist/2 is smoothed to in-between values
+        let samples = apu.take_samples();
+        let full = 0.25; // DAC +-1.0, NR50 7: 1.0 * 8 / 32
+        let in_between = samples
+            .iter()
+            .filter(|(l, _)| (l.abs() - full).abs() > 0.01)
+            .count();
+        assert!(in_between > samples.len() / 2);
+    }
+
+    fn powered_apu() -> APU {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF24, 0x77);
+        apu.write_register(0xFF25, 0xFF);
+        apu
+    }
+
+    fn run_samples(apu: &mut APU, m_cycles: u32) -> Vec<(f32, f32)> {
+        for _ in 0..m_cycles / 100 {
+            apu.step(100);
+        }
+        apu.take_samples()
+    }
+
+    #[test]
+    fn test_dac_off_outputs_zero() {
+        let mut apu = powered_apu();
+        apu.high_pass_enabled = false;
+
+        // Volume 0, envelope down: DAC off, channel disabled on trigger
+        apu.write_register(0xFF12, 0x00);
+        apu.write_register(0xFF14, 0x80);
+
+        assert_eq!(apu.channel1.output(), 0.0);
+        let samples = run_samples(&mut apu, 10_000);
+        assert!(samples.iter().all(|&(l, r)| l == 0.0 && r == 0.0));
+    }
+
+    #[test]
+    fn test_dac_on_silent_channel_has_dc_offset() {
+        let mut apu = powered_apu();
+        apu.high_pass_enabled = false;
+
+        // Volume 0 with envelope up keeps the DAC on but the channel silent
+        apu.write_register(0xFF12, 0x08);
+        apu.write_register(0xFF14, 0x80);
+
+        assert!(apu.channel1.dac_enabled());
+        assert_eq!(apu.channel1.digital_output(), 0);
+        assert_eq!(apu.channel1.output(), 1.0);
+
+        let samples = run_samples(&mut apu, 10_000);
+        let (last, _) = *samples.last().unwrap();
+        assert!((last - 0.25).abs() < 0.001); // 1.0 * 8 / 32
+    }
+
+    #[test]
+    fn test_high_pass_filter_removes_dc_offset() {
+        let mut apu = powered_apu();
+
+        apu.write_register(0xFF12, 0x08);
+        apu.write_register(0xFF14, 0x80);
+
+        // The capacitor charges to the offset within a fraction of a second
+        let samples = run_samples(&mut apu, 1_048_576 / 2);
+        let (first, _) = samples[32];
+        let (last, _) = *samples.last().unwrap();
+        assert!(first > 0.2);
+        assert!(last.abs() < 0.001);
+    }
+
+    #[test]
+    fn test_high_pass_square_wave_is_centered() {
+        let mut apu = powered_apu();
+
+        apu.write_register(0xFF11, 0x80); // 50% duty
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF13, 0x00); // ~64 Hz
+        apu.write_register(0xFF14, 0x84);
+
+        run_samples(&mut apu, 1_048_576 / 2);
+        let samples = run_samples(&mut apu, 1_048_576 / 4);
+
+        let mean = samples.iter().map(|&(l, _)| l).sum::<f32>() / samples.len() as f32;
+        let max = samples.iter().map(|&(l, _)| l).fold(f32::MIN, f32::max);
+        let min = samples.iter().map(|&(l, _)| l).fold(f32::MAX, f32::min);
+        assert!(mean.abs() < 0.02);
+        assert!(max > 0.2 && min < -0.2);
+    }
+
+    #[test]
+    fn test_wave_dac_controlled_by_nr30() {
+        let mut apu = powered_apu();
+
+        for i in 0..16 {
+            apu.write_register(0xFF30 + i, 0x00);
+        }
+        apu.write_register(0xFF1C, 0x20); // 100% volume
+        apu.write_register(0xFF1A, 0x80);
+        apu.write_register(0xFF1E, 0x80);
+        assert!(apu.channel3.dac_enabled());
+        assert_eq!(apu.channel3.output(), 1.0);
+
+        apu.write_register(0xFF1A, 0x00);
+        assert!(!apu.channel3.dac_enabled());
+        assert_eq!(apu.channel3.output(), 0.0);
+    }
+
+    #[test]
+    fn test_vin_routing() {
+        let mut apu = powered_apu();
+        apu.high_pass_enabled = false;
+        apu.vin = 1.0;
+
+        apu.write_register(0xFF24, 0x80 | 0x77); // VIN to the left side only
+        let samples = run_samples(&mut apu, 10_000);
+        let (l, r) = *samples.last().unwrap();
+        assert!((l - 0.25).abs() < 0.001);
+        assert_eq!(r, 0.0);
+    }
+
+    #[test]
+    fn test_length_enable_in_first_half_clocks_extra() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF11, 0x3E); // length 2
+        apu.write_register(0xFF14, 0x80); // trigger, length disabled
+
+        // Run past step 0 so the next step doesn't clock length
+        apu.clock_frame_sequencer();
+        assert!(apu.channel1.enabled);
+
+        // Enabling length now clocks it once: 2 -> 1, channel still on
+        apu.write_register(0xFF14, 0x40);
+        assert!(apu.channel1.enabled);
+
+        // Disabling and enabling again expires it
+        apu.write_register(0xFF14, 0x00);
+        apu.write_register(0xFF14, 0x40);
+        assert!(!apu.channel1.enabled);
+    }
+
+    #[test]
+    fn test_length_enable_in_second_half_no_extra_clock() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF11, 0x3F); // length 1
+        apu.write_register(0xFF14, 0x80);
+
+        // Next step is 0, which clocks length: no extra clock
+        apu.write_register(0xFF14, 0x40);
+        assert!(apu.channel1.enabled);
+    }
+
+    #[test]
+    fn test_zombie_mode_volume_write() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF11, 0xC0); // 75% duty
+        apu.write_register(0xFF12, 0x08); // volume 0, increase, pace 0
+        apu.write_register(0xFF13, 0xFF);
+        apu.write_register(0xFF14, 0x87); // trigger, fastest rate
+        apu.step(2); // move to a high duty step
+        assert_eq!(apu.channel1.digital_output(), 0);
+
+        // Pace 0 with the envelope still running: +1 per write
+        apu.write_register(0xFF12, 0x08);
+        apu.write_register(0xFF12, 0x08);
+        assert_eq!(apu.channel1.digital_output(), 2);
+
+        // Switching direction inverts the volume: 16 - (2 + 1)
+        apu.write_register(0xFF12, 0x11);
+        assert_eq!(apu.channel1.digital_output(), 13);
+    }
+
+    #[test]
+    fn test_sweep_negate_lockout() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF10, 0x19); // pace 1, subtract, step 1
+        apu.write_register(0xFF13, 0x00);
+        apu.write_register(0xFF14, 0x84); // trigger calculates in negate mode
+        assert!(apu.channel1.enabled);
+
+        apu.write_register(0xFF10, 0x11); // back to add mode
+        assert!(!apu.channel1.enabled);
+
+        // Without a negate calculation since the trigger nothing happens
+        apu.write_register(0xFF10, 0x10); // pace 1, add, step 0
+        apu.write_register(0xFF14, 0x84);
+        apu.write_register(0xFF10, 0x18);
+        apu.write_register(0xFF10, 0x10);
+        assert!(apu.channel1.enabled);
+    }
+
+    #[test]
+    fn test_wave_ram_blocked_while_playing() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        for i in 0..16 {
+            apu.write_register(0xFF30 + i, i as u8 * 0x11);
+        }
+        apu.write_register(0xFF1A, 0x80);
+        apu.write_register(0xFF1D, 0x00); // slowest rate
+        apu.write_register(0xFF1E, 0x80);
+        assert!(apu.channel3.enabled);
+
+        // Between fetches reads return 0xFF and writes are ignored
+        apu.step(1);
+        assert_eq!(apu.read_register(0xFF35), 0xFF);
+        apu.write_register(0xFF35, 0x00);
+
+        apu.write_register(0xFF1A, 0x00); // DAC off stops the channel
+        assert_eq!(apu.read_register(0xFF35), 0x55);
+    }
+
+    #[test]
+    fn test_power_off_keeps_length_counters() {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF11, 0xBF); // duty 2, length 1
+        apu.write_register(0xFF12, 0xF0);
+
+        apu.write_register(0xFF26, 0x00);
+        assert_eq!(apu.read_register(0xFF11), 0x3F); // duty cleared
+        assert_eq!(apu.read_register(0xFF12), 0x00);
+
+        // The length counter survived: enabling length and triggering
+        // expires it on the first length clock
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF14, 0xC0);
+        assert!(apu.channel1.enabled);
+        apu.clock_frame_sequencer();
+        assert!(!apu.channel1.enabled);
+    }
+
+    #[test]
+    fn test_mute_removes_channel_from_mix() {
+        let mut apu = powered_apu();
+        apu.high_pass_enabled = false;
+
+        // DAC on, silent: constant +1.0 from channel 1
+        apu.write_register(0xFF12, 0x08);
+        apu.write_register(0xFF14, 0x80);
+
+        apu.muted[0] = true;
+        let samples = run_samples(&mut apu, 10_000);
+        assert!(samples.iter().all(|&(l, r)| l == 0.0 && r == 0.0));
+
+        // Register state is untouched
+        assert!(apu.channel1.enabled);
+        assert_eq!(apu.read_register(0xFF26) & 0x01, 0x01);
+    }
+
+    #[test]
+    fn test_solo() {
+        let mut apu = APU::new();
+        apu.solo(2);
+        assert_eq!(apu.muted, [true, true, false, true]);
+        assert!(apu.is_soloed(2));
+        assert!(!apu.is_soloed(1));
+
+        apu.muted = [false; 4];
+        assert!(!apu.is_soloed(2));
+    }
+
+    #[test]
+    fn test_note_name() {
+        assert_eq!(note_name(440.0).as_deref(), Some("A4"));
+        assert_eq!(note_name(261.63).as_deref(), Some("C4"));
+        assert_eq!(note_name(64.0).as_deref(), Some("C2"));
+        assert_eq!(note_name(0.0), None);
+    }
+
+    #[test]
+    fn test_channel_info_and_scope() {
+        let mut apu = powered_apu();
+        apu.write_register(0xFF11, 0x80); // 50% duty
+        apu.write_register(0xFF12, 0xA3); // volume 10, down, pace 3
+        apu.write_register(0xFF13, 0xFF);
+        apu.write_register(0xFF14, 0x87); // 2047: 131072 Hz
+
+        let info = apu.channel_info(0);
+        assert!(info.enabled);
+        assert_eq!(info.frequency_hz, 131072.0);
+        assert_eq!(info.volume, 10);
+        assert_eq!(info.envelope, Some((false, 3)));
+        assert_eq!(info.duty, Some(2));
+
+        // Scope keeps recording muted channels
+        apu.muted[0] = true;
+        run_samples(&mut apu, 32 * SCOPE_LENGTH as u32);
+        let scope = apu.scope(0);
+        assert_eq!(scope.len(), SCOPE_LENGTH);
+        assert!(scope.iter().any(|&v| v != 0.0));
+        assert!(apu.scope(1).iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn test_stems_sum_to_mix() {
+        let mut apu = powered_apu();
+        apu.high_pass_enabled = false;
+        assert!(apu.take_stem_samples().is_none());
+        apu.set_stems_enabled(true);
+
+        apu.write_register(0xFF25, 0x21); // CH1 right, CH2 left
+        apu.write_register(0xFF11, 0x80);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF14, 0x86);
+        apu.write_register(0xFF16, 0x40);
+        apu.write_register(0xFF17, 0xA0);
+        apu.write_register(0xFF19, 0x85);
+
+        let mix = run_samples(&mut apu, 20_000);
+        let stems = apu.take_stem_samples().unwrap();
+        assert!(stems.iter().all(|stem| stem.len() == mix.len()));
+
+        // Panning applies to stems: CH1 only on the right
+        assert!(stems[0].iter().all(|&(l, _)| l.abs() < 1e-6));
+        assert!(stems[0].iter().any(|&(_, r)| r.abs() > 0.1));
+        assert!(stems[2].iter().all(|&(l, r)| l == 0.0 && r == 0.0));
+
+        for (i, &(l, r)) in mix.iter().enumerate() {
+            let sum_l: f32 = stems.iter().map(|stem| stem[i].0).sum();
+            let sum_r: f32 = stems.iter().map(|stem| stem[i].1).sum();
+            assert!((l - sum_l).abs() < 1e-3);
+            assert!((r - sum_r).abs() < 1e-3);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use emulator::audio_buffer::{RateControl, audio_ring};
+
+    #[test]
+    fn test_fifo_order() {
+        let (mut producer, mut consumer) = audio_ring(8);
+        assert!(consumer.is_empty());
+
+        assert_eq!(producer.push_slice(&[(0.1, -0.1), (0.2, -0.2)]), 2);
+        assert_eq!(producer.len(), 2);
+        assert_eq!(consumer.pop(), Some((0.1, -0.1)));
+        assert_eq!(consumer.pop(), Some((0.2, -0.2)));
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn test_full_ring_drops_excess() {
+        let (mut producer, mut consumer) = audio_ring(4);
+        let frames: Vec<(f32, f32)> = (0..6).map(|i| (i as f32, 0.0)).collect();
+
+        assert_eq!(producer.push_slice(&frames), 4);
+        assert_eq!(producer.capacity(), 4);
+        assert_eq!(producer.push_slice(&frames), 0);
+        assert_eq!(consumer.pop(), Some((0.0, 0.0)));
+        assert_eq!(producer.push_slice(&frames[4..]), 1);
+        assert_eq!(consumer.len(), 4);
+    }
+
+    #[test]
+    fn test_wraps_around() {
+        let (mut producer, mut consumer) = audio_ring(3);
+        for i in 0..20 {
+            let frame = (i as f32, -(i as f32));
+            assert_eq!(producer.push_slice(&[frame]), 1);
+            assert_eq!(consumer.pop(), Some(frame));
+        }
+    }
+
+    #[test]
+    fn test_producer_and_consumer_on_separate_threads() {
+        let (mut producer, mut consumer) = audio_ring(64);
+        let total = 10_000;
+
+        let reader = std::thread::spawn(move || {
+            let mut expected = 0;
+            while expected < total {
+                if let Some((left, right)) = consumer.pop() {
+                    assert_eq!(left, expected as f32);
+                    assert_eq!(right, -(expected as f32));
+                    expected += 1;
+                }
+            }
+        });
+
+        let mut next = 0;
+        while next < total {
+            let frame = (next as f32, -(next as f32));
+            next += producer.push_slice(&[frame]);
+        }
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_rate_control() {
+        let control = RateControl::new(48000, 2400);
+
+        assert_eq!(control.rate(2400), 48000);
+        // Starved: synthesize more samples per emulated second
+        assert_eq!(control.rate(0), 48240);
+        // Overfull: fewer, bounded to the same maximum deviation
+        assert_eq!(control.rate(4800), 47760);
+        assert_eq!(control.rate(100_000), 47760);
+        assert!(control.rate(1200) > 48000 && control.rate(1200) < 48240);
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use emulator::blip::BlipBuffer;
+
+    const CLOCK: u32 = 4_194_304;
+
+    #[test]
+    fn test_sample_count_follows_rate() {
+        let mut blip = BlipBuffer::new(CLOCK, 48000);
+
+        blip.advance(CLOCK / 8);
+        assert_eq!(blip.read_samples().len(), 6000);
+
+        blip.set_sample_rate(22050);
+        blip.advance(CLOCK / 2);
+        let count = blip.read_samples().len();
+        assert!((11024..=11025).contains(&count), "got {}", count);
+    }
+
+    #[test]
+    fn test_step_settles_to_delta() {
+        let mut blip = BlipBuffer::new(CLOCK, 44100);
+
+        blip.advance(1000);
+        blip.add_delta(0.5);
+        blip.advance(10000);
+
+        let samples = blip.read_samples();
+        assert!(samples[0].abs() < 1e-6);
+        assert!((samples.last().unwrap() - 0.5).abs() < 1e-4);
+
+        // The edge is smoothed over several samples instead of a single jump
+        let transition = samples.iter().filter(|s| **s > 0.01 && **s < 0.49).count();
+        assert!(
+            transition >= 2,
+            "only {} samples in the transition",
+            transition
+        );
+    }
+
+    #[test]
+    fn test_sub_sample_position_matters() {
+        // Same step, a fraction of a sample apart: point sampling would give
+        // identical output, band-limited synthesis does not
+        let render = |offset: u32| {
+            let mut blip = BlipBuffer::new(CLOCK, 44100);
+            blip.advance(950 + offset);
+            blip.add_delta(1.0);
+            blip.advance(2000);
+            blip.read_samples()
+        };
+
+        assert_ne!(render(0), render(40));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut blip = BlipBuffer::new(CLOCK, 44100);
+        blip.add_delta(1.0);
+        blip.advance(5000);
+        blip.clear();
+
+        blip.advance(5000);
+        assert!(blip.read_samples().iter().all(|s| *s == 0.0));
+    }
+}
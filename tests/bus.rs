@@ -0,0 +1,215 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        bus::Bus,
+        debugger::{WatchKind, Watchpoint},
+        gameboy::Gameboy,
+        memory_viewer::WriteHistory,
+        ppu::PPUMode,
+    };
+
+    fn advance_ppu(bus: &mut Bus, dots: u32) {
+        let mut remaining = dots;
+        while remaining > 0 {
+            let step = std::cmp::min(remaining, 4) as u8;
+            bus.ppu_step(step);
+            remaining -= step as u32;
+        }
+    }
+
+    #[test]
+    fn test_vram_locked_mid_instruction() {
+        let mut bus = Bus::new();
+        bus.memory.write_byte(0x8000, 0x12);
+        bus.memory.write_byte(0x8001, 0x34);
+
+        // 4 dots before the end of OAM scan
+        advance_ppu(&mut bus, 76);
+        assert_eq!(bus.ppu.get_mode(), PPUMode::OAMScan);
+
+        bus.begin_instruction();
+        assert_eq!(bus.read_byte(0x8000), 0x12);
+        // Second access lands in mode 3 although the PPU has not been stepped
+        assert_eq!(bus.read_byte(0x8001), 0xFF);
+        bus.end_instruction();
+    }
+
+    #[test]
+    fn test_oam_unlocked_mid_instruction() {
+        let mut bus = Bus::new();
+        bus.memory.write_byte(0xFE00, 0x56);
+
+        // Last dots of mode 3
+        advance_ppu(&mut bus, 80 + 168);
+        assert_eq!(bus.ppu.get_mode(), PPUMode::Drawing);
+
+        bus.begin_instruction();
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(bus.read_byte(0xFE00), 0x56);
+        bus.end_instruction();
+    }
+
+    #[test]
+    fn test_peek_has_no_side_effects() {
+        let mut bus = Bus::new();
+        bus.memory.write_byte(0x8000, 0x12);
+        bus.watchpoints
+            .push(Watchpoint::new(0x8000, 0x8000, WatchKind::Read));
+        advance_ppu(&mut bus, 76);
+
+        // Peeks neither hit watchpoints nor move the access timing on
+        bus.begin_instruction();
+        assert_eq!(bus.peek(0x8000), 0x12);
+        assert_eq!(bus.peek(0x8000), 0x12);
+        assert!(bus.take_watch_hit().is_none());
+        assert_eq!(bus.read_byte(0x8000), 0x12);
+        assert_eq!(bus.read_byte(0x8000), 0xFF);
+        assert_eq!(bus.peek(0x8000), 0x12);
+        bus.end_instruction();
+        assert!(bus.take_watch_hit().is_some());
+    }
+
+    #[test]
+    fn test_access_locking_can_be_disabled() {
+        let mut bus = Bus::new();
+        bus.access_locking = false;
+
+        advance_ppu(&mut bus, 100);
+        assert_eq!(bus.ppu.get_mode(), PPUMode::Drawing);
+
+        bus.write_byte(0x8000, 0x12);
+        bus.write_byte(0xFE00, 0x34);
+        assert_eq!(bus.read_byte(0x8000), 0x12);
+        assert_eq!(bus.read_byte(0xFE00), 0x34);
+    }
+
+    #[test]
+    fn test_locked_writes_are_not_highlighted() {
+        let mut bus = Bus::new();
+        bus.write_history = Some(WriteHistory::new());
+
+        advance_ppu(&mut bus, 100);
+        assert_eq!(bus.ppu.get_mode(), PPUMode::Drawing);
+
+        bus.write_byte(0x8000, 0x12);
+        bus.write_byte(0xFE00, 0x34);
+        bus.write_byte(0xC000, 0x56);
+        let history = bus.write_history.as_ref().unwrap();
+        assert_eq!(history.age(0x8000), None);
+        assert_eq!(history.age(0xFE00), None);
+        assert_eq!(history.age(0xC000), Some(0));
+    }
+
+    fn write_oam_row(gameboy: &mut Gameboy, row: u16, bytes: [u8; 8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            gameboy
+                .bus
+                .memory
+                .write_byte(0xFE00 + row * 8 + i as u16, *byte);
+        }
+    }
+
+    fn read_oam_row(gameboy: &Gameboy, row: u16) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = gameboy.bus.memory.read_oam(0xFE00 + row * 8 + i as u16);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_oam_bug_on_16bit_inc_during_oam_scan() {
+        let mut gameboy = Gameboy::new();
+        write_oam_row(
+            &mut gameboy,
+            0,
+            [0xAA, 0xAA, 0x01, 0x02, 0x66, 0x66, 0x03, 0x04],
+        );
+        write_oam_row(
+            &mut gameboy,
+            1,
+            [0xCC, 0xCC, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16],
+        );
+
+        // INC HL with HL in OAM; the IDU access happens on the second M-cycle,
+        // while the PPU reads row 1
+        gameboy.bus.write_byte(0x0100, 0x23);
+        gameboy.cpu.set_hl(0xFE40);
+        gameboy.step().unwrap();
+
+        assert_eq!(gameboy.cpu.hl(), 0xFE41);
+        assert_eq!(
+            read_oam_row(&gameboy, 1),
+            [0xEE, 0xEE, 0x01, 0x02, 0x66, 0x66, 0x03, 0x04]
+        );
+        assert_eq!(
+            read_oam_row(&gameboy, 0),
+            [0xAA, 0xAA, 0x01, 0x02, 0x66, 0x66, 0x03, 0x04]
+        );
+    }
+
+    #[test]
+    fn test_oam_bug_needs_mode_2_and_locking() {
+        let row1 = [0xCC, 0xCC, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16];
+
+        // Outside OAM scan
+        let mut gameboy = Gameboy::new();
+        write_oam_row(&mut gameboy, 1, row1);
+        advance_ppu(&mut gameboy.bus, 100);
+        gameboy.bus.write_byte(0x0100, 0x0B); // DEC BC
+        gameboy.cpu.set_bc(0xFE00);
+        gameboy.step().unwrap();
+        assert_eq!(read_oam_row(&gameboy, 1), row1);
+
+        // Locking disabled
+        let mut gameboy = Gameboy::new();
+        gameboy.bus.access_locking = false;
+        write_oam_row(&mut gameboy, 1, row1);
+        gameboy.bus.write_byte(0x0100, 0x0B);
+        gameboy.cpu.set_bc(0xFE00);
+        gameboy.step().unwrap();
+        assert_eq!(read_oam_row(&gameboy, 1), row1);
+
+        // Address outside OAM
+        let mut gameboy = Gameboy::new();
+        write_oam_row(&mut gameboy, 1, row1);
+        gameboy.bus.write_byte(0x0100, 0x0B);
+        gameboy.cpu.set_bc(0xC000);
+        gameboy.step().unwrap();
+        assert_eq!(read_oam_row(&gameboy, 1), row1);
+    }
+
+    #[test]
+    fn test_frame_sequencer_follows_div_bit_4() {
+        let mut bus = Bus::new();
+        bus.write_byte(0xFF26, 0x80);
+        assert_eq!(bus.apu.frame_sequencer_step(), 0);
+
+        // DIV bit 4 rises after 1024 M-cycles and falls after 2048
+        for _ in 0..2047 {
+            bus.timer_step(1);
+        }
+        assert_eq!(bus.apu.frame_sequencer_step(), 0);
+        bus.timer_step(1);
+        assert_eq!(bus.apu.frame_sequencer_step(), 1);
+    }
+
+    #[test]
+    fn test_div_write_clocks_frame_sequencer() {
+        let mut bus = Bus::new();
+        bus.write_byte(0xFF26, 0x80);
+
+        // Reset while bit 4 is low: no edge
+        bus.timer_step(100);
+        bus.write_byte(0xFF04, 0x00);
+        assert_eq!(bus.apu.frame_sequencer_step(), 0);
+
+        // Reset while bit 4 is high: falling edge
+        for _ in 0..5 {
+            bus.timer_step(255);
+        }
+        assert_ne!(bus.read_byte(0xFF04) & 0x10, 0);
+        bus.write_byte(0xFF04, 0x00);
+        assert_eq!(bus.apu.frame_sequencer_step(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        call_stack::{FrameKind, Function},
+        gameboy::Gameboy,
+    };
+
+    fn depth(gameboy: &Gameboy) -> usize {
+        gameboy.cpu.call_stack.depth()
+    }
+
+    #[test]
+    fn test_call_rst_and_ret() {
+        let mut gameboy = Gameboy::new();
+        // CALL $0200; RST $38; JR -2
+        load(&mut gameboy, 0x0100, &[0xCD, 0x00, 0x02, 0xFF, 0x18, 0xFE]);
+        // CALL $0300; RET
+        load(&mut gameboy, 0x0200, &[0xCD, 0x00, 0x03, 0xC9]);
+        // NOP; RET
+        load(&mut gameboy, 0x0300, &[0x00, 0xC9]);
+        load(&mut gameboy, 0x0038, &[0xC9]);
+
+        gameboy.step().unwrap();
+        let frame = *gameboy.cpu.call_stack.top().unwrap();
+        assert_eq!(frame.function, Function::new(0x0200, 1));
+        assert_eq!(frame.return_address, 0x0103);
+        assert_eq!(frame.sp, 0xFFFC);
+        assert_eq!(frame.kind, FrameKind::Call);
+
+        gameboy.step().unwrap();
+        gameboy.step().unwrap();
+        assert_eq!(depth(&gameboy), 2);
+        gameboy.step().unwrap();
+        assert_eq!(depth(&gameboy), 1);
+        gameboy.step().unwrap();
+        assert_eq!(depth(&gameboy), 0);
+        assert_eq!(gameboy.cpu.pc, 0x0103);
+
+        gameboy.step().unwrap();
+        let frame = *gameboy.cpu.call_stack.top().unwrap();
+        assert_eq!(frame.function.address, 0x0038);
+        assert_eq!(frame.kind, FrameKind::Rst);
+        gameboy.step().unwrap();
+        assert_eq!(depth(&gameboy), 0);
+        assert_eq!(gameboy.cpu.call_stack.entries(), 3);
+    }
+
+    #[test]
+    fn test_interrupt_and_reti() {
+        let mut gameboy = Gameboy::new();
+        load(&mut gameboy, 0x0040, &[0xD9]);
+
+        gameboy.cpu.interrupt(&mut gameboy.bus, 0x0040);
+        let frame = *gameboy.cpu.call_stack.top().unwrap();
+        assert_eq!(frame.kind, FrameKind::Interrupt);
+        assert_eq!(frame.return_address, 0x0100);
+
+        gameboy.step().unwrap();
+        assert_eq!(depth(&gameboy), 0);
+        assert_eq!(gameboy.cpu.pc, 0x0100);
+    }
+
+    #[test]
+    fn test_pushed_return_address_keeps_frame() {
+        let mut gameboy = Gameboy::new();
+        // LD HL,$0200; CALL $0300
+        load(&mut gameboy, 0x0100, &[0x21, 0x00, 0x02, 0xCD, 0x00, 0x03]);
+        load(&mut gameboy, 0x0200, &[0xC9]);
+        // PUSH HL; RET: jumps to $0200 inside the same call
+        load(&mut gameboy, 0x0300, &[0xE5, 0xC9]);
+
+        for _ in 0..4 {
+            gameboy.step().unwrap();
+        }
+        assert_eq!(gameboy.cpu.pc, 0x0200);
+        assert_eq!(depth(&gameboy), 1);
+
+        gameboy.step().unwrap();
+        assert_eq!(gameboy.cpu.pc, 0x0106);
+        assert_eq!(depth(&gameboy), 0);
+    }
+
+    #[test]
+    fn test_popped_return_address_drops_frame() {
+        let mut gameboy = Gameboy::new();
+        load(&mut gameboy, 0x0100, &[0xCD, 0x00, 0x02]);
+        load(&mut gameboy, 0x0200, &[0xCD, 0x00, 0x03]);
+        // POP HL; RET: returns from both calls at once
+        load(&mut gameboy, 0x0300, &[0xE1, 0xC9]);
+
+        for _ in 0..3 {
+            gameboy.step().unwrap();
+        }
+        assert_eq!(depth(&gameboy), 2);
+
+        gameboy.step().unwrap();
+        assert_eq!(gameboy.cpu.pc, 0x0103);
+        assert_eq!(depth(&gameboy), 0);
+    }
+}
@@ -0,0 +1,64 @@
+// Fixtures shared by the integration tests. Each test file is its own crate
+// and uses only some of them.
+#![allow(dead_code)]
+
+use emulator::{gameboy::Gameboy, memory::Mapper};
+
+// Writes `code` through the bus from `address` on
+pub fn load(gameboy: &mut Gameboy, address: u16, code: &[u8]) {
+    for (i, &byte) in code.iter().enumerate() {
+        gameboy.bus.write_byte(address + i as u16, byte);
+    }
+}
+
+// 0100: LD A,$01
+// 0102: CALL $0200
+// 0105: LD ($C000),A
+// 0108: INC A
+// 0109: JR $0108
+// 0200: LD B,$05
+// 0202: RET
+pub fn program() -> Gameboy {
+    let mut gameboy = Gameboy::new();
+    load(
+        &mut gameboy,
+        0x0100,
+        &[
+            0x3E, 0x01, 0xCD, 0x00, 0x02, 0xEA, 0x00, 0xC0, 0x3C, 0x18, 0xFD,
+        ],
+    );
+    load(&mut gameboy, 0x0200, &[0x06, 0x05, 0xC9]);
+    gameboy
+}
+
+// Maps `banks[n]` at 0x4000 when n is written to 0x2000-0x3FFF, bank 1 to
+// begin with
+#[derive(Debug, Clone)]
+pub struct TestMapper {
+    banks: Vec<Vec<u8>>,
+    bank: usize,
+}
+
+impl TestMapper {
+    pub fn new(banks: Vec<Vec<u8>>) -> Self {
+        Self { banks, bank: 1 }
+    }
+}
+
+impl Mapper for TestMapper {
+    fn write(&mut self, address: u16, value: u8) -> Option<&[u8]> {
+        if !(0x2000..=0x3FFF).contains(&address) {
+            return None;
+        }
+        self.bank = value as usize;
+        Some(&self.banks[self.bank])
+    }
+
+    fn rom_bank(&self) -> usize {
+        self.bank
+    }
+
+    fn clone_box(&self) -> Box<dyn Mapper> {
+        Box::new(self.clone())
+    }
+}
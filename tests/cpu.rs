@@ -511,6 +511,33 @@ mod tests {
         // TODO: test LD (HL) case in the future
     }
 
+    #[test]
+    fn test_hl_operand() {
+        let mut cpu = CPU::new();
+        let mut bus = Bus::new();
+        cpu.set_hl(0xC000);
+        cpu.b = 0x21;
+
+        // LD (HL),B
+        assert_eq!(cpu.execute_instruction(0x70, &mut bus), 8);
+        assert_eq!(bus.read_byte(0xC000), 0x21);
+
+        // LD C,(HL)
+        assert_eq!(cpu.execute_instruction(0x4E, &mut bus), 8);
+        assert_eq!(cpu.c, 0x21);
+
+        // ADD A,(HL)
+        cpu.a = 0x01;
+        assert_eq!(cpu.execute_instruction(0x86, &mut bus), 8);
+        assert_eq!(cpu.a, 0x22);
+
+        // SET 7,(HL)
+        cpu.pc = 0xC100;
+        bus.write_byte(0xC100, 0xFE);
+        assert_eq!(cpu.execute_instruction(0xCB, &mut bus), 16);
+        assert_eq!(bus.read_byte(0xC000), 0xA1);
+    }
+
     #[test]
     fn test_halt_instruction() {
         let mut cpu = CPU::new();
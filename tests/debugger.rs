@@ -0,0 +1,143 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::program;
+    use emulator::{
+        debugger::{
+            BreakReason, Comparison, Condition, Debugger, Register, WatchHit, WatchKind,
+            Watchpoint, parse_range,
+        },
+        gameboy::StopReason,
+    };
+
+    #[test]
+    fn test_breakpoint_stops_and_resumes() {
+        let mut gameboy = program();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x0105, None, None);
+
+        assert_eq!(
+            debugger.resume(&mut gameboy, 1000),
+            StopReason::Break(BreakReason::Breakpoint(0x0105))
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0105);
+        assert_eq!(gameboy.cpu.b, 0x05);
+
+        // Resuming runs the instruction under the breakpoint
+        assert_eq!(
+            debugger.resume(&mut gameboy, 10),
+            StopReason::Break(BreakReason::StepLimit)
+        );
+        assert_eq!(gameboy.bus.read_byte(0xC000), 0x01);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut gameboy = program();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x0108, None, Some(Condition::parse("A == $10").unwrap()));
+
+        assert_eq!(
+            debugger.resume(&mut gameboy, 1000),
+            StopReason::Break(BreakReason::Breakpoint(0x0108))
+        );
+        assert_eq!(gameboy.cpu.a, 0x10);
+    }
+
+    #[test]
+    fn test_write_watchpoint() {
+        let mut gameboy = program();
+        let mut debugger = Debugger::new();
+        gameboy
+            .bus
+            .watchpoints
+            .push(Watchpoint::new(0xC000, 0xC0FF, WatchKind::Write));
+
+        assert_eq!(
+            debugger.resume(&mut gameboy, 1000),
+            StopReason::Break(BreakReason::Watchpoint {
+                pc: 0x0105,
+                hit: WatchHit {
+                    address: 0xC000,
+                    value: 0x01,
+                    write: true,
+                },
+            })
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0108);
+
+        // Accesses made outside of instructions don't trigger it
+        gameboy.bus.write_byte(0xC001, 0x42);
+        assert_eq!(gameboy.bus.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut gameboy = program();
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            debugger.step_into(&mut gameboy),
+            StopReason::Break(BreakReason::Step)
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0102);
+        assert_eq!(
+            debugger.step_over(&mut gameboy),
+            StopReason::Break(BreakReason::Step)
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0105);
+        assert_eq!(gameboy.cpu.b, 0x05);
+
+        let mut gameboy = program();
+        debugger.step_into(&mut gameboy);
+        debugger.step_into(&mut gameboy);
+        assert_eq!(gameboy.cpu.pc, 0x0200);
+        assert_eq!(
+            debugger.step_out(&mut gameboy),
+            StopReason::Break(BreakReason::Step)
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0105);
+        assert_eq!(gameboy.cpu.sp, 0xFFFE);
+    }
+
+    #[test]
+    fn test_run_to_stops_on_breakpoint_first() {
+        let mut gameboy = program();
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.run_to(&mut gameboy, 0x0108),
+            StopReason::Break(BreakReason::Step)
+        );
+        assert_eq!(gameboy.cpu.pc, 0x0108);
+
+        let mut gameboy = program();
+        debugger.add_breakpoint(0x0200, None, None);
+        assert_eq!(
+            debugger.run_to(&mut gameboy, 0x0108),
+            StopReason::Break(BreakReason::Breakpoint(0x0200))
+        );
+    }
+
+    #[test]
+    fn test_parse_conditions_and_ranges() {
+        assert_eq!(
+            Condition::parse("hl >= 0xC000"),
+            Ok(Condition {
+                register: Register::HL,
+                comparison: Comparison::GreaterOrEqual,
+                value: 0xC000,
+            })
+        );
+        assert_eq!(
+            Condition::parse("B<#10").map(|c| c.comparison),
+            Ok(Comparison::Less)
+        );
+        assert!(Condition::parse("Q == 1").is_err());
+        assert!(Condition::parse("A 5").is_err());
+
+        assert_eq!(parse_range("$C000-$C0FF"), Ok((0xC000, 0xC0FF)));
+        assert_eq!(parse_range("FF40"), Ok((0xFF40, 0xFF40)));
+        assert!(parse_range("C0FF-C000").is_err());
+    }
+}
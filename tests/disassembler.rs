@@ -0,0 +1,83 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        disassembler::{Flow, decode, disassemble_around},
+        gameboy::Gameboy,
+    };
+
+    fn decode_bytes(bytes: &[u8], address: u16) -> (String, Flow, Option<u16>, u16) {
+        let read = |a: u16| {
+            bytes
+                .get(a.wrapping_sub(address) as usize)
+                .copied()
+                .unwrap_or(0)
+        };
+        let instruction = decode(&read, address);
+        let length = instruction.length();
+        (
+            instruction.text,
+            instruction.flow,
+            instruction.target,
+            length,
+        )
+    }
+
+    #[test]
+    fn test_decodes_rgbds_syntax() {
+        let cases: [(&[u8], &str); 10] = [
+            (&[0x00], "NOP"),
+            (&[0x3E, 0x10], "LD A,$10"),
+            (&[0x21, 0x34, 0x12], "LD HL,$1234"),
+            (&[0xEA, 0x00, 0xC0], "LD ($C000),A"),
+            (&[0xE0, 0x40], "LDH ($FF40),A"),
+            (&[0x22], "LD (HL+),A"),
+            (&[0xA8], "XOR B"),
+            (&[0xCB, 0x7C], "BIT 7,H"),
+            (&[0xF8, 0xFE], "LD HL,SP-2"),
+            (&[0xD3], "DB $D3"),
+        ];
+        for (bytes, text) in cases {
+            let (decoded, _, _, length) = decode_bytes(bytes, 0x0150);
+            assert_eq!(decoded, text);
+            assert_eq!(length as usize, bytes.len(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_control_flow_and_targets() {
+        assert_eq!(
+            decode_bytes(&[0xCD, 0x00, 0x02], 0x0100),
+            ("CALL $0200".to_string(), Flow::Call, Some(0x0200), 3)
+        );
+        // JR is relative to the next instruction
+        assert_eq!(
+            decode_bytes(&[0x20, 0xFE], 0x0100),
+            ("JR NZ,$0100".to_string(), Flow::Jump, Some(0x0100), 2)
+        );
+        assert_eq!(
+            decode_bytes(&[0xFF], 0x0100),
+            ("RST $38".to_string(), Flow::Call, Some(0x0038), 1)
+        );
+        assert_eq!(decode_bytes(&[0xD9], 0x0100).1, Flow::Return);
+        assert_eq!(decode_bytes(&[0xC8], 0x0100).1, Flow::Return);
+    }
+
+    #[test]
+    fn test_listing_around_pc_is_aligned() {
+        let mut gameboy = Gameboy::new();
+        // LD A,$01; LD HL,$C000; LD (HL),A; INC A; NOP
+        load(
+            &mut gameboy,
+            0x0100,
+            &[0x3E, 0x01, 0x21, 0x00, 0xC0, 0x77, 0x3C, 0x00],
+        );
+
+        let listing = disassemble_around(&gameboy.bus, 0x0105, 2, 1);
+        let addresses: Vec<u16> = listing.iter().map(|i| i.address).collect();
+        assert_eq!(addresses, vec![0x0100, 0x0102, 0x0105, 0x0106]);
+        assert_eq!(listing[2].text, "LD (HL),A");
+    }
+}
@@ -0,0 +1,186 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{bus::Bus, gameboy::Gameboy};
+
+    fn fill_source(bus: &mut Bus, base: u16) {
+        for i in 0..0xA0u16 {
+            bus.write_byte(base + i, i as u8);
+        }
+    }
+
+    #[test]
+    fn test_dma_register_reads_back_last_value() {
+        let mut bus = Bus::new();
+
+        assert_eq!(bus.read_byte(0xFF46), 0xFF);
+
+        bus.write_byte(0xFF46, 0xC1);
+        assert_eq!(bus.read_byte(0xFF46), 0xC1);
+    }
+
+    #[test]
+    fn test_dma_is_not_instantaneous() {
+        let mut bus = Bus::new();
+        fill_source(&mut bus, 0xC000);
+
+        bus.write_byte(0xFF46, 0xC0);
+        assert_eq!(bus.memory.read_oam(0xFE00), 0x00);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x00);
+
+        // Startup delay + first byte
+        bus.dma_step(8);
+        assert!(bus.dma.is_active());
+        assert_eq!(bus.memory.read_oam(0xFE01), 0x00);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x00);
+    }
+
+    #[test]
+    fn test_dma_takes_160_m_cycles() {
+        let mut bus = Bus::new();
+        fill_source(&mut bus, 0xC000);
+        bus.write_byte(0xC000, 0x42);
+
+        bus.write_byte(0xFF46, 0xC0);
+
+        // 1 M-cycle startup delay, then 159 bytes
+        for _ in 0..160 {
+            bus.dma_step(4);
+        }
+        assert!(bus.dma.is_active());
+        assert_eq!(bus.memory.read_oam(0xFE9E), 0x9E);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x00);
+
+        bus.dma_step(4);
+        assert!(!bus.dma.is_active());
+        assert_eq!(bus.memory.read_oam(0xFE00), 0x42);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x9F);
+    }
+
+    #[test]
+    fn test_cpu_restricted_to_hram_during_dma() {
+        let mut bus = Bus::new();
+        fill_source(&mut bus, 0xC000);
+        bus.write_byte(0xD000, 0x55);
+        bus.write_byte(0xFF80, 0x66);
+
+        bus.write_byte(0xFF46, 0xC0);
+        bus.dma_step(12);
+
+        // HRAM and I/O stay accessible
+        assert_eq!(bus.read_byte(0xFF80), 0x66);
+        bus.write_byte(0xFF81, 0x77);
+        assert_eq!(bus.read_byte(0xFF81), 0x77);
+        assert_eq!(bus.read_byte(0xFF46), 0xC0);
+
+        // OAM is owned by the DMA unit
+        assert_eq!(bus.read_byte(0xFE00), 0xFF);
+
+        // WRAM shares the bus with the source: the CPU sees the byte in flight
+        assert_eq!(bus.read_byte(0xD000), 0x01);
+
+        // Writes to the busy bus are dropped
+        bus.write_byte(0xD000, 0x99);
+
+        for _ in 0..160 {
+            bus.dma_step(4);
+        }
+        assert_eq!(bus.read_byte(0xD000), 0x55);
+        assert_eq!(bus.memory.read_oam(0xFE00), 0x00);
+    }
+
+    #[test]
+    fn test_vram_accessible_during_dma_from_wram() {
+        let mut bus = Bus::new();
+        bus.ppu.lcdc = 0x00; // LCD off, VRAM unlocked
+        bus.write_byte(0x8000, 0xAB);
+
+        bus.write_byte(0xFF46, 0xC0);
+        bus.dma_step(8);
+
+        assert_eq!(bus.read_byte(0x8000), 0xAB);
+    }
+
+    #[test]
+    fn test_dma_restart() {
+        let mut bus = Bus::new();
+        fill_source(&mut bus, 0xC000);
+        for i in 0..0xA0u16 {
+            bus.write_byte(0xC100 + i, 0xFF - i as u8);
+        }
+
+        bus.write_byte(0xFF46, 0xC0);
+        for _ in 0..11 {
+            bus.dma_step(4);
+        }
+
+        // Restart: the first transfer keeps running through the startup delay
+        bus.write_byte(0xFF46, 0xC1);
+        bus.dma_step(4);
+        assert!(bus.dma.is_active());
+        assert_eq!(bus.memory.read_oam(0xFE0A), 0x0A);
+
+        for _ in 0..160 {
+            bus.dma_step(4);
+        }
+        assert!(!bus.dma.is_active());
+        assert_eq!(bus.memory.read_oam(0xFE00), 0xFF);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x60);
+    }
+
+    #[test]
+    fn test_dma_source_above_dfff_mirrors_wram() {
+        let mut bus = Bus::new();
+        fill_source(&mut bus, 0xDE00);
+
+        bus.write_byte(0xFF46, 0xFE);
+        for _ in 0..161 {
+            bus.dma_step(4);
+        }
+
+        assert_eq!(bus.read_byte(0xFF46), 0xFE);
+        assert_eq!(bus.memory.read_oam(0xFE10), 0x10);
+        assert_eq!(bus.memory.read_oam(0xFE9F), 0x9F);
+    }
+
+    // LD A,($C000) run from HRAM: its read is the 4th M-cycle of the
+    // instruction
+    fn hram_load(gameboy: &mut Gameboy) {
+        fill_source(&mut gameboy.bus, 0xC000);
+        gameboy.bus.write_byte(0xC000, 0x42);
+        load(gameboy, 0xFF80, &[0xFA, 0x00, 0xC0]);
+        gameboy.cpu.pc = 0xFF80;
+    }
+
+    #[test]
+    fn test_dma_conflict_starts_mid_instruction() {
+        let mut gameboy = Gameboy::new();
+        hram_load(&mut gameboy);
+
+        // Still in its startup delay when the instruction begins
+        gameboy.bus.write_byte(0xFF46, 0xC0);
+        gameboy.step().unwrap();
+
+        // Two bytes copied by the read: the CPU gets the one on the bus
+        assert_eq!(gameboy.cpu.a, 0x01);
+    }
+
+    #[test]
+    fn test_dma_conflict_ends_mid_instruction() {
+        let mut gameboy = Gameboy::new();
+        hram_load(&mut gameboy);
+
+        gameboy.bus.write_byte(0xFF46, 0xC0);
+        for _ in 0..159 {
+            gameboy.bus.dma_step(4);
+        }
+        assert!(gameboy.bus.dma.is_active());
+        gameboy.step().unwrap();
+
+        // The last two bytes were copied before the read
+        assert!(!gameboy.bus.dma.is_active());
+        assert_eq!(gameboy.cpu.a, 0x42);
+    }
+}
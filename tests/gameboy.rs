@@ -0,0 +1,86 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        debugger::{BreakReason, Debugger},
+        gameboy::{Gameboy, StopReason},
+    };
+
+    #[test]
+    fn test_illegal_opcode_locks_up() {
+        let mut gameboy = Gameboy::new();
+        // NOP; illegal $D3
+        load(&mut gameboy, 0x0100, &[0x00, 0xD3]);
+
+        assert_eq!(gameboy.step(), Ok(false));
+        let lock_up = StopReason::LockUp {
+            pc: 0x0101,
+            opcode: 0xD3,
+        };
+        assert_eq!(gameboy.step(), Err(lock_up));
+        // The CPU stays on it
+        assert_eq!(gameboy.step(), Err(lock_up));
+        assert_eq!(gameboy.cpu.pc, 0x0101);
+        assert_eq!(gameboy.run_frame(), Err(lock_up));
+    }
+
+    #[test]
+    fn test_invalid_pc() {
+        let mut gameboy = Gameboy::new();
+        gameboy.cpu.pc = 0xFEA0;
+
+        assert_eq!(gameboy.step(), Err(StopReason::InvalidPc(0xFEA0)));
+        assert_eq!(
+            gameboy.step().unwrap_err().to_string(),
+            "PC in unusable memory: $FEA0"
+        );
+    }
+
+    #[test]
+    fn test_loop_detected_without_interrupts() {
+        let mut gameboy = Gameboy::new();
+        // DI; JR -2
+        load(&mut gameboy, 0x0100, &[0xF3, 0x18, 0xFE]);
+
+        assert_eq!(gameboy.run_frame(), Err(StopReason::LoopDetected(0x0101)));
+
+        // Reported again only after another full run of the loop
+        for _ in 0..999 {
+            gameboy.step().unwrap();
+        }
+        assert_eq!(gameboy.step(), Err(StopReason::LoopDetected(0x0101)));
+    }
+
+    #[test]
+    fn test_loop_waiting_for_interrupt() {
+        let mut gameboy = Gameboy::new();
+        // EI; JR -2
+        load(&mut gameboy, 0x0100, &[0xFB, 0x18, 0xFE]);
+        gameboy.bus.write_byte(0xFFFF, 0x04);
+
+        for _ in 0..2000 {
+            gameboy.step().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_debugger_reports_errors() {
+        let mut gameboy = Gameboy::new();
+        load(&mut gameboy, 0x0100, &[0x00, 0xDD]);
+        let mut debugger = Debugger::new();
+
+        assert_eq!(
+            debugger.step_into(&mut gameboy),
+            StopReason::Break(BreakReason::Step)
+        );
+        assert_eq!(
+            debugger.resume(&mut gameboy, 10),
+            StopReason::LockUp {
+                pc: 0x0101,
+                opcode: 0xDD
+            }
+        );
+    }
+}
@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        gameboy::Gameboy,
+        gbs::{GbsFile, GbsPlayer},
+    };
+
+    const LOAD: u16 = 0x0400;
+    const PLAY: u16 = 0x0410;
+
+    fn padded(text: &str) -> [u8; 32] {
+        let mut field = [0u8; 32];
+        field[..text.len()].copy_from_slice(text.as_bytes());
+        field
+    }
+
+    // INIT stores the song number at 0xC000, PLAY increments 0xC001
+    fn build_gbs(song_count: u8, timer_modulo: u8, timer_control: u8) -> Vec<u8> {
+        let mut data = b"GBS".to_vec();
+        data.push(1);
+        data.push(song_count);
+        data.push(1);
+        for address in [LOAD, LOAD, PLAY, 0xFFFE] {
+            data.extend_from_slice(&address.to_le_bytes());
+        }
+        data.push(timer_modulo);
+        data.push(timer_control);
+        data.extend_from_slice(&padded("Test Tune"));
+        data.extend_from_slice(&padded("Someone"));
+        data.extend_from_slice(&padded("2026 Nobody"));
+
+        let mut code = vec![0u8; 0x20];
+        code[0x00..0x04].copy_from_slice(&[0xEA, 0x00, 0xC0, 0xC9]); // LD (0xC000),A; RET
+        code[0x10..0x15].copy_from_slice(&[0x21, 0x01, 0xC0, 0x34, 0xC9]); // LD HL,0xC001; INC (HL); RET
+        data.extend_from_slice(&code);
+        data
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let file = GbsFile::parse(&build_gbs(5, 0, 0)).unwrap();
+        let header = &file.header;
+        assert_eq!(header.song_count, 5);
+        assert_eq!(header.first_song, 1);
+        assert_eq!(header.load_address, LOAD);
+        assert_eq!(header.init_address, LOAD);
+        assert_eq!(header.play_address, PLAY);
+        assert_eq!(header.stack_pointer, 0xFFFE);
+        assert_eq!(header.title, "Test Tune");
+        assert_eq!(header.author, "Someone");
+        assert_eq!(header.copyright, "2026 Nobody");
+
+        // Data lands at the load address, RST vectors jump relative to it
+        assert_eq!(file.rom[LOAD as usize], 0xEA);
+        assert_eq!(&file.rom[0x08..0x0B], &[0xC3, 0x08, 0x04]);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_files() {
+        assert!(GbsFile::parse(b"GBS").is_err());
+
+        let mut data = build_gbs(1, 0, 0);
+        data[0] = b'X';
+        assert!(GbsFile::parse(&data).is_err());
+
+        let mut data = build_gbs(1, 0, 0);
+        data[3] = 2;
+        assert!(GbsFile::parse(&data).is_err());
+
+        assert!(GbsFile::parse(&build_gbs(0, 0, 0)).is_err());
+    }
+
+    #[test]
+    fn test_play_period() {
+        let vblank = GbsFile::parse(&build_gbs(1, 0, 0)).unwrap();
+        assert_eq!(vblank.play_period(), 70224);
+
+        // Timer at 4096 Hz overflowing every 64 ticks: 64 Hz
+        let timer = GbsFile::parse(&build_gbs(1, 0xC0, 0x04)).unwrap();
+        assert_eq!(timer.play_period(), 64 * 1024);
+        assert!((timer.play_rate_hz() - 64.0).abs() < 0.01);
+
+        let double_speed = GbsFile::parse(&build_gbs(1, 0xC0, 0x84)).unwrap();
+        assert_eq!(double_speed.play_period(), 32 * 1024);
+    }
+
+    #[test]
+    fn test_init_receives_song_and_play_runs_each_frame() {
+        let mut player = GbsPlayer::new(GbsFile::parse(&build_gbs(3, 0, 0)).unwrap());
+        let mut gameboy = Gameboy::new();
+
+        player.start_song(&mut gameboy, 2).unwrap();
+        assert_eq!(player.song(), 2);
+        assert_eq!(gameboy.bus.read_byte(0xC000), 2);
+        assert_ne!(gameboy.bus.read_byte(0xFF26) & 0x80, 0);
+
+        for _ in 0..10 {
+            player.run_frame(&mut gameboy).unwrap();
+        }
+        assert_eq!(gameboy.bus.read_byte(0xC001), 10);
+        assert!(!gameboy.take_audio_samples().is_empty());
+
+        assert!(player.start_song(&mut gameboy, 3).is_err());
+    }
+
+    #[test]
+    fn test_timer_play_rate() {
+        // The timer vector calls play, the others lead into the driver
+        let file = GbsFile::parse(&build_gbs(1, 0xC0, 0x04)).unwrap();
+        assert_eq!(
+            file.rom[0x50..0x54],
+            [0xCD, PLAY as u8, (PLAY >> 8) as u8, 0xD9]
+        );
+        assert_eq!(file.rom[0x40..0x43], [0xC3, 0x40, (LOAD >> 8) as u8]);
+
+        // 0x10000 cycles between play calls, 4 calls in 4 frames
+        let mut player = GbsPlayer::new(file);
+        let mut gameboy = Gameboy::new();
+        player.start_song(&mut gameboy, 0).unwrap();
+
+        for _ in 0..4 {
+            player.run_frame(&mut gameboy).unwrap();
+        }
+        assert_eq!(gameboy.bus.read_byte(0xC001), 4);
+    }
+
+    #[test]
+    fn test_routine_that_never_returns() {
+        let mut data = build_gbs(1, 0, 0);
+        let play = 0x70 + (PLAY - LOAD) as usize;
+        data[play..play + 2].copy_from_slice(&[0x18, 0xFE]); // JR -2
+
+        let mut player = GbsPlayer::new(GbsFile::parse(&data).unwrap());
+        let mut gameboy = Gameboy::new();
+        player.start_song(&mut gameboy, 0).unwrap();
+        // Play is first called at the end of the first frame
+        let result = (0..2).try_for_each(|_| player.run_frame(&mut gameboy));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rom_bank_switching() {
+        // 4 banks of 16KB past the driver code, each filled with its own number
+        let mut data = build_gbs(1, 0, 0);
+        let rom_offset = LOAD as usize - 0x70;
+        for address in data.len() + rom_offset..0x10000 {
+            data.push((address / 0x4000) as u8);
+        }
+
+        let mut player = GbsPlayer::new(GbsFile::parse(&data).unwrap());
+        let mut gameboy = Gameboy::new();
+        player.start_song(&mut gameboy, 0).unwrap();
+        let bus = &mut gameboy.bus;
+        assert_eq!(bus.read_byte(LOAD), 0xEA);
+        assert_eq!(bus.read_byte(0x4000), 1);
+
+        bus.write_byte(0x2000, 3);
+        assert_eq!(bus.read_byte(0x4000), 3);
+        assert_eq!(bus.read_byte(0x7FFF), 3);
+        assert_eq!(bus.memory.rom_bank(), 3);
+        assert_eq!(bus.read_byte(LOAD), 0xEA);
+
+        // Bank 0 selects bank 1, writes outside 0x2000-0x3FFF don't touch ROM
+        bus.write_byte(0x2000, 0);
+        assert_eq!(bus.read_byte(0x4000), 1);
+        bus.write_byte(0x2000, 3);
+        // Numbers past the last bank wrap around, never onto bank 0
+        bus.write_byte(0x2000, 4);
+        assert_eq!(bus.read_byte(0x4000), 1);
+        bus.write_byte(0x4000, 0x55);
+        assert_eq!(bus.read_byte(0x4000), 1);
+    }
+}
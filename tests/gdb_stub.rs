@@ -0,0 +1,163 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::program;
+    use emulator::gdb_stub::{GdbServer, SessionEnd};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread::{self, JoinHandle};
+
+    struct Client {
+        stream: TcpStream,
+        server: JoinHandle<SessionEnd>,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let server = GdbServer::bind("127.0.0.1:0").unwrap();
+            let address = server.local_address();
+            let handle = thread::spawn(move || {
+                let mut gameboy = program();
+                server.accept().unwrap().serve(&mut gameboy).unwrap()
+            });
+            Self {
+                stream: TcpStream::connect(address).unwrap(),
+                server: handle,
+            }
+        }
+
+        fn read_byte(&mut self) -> u8 {
+            let mut byte = [0u8];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        fn send(&mut self, payload: &str) {
+            let sum = payload.bytes().fold(0u8, |s, b| s.wrapping_add(b));
+            write!(self.stream, "${}#{:02x}", payload, sum).unwrap();
+        }
+
+        // Sends a command and returns the reply payload
+        fn request(&mut self, payload: &str) -> String {
+            self.send(payload);
+            assert_eq!(self.read_byte(), b'+');
+            self.reply()
+        }
+
+        fn reply(&mut self) -> String {
+            assert_eq!(self.read_byte(), b'$');
+            let mut payload = Vec::new();
+            loop {
+                match self.read_byte() {
+                    b'#' => break,
+                    byte => payload.push(byte),
+                }
+            }
+            self.read_byte();
+            self.read_byte();
+            self.stream.write_all(b"+").unwrap();
+            String::from_utf8(payload).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_registers_and_memory() {
+        let mut client = Client::start();
+
+        assert_eq!(client.request("?"), "S05");
+        let registers = client.request("g");
+        assert_eq!(registers.len(), 24);
+        // PC is register 5, little-endian
+        assert_eq!(&registers[20..], "0001");
+
+        assert_eq!(client.request("m100,3"), "3e01cd");
+        assert_eq!(client.request("Mc000,2:abcd"), "OK");
+        assert_eq!(client.request("mc000,2"), "abcd");
+
+        assert_eq!(client.request("P5=0002"), "OK");
+        assert_eq!(client.request("p5"), "0002");
+        // AF = $FF5A, the low nibble of F doesn't exist
+        assert_eq!(client.request("P0=5aff"), "OK");
+        assert_eq!(client.request("p0"), "50ff");
+
+        // Non-hex data is refused, nothing gets written
+        assert_eq!(client.request("Mc000,2:a\u{e9}1"), "E01");
+        assert_eq!(client.request("Mc000,2:+1ff"), "E01");
+        assert_eq!(client.request("mc000,2"), "abcd");
+        let registers = format!("000\u{e9}{}", "0".repeat(19));
+        assert_eq!(client.request(&format!("G{}", registers)), "E01");
+        assert_eq!(client.request("P5=\u{e9}01"), "E01");
+        assert_eq!(client.request("p5"), "0002");
+
+        client.send("k");
+        assert_eq!(client.server.join().unwrap(), SessionEnd::Killed);
+    }
+
+    #[test]
+    fn test_breakpoint_step_and_continue() {
+        let mut client = Client::start();
+
+        assert_eq!(client.request("Z0,105,1"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.request("p5"), "0501");
+        // BC, B is the high byte
+        assert_eq!(&client.request("p1")[2..], "05");
+
+        assert_eq!(client.request("s"), "S05");
+        assert_eq!(client.request("p5"), "0801");
+
+        assert_eq!(client.request("z0,105,1"), "OK");
+        assert_eq!(client.request("Z2,c001,1"), "OK");
+        assert_eq!(client.request("Z3,c000,1"), "OK");
+        assert_eq!(client.request("Z4,c000,1"), "OK");
+        assert_eq!(client.request("D"), "OK");
+        assert_eq!(client.server.join().unwrap(), SessionEnd::Detached);
+    }
+
+    #[test]
+    fn test_watchpoint_stop_reply() {
+        let mut client = Client::start();
+
+        assert_eq!(client.request("Z2,c000,2"), "OK");
+        assert_eq!(client.request("c"), "T05watch:c000;");
+        assert_eq!(client.request("p5"), "0801");
+
+        client.send("k");
+        client.server.join().unwrap();
+    }
+
+    #[test]
+    fn test_interrupt_stops_continue() {
+        let mut client = Client::start();
+
+        client.send("c");
+        assert_eq!(client.read_byte(), b'+');
+        client.stream.write_all(&[0x03]).unwrap();
+        assert_eq!(client.reply(), "S02");
+
+        client.send("k");
+        client.server.join().unwrap();
+    }
+
+    #[test]
+    fn test_target_description_and_no_ack() {
+        let mut client = Client::start();
+
+        assert!(
+            client
+                .request("qSupported:xmlRegisters=i386")
+                .contains("qXfer:features:read+")
+        );
+        let xml = client.request("qXfer:features:read:target.xml:0,1000");
+        assert!(xml.starts_with("l<?xml"));
+        assert!(xml.contains("name=\"pc\""));
+
+        assert_eq!(client.request("QStartNoAckMode"), "OK");
+        client.send("m100,1");
+        assert_eq!(client.reply(), "3e");
+
+        client.send("k");
+        client.server.join().unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{headless::HeadlessOptions, postprocess::Filter, recorder::WavFormat};
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_no_args_starts_gui() {
+        assert_eq!(HeadlessOptions::parse(&[]), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_screenshot_options() {
+        let options = HeadlessOptions::parse(&args(&[
+            "--rom",
+            "game.gb",
+            "--frames",
+            "120",
+            "--screenshot",
+            "out.png",
+            "--filter",
+            "scale3x",
+            "--ghosting",
+            "0.5",
+            "--palette",
+            "Pocket",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.rom_path, "game.gb");
+        assert_eq!(options.frames, 120);
+        assert_eq!(options.screenshot.as_deref(), Some("out.png"));
+        assert_eq!(options.filter, Filter::Scale3x);
+        assert_eq!(options.ghosting, 0.5);
+        assert_eq!(options.palette.as_deref(), Some("Pocket"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(HeadlessOptions::parse(&args(&["--frames", "10"])).is_err());
+        assert!(HeadlessOptions::parse(&args(&["--screenshot"])).is_err());
+        assert!(
+            HeadlessOptions::parse(&args(&["--screenshot", "a.png", "--frames", "x"])).is_err()
+        );
+        assert!(
+            HeadlessOptions::parse(&args(&["--screenshot", "a.png", "--filter", "hq4x"])).is_err()
+        );
+        assert!(HeadlessOptions::parse(&args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_record_audio() {
+        let options = HeadlessOptions::parse(&args(&[
+            "--record-audio",
+            "out.wav",
+            "--frames",
+            "600",
+            "--audio-format",
+            "float",
+            "--stems",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.record_audio.as_deref(), Some("out.wav"));
+        assert_eq!(options.frames, 600);
+        assert_eq!(options.audio_format, WavFormat::Float32);
+        assert!(options.stems);
+        assert_eq!(options.screenshot, None);
+
+        assert!(
+            HeadlessOptions::parse(&args(&["--record-audio", "a.wav", "--audio-format", "ogg"]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_gbs() {
+        let options = HeadlessOptions::parse(&args(&[
+            "--gbs",
+            "music.gbs",
+            "--track",
+            "3",
+            "--record-audio",
+            "out.wav",
+        ]))
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.gbs.as_deref(), Some("music.gbs"));
+        assert_eq!(options.track, Some(3));
+
+        assert!(
+            HeadlessOptions::parse(&args(&["--gbs", "a.gbs", "--screenshot", "a.png"])).is_err()
+        );
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--gbs",
+                "a.gbs",
+                "--track",
+                "0",
+                "--record-audio",
+                "a.wav"
+            ]))
+            .is_err()
+        );
+        assert!(
+            HeadlessOptions::parse(&args(&["--track", "2", "--record-audio", "a.wav"])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_link() {
+        let options = HeadlessOptions::parse(&args(&[
+            "--screenshot",
+            "a.png",
+            "--link-listen",
+            "0.0.0.0:5000",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.link_listen.as_deref(), Some("0.0.0.0:5000"));
+        assert_eq!(options.link_connect, None);
+
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--screenshot",
+                "a.png",
+                "--link-listen",
+                "unix:/tmp/a.sock",
+                "--link-connect",
+                "unix:/tmp/a.sock",
+            ]))
+            .is_err()
+        );
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--gbs",
+                "a.gbs",
+                "--record-audio",
+                "a.wav",
+                "--link-connect",
+                "localhost:5000",
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_printer() {
+        let options = HeadlessOptions::parse(&args(&["--printer", "prints", "--frames", "600"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.printer.as_deref(), Some("prints"));
+
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--printer",
+                "prints",
+                "--link-connect",
+                "localhost:5000",
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_gdb() {
+        let options = HeadlessOptions::parse(&args(&["--gdb", "127.0.0.1:2345"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.gdb.as_deref(), Some("127.0.0.1:2345"));
+
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--gbs",
+                "a.gbs",
+                "--record-audio",
+                "a.wav",
+                "--gdb",
+                "127.0.0.1:2345",
+            ]))
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_trace() {
+        let options =
+            HeadlessOptions::parse(&args(&["--trace", "trace.txt", "--symbols", "game.sym"]))
+                .unwrap()
+                .unwrap();
+        assert_eq!(options.trace.as_deref(), Some("trace.txt"));
+        assert_eq!(options.symbols.as_deref(), Some("game.sym"));
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let options = HeadlessOptions::parse(&args(&[
+            "--screenshot",
+            "out.png",
+            "--log",
+            "warn,ppu=debug",
+            "--log-file",
+            "out.log",
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(options.log.as_deref(), Some("warn,ppu=debug"));
+        assert_eq!(options.log_file.as_deref(), Some("out.log"));
+
+        assert!(
+            HeadlessOptions::parse(&args(&["--screenshot", "out.png", "--log", "gpu=debug"]))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        let options = HeadlessOptions::parse(&args(&["--profile", "game.folded"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(options.profile.as_deref(), Some("game.folded"));
+
+        assert!(
+            HeadlessOptions::parse(&args(&[
+                "--gbs",
+                "a.gbs",
+                "--record-audio",
+                "a.wav",
+                "--profile",
+                "a.folded",
+            ]))
+            .is_err()
+        );
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        gameboy::Gameboy,
+        link::{self, link_cable},
+        serial::{Serial, SerialLink},
+    };
+
+    #[test]
+    fn test_cable_exchanges_bytes() {
+        let (mut a, mut b) = link_cable();
+
+        // Nobody waiting on the other end
+        assert_eq!(b.transfer(0xAA), 0xFF);
+
+        assert_eq!(a.receive(0x55), None);
+        assert_eq!(b.transfer(0xAA), 0x55);
+        assert_eq!(a.receive(0x55), Some(0xAA));
+
+        // The waiting side has completed, a new transfer finds nobody
+        assert_eq!(b.transfer(0xAA), 0xFF);
+    }
+
+    // LD A,byte; LDH (01),A; LD A,sc; LDH (02),A; loop: NOP; JR loop
+    fn send_program(byte: u8, sc: u8) -> [u8; 11] {
+        [
+            0x3E, byte, 0xE0, 0x01, 0x3E, sc, 0xE0, 0x02, 0x00, 0x18, 0xFD,
+        ]
+    }
+
+    #[test]
+    fn test_linked_gameboys_trade_a_byte() {
+        let mut slave = Gameboy::new();
+        let mut master = Gameboy::new();
+        load(&mut slave, 0x0100, &send_program(0x55, 0x80));
+        // The master starts a few NOPs later, once the slave listens
+        load(&mut master, 0x0108, &send_program(0xAA, 0x81));
+        // Only the serial interrupt may be flagged afterwards
+        slave.bus.write_byte(0xFF0F, 0x00);
+        master.bus.write_byte(0xFF0F, 0x00);
+        link::connect(&mut slave, &mut master);
+
+        link::run_frame(&mut slave, &mut master).unwrap();
+
+        assert_eq!(slave.bus.read_byte(0xFF01), 0xAA);
+        assert_eq!(master.bus.read_byte(0xFF01), 0x55);
+        assert_ne!(slave.bus.read_byte(0xFF0F) & 0x08, 0);
+        assert_ne!(master.bus.read_byte(0xFF0F) & 0x08, 0);
+    }
+
+    #[test]
+    fn test_cancelled_wait_is_not_delivered() {
+        let (a, mut b) = link_cable();
+        let mut slave = Serial::new();
+        slave.set_link(Box::new(a));
+        slave.write_register(0xFF01, 0x55);
+        slave.write_register(0xFF02, 0x80);
+        slave.step(1);
+
+        // The slave gives up before the master clocks
+        slave.write_register(0xFF02, 0x00);
+        assert_eq!(b.transfer(0xAA), 0xFF);
+        assert_eq!(slave.read_register(0xFF01), 0x55);
+    }
+
+    #[test]
+    fn test_linked_gameboys_stay_in_lockstep() {
+        let mut first = Gameboy::new();
+        let mut second = Gameboy::new();
+        link::connect(&mut first, &mut second);
+
+        for _ in 0..3 {
+            link::run_frame(&mut first, &mut second).unwrap();
+        }
+        let drift = first.total_cycles.abs_diff(second.total_cycles);
+        assert!(drift < 70224, "drift of {} cycles", drift);
+    }
+}
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        debug,
+        logger::{self, Level, Target},
+        trace, warn,
+    };
+
+    #[test]
+    fn test_parse_filter() {
+        assert_eq!(
+            logger::parse_filter("warn, ppu=trace,cpu=off").unwrap(),
+            vec![
+                (None, Level::Warn),
+                (Some(Target::Ppu), Level::Trace),
+                (Some(Target::Cpu), Level::Off),
+            ]
+        );
+        assert!(logger::parse_filter("").unwrap().is_empty());
+        assert!(logger::parse_filter("loud").is_err());
+        assert!(logger::parse_filter("gpu=debug").is_err());
+    }
+
+    // Levels and outputs are global, so everything touching them is in here
+    #[test]
+    fn test_levels_and_console() {
+        logger::log_to_stderr(false);
+        logger::capture_console(true);
+        logger::set_filter("info,ppu=debug").unwrap();
+
+        assert_eq!(logger::level(Target::Cpu), Level::Info);
+        assert_eq!(logger::level(Target::Ppu), Level::Debug);
+        assert!(logger::enabled(Target::Ppu, Level::Debug));
+        assert!(!logger::enabled(Target::Ppu, Level::Trace));
+        assert!(!logger::enabled(Target::Timer, Level::Debug));
+
+        let mut evaluated = 0;
+        let mut count = || {
+            evaluated += 1;
+            evaluated
+        };
+        debug!(Ppu, "LY={}", 144);
+        warn!(Apu, "channel {}", 3);
+        debug!(Cpu, "skipped {}", count());
+        trace!(Ppu, "skipped {}", count());
+        assert_eq!(evaluated, 0);
+
+        let lines: Vec<String> = logger::take_console_records()
+            .iter()
+            .map(|record| record.to_string())
+            .collect();
+        assert_eq!(lines, vec!["[DEBUG ppu] LY=144", "[WARN apu] channel 3"]);
+        assert!(logger::take_console_records().is_empty());
+
+        logger::capture_console(false);
+        debug!(Ppu, "not captured");
+        assert!(logger::take_console_records().is_empty());
+        logger::set_all_levels(Level::Warn);
+    }
+}
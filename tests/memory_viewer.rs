@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        bus::Bus,
+        memory::Mapper,
+        memory_viewer::{
+            Region, WriteHistory, decode_io, find_pattern, io_register_name, parse_pattern, region,
+        },
+    };
+
+    // Any write selects the bank written
+    #[derive(Debug, Clone)]
+    struct TestMapper {
+        bank: usize,
+        contents: [u8; 0x4000],
+    }
+
+    impl Mapper for TestMapper {
+        fn write(&mut self, _address: u16, value: u8) -> Option<&[u8]> {
+            self.bank = value as usize;
+            Some(&self.contents)
+        }
+
+        fn rom_bank(&self) -> usize {
+            self.bank
+        }
+
+        fn clone_box(&self) -> Box<dyn Mapper> {
+            Box::new(self.clone())
+        }
+    }
+
+    #[test]
+    fn test_regions_follow_rom_bank() {
+        let mut bus = Bus::new();
+        bus.memory.load_rom(&vec![0u8; 0x8000]).unwrap();
+        bus.memory.set_mapper(Box::new(TestMapper {
+            bank: 1,
+            contents: [0; 0x4000],
+        }));
+
+        assert_eq!(region(&bus.memory, 0x0150), Region::RomBank(0));
+        assert_eq!(region(&bus.memory, 0x4000), Region::RomBank(1));
+        bus.write_byte(0x2000, 3);
+        assert_eq!(region(&bus.memory, 0x7FFF).name(), "ROM bank 3");
+
+        assert_eq!(region(&bus.memory, 0x9800), Region::Vram);
+        assert_eq!(region(&bus.memory, 0xE000), Region::Echo);
+        assert_eq!(region(&bus.memory, 0xFEA0), Region::Unusable);
+        assert_eq!(region(&bus.memory, 0xFF80), Region::Hram);
+        assert_eq!(region(&bus.memory, 0xFFFF).short_name(), "IE");
+    }
+
+    #[test]
+    fn test_io_registers_are_decoded() {
+        assert_eq!(io_register_name(0xFF40), Some("LCDC"));
+        assert_eq!(io_register_name(0xFF35), Some("WAVE"));
+        assert_eq!(io_register_name(0xFF03), None);
+
+        assert_eq!(
+            decode_io(0xFF41, 0x47).as_deref(),
+            Some("mode 3 (drawing), LYC=LY on, interrupts: LYC")
+        );
+        assert_eq!(
+            decode_io(0xFF07, 0x05).as_deref(),
+            Some("timer on, 262144 Hz")
+        );
+        assert_eq!(
+            decode_io(0xFF12, 0xF3).as_deref(),
+            Some("volume 15, decrease, pace 3")
+        );
+        assert_eq!(
+            decode_io(0xFF00, 0xDE).as_deref(),
+            Some("select buttons, pressed: A")
+        );
+        assert!(decode_io(0xFF40, 0x91).unwrap().starts_with("LCD on"));
+        assert_eq!(decode_io(0xFF42, 0x10), None);
+    }
+
+    #[test]
+    fn test_pattern_search() {
+        assert_eq!(
+            parse_pattern("3E ?? cd"),
+            Ok(vec![Some(0x3E), None, Some(0xCD)])
+        );
+        assert!(parse_pattern("3E 1").is_err());
+        assert!(parse_pattern("XY").is_err());
+
+        let mut bus = Bus::new();
+        for (i, byte) in [0x3E, 0x42, 0xCD, 0x3E, 0x07, 0xCD].iter().enumerate() {
+            bus.write_byte(0xC100 + i as u16, *byte);
+        }
+        let pattern = parse_pattern("3E??CD").unwrap();
+        assert_eq!(find_pattern(&bus, &pattern, 0xC000), Some(0xC100));
+        assert_eq!(find_pattern(&bus, &pattern, 0xC101), Some(0xC103));
+        // Wraps around
+        assert_eq!(find_pattern(&bus, &pattern, 0xC104), Some(0xC100));
+    }
+
+    #[test]
+    fn test_write_history_ages() {
+        let mut bus = Bus::new();
+        bus.write_byte(0xC000, 1);
+        bus.write_history = Some(WriteHistory::new());
+        bus.write_byte(0xC001, 1);
+        bus.write_history.as_mut().unwrap().next_frame();
+        bus.write_history.as_mut().unwrap().next_frame();
+        bus.write_byte(0xC002, 1);
+
+        let history = bus.write_history.as_ref().unwrap();
+        assert_eq!(history.age(0xC000), None);
+        assert_eq!(history.age(0xC001), Some(2));
+        assert_eq!(history.age(0xC002), Some(0));
+    }
+}
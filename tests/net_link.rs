@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        net_link::{MAX_FRAME_LEAD, NetLink, NetListener},
+        serial::{Serial, SerialLink},
+    };
+    use std::time::{Duration, Instant};
+
+    fn connected_pair(address: &str) -> (NetLink, NetLink) {
+        let listener = NetListener::bind(address).unwrap();
+        let address = listener.local_address();
+        let host = std::thread::spawn(move || listener.accept().unwrap());
+        let guest = NetLink::connect(&address).unwrap();
+        (host.join().unwrap(), guest)
+    }
+
+    fn exchange(host: &mut NetLink, guest: &mut NetLink) {
+        // Nobody listening yet
+        assert_eq!(guest.transfer(0xAA), 0xFF);
+
+        assert_eq!(host.receive(0x55), None);
+        assert_eq!(guest.transfer(0xAA), 0x55);
+        assert_eq!(host.receive(0x55), Some(0xAA));
+
+        // Roles can swap from one byte to the next
+        assert_eq!(guest.receive(0x12), None);
+        assert_eq!(host.transfer(0x34), 0x12);
+        assert_eq!(guest.receive(0x12), Some(0x34));
+    }
+
+    #[test]
+    fn test_tcp_exchange() {
+        let (mut host, mut guest) = connected_pair("127.0.0.1:0");
+        exchange(&mut host, &mut guest);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_exchange() {
+        let path = std::env::temp_dir().join(format!("dmg-link-{}.sock", std::process::id()));
+        let (mut host, mut guest) = connected_pair(&format!("unix:{}", path.display()));
+        exchange(&mut host, &mut guest);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_cancelled_wait_is_not_delivered() {
+        let (host, mut guest) = connected_pair("127.0.0.1:0");
+        let mut slave = Serial::new();
+        slave.set_link(Box::new(host));
+        slave.write_register(0xFF01, 0x55);
+        slave.write_register(0xFF02, 0x80);
+        slave.step(1);
+
+        // The slave gives up before the master clocks
+        slave.write_register(0xFF02, 0x00);
+        assert_eq!(guest.transfer(0xAA), 0xFF);
+        assert_eq!(slave.read_register(0xFF01), 0x55);
+    }
+
+    #[test]
+    fn test_frame_sync_bounds_the_lead() {
+        let (host, guest) = connected_pair("127.0.0.1:0");
+        let mut host_sync = host.frame_sync();
+        let mut guest_sync = guest.frame_sync();
+
+        // Alternating frames never block
+        for _ in 0..10 {
+            host_sync.end_frame().unwrap();
+            guest_sync.end_frame().unwrap();
+        }
+
+        // The host may run MAX_FRAME_LEAD frames ahead, then waits; with
+        // the guest gone it gives up
+        for _ in 0..MAX_FRAME_LEAD {
+            host_sync.end_frame().unwrap();
+        }
+        drop(guest_sync);
+        drop(guest);
+        assert!(host_sync.end_frame().is_err());
+        assert!(!host.is_connected());
+    }
+
+    #[test]
+    fn test_background_accept() {
+        let listener = NetListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_address();
+        let pending = listener.accept_in_background().unwrap();
+        assert!(pending.poll().is_none());
+
+        let _guest = NetLink::connect(&address).unwrap();
+        let host = loop {
+            if let Some(result) = pending.poll() {
+                break result.unwrap();
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        };
+        assert!(host.is_connected());
+    }
+
+    #[test]
+    fn test_cancelled_accept_frees_the_address() {
+        let listener = NetListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_address();
+        drop(listener.accept_in_background().unwrap());
+        assert!(NetListener::bind(&address).is_ok());
+    }
+
+    #[test]
+    fn test_ready_never_blocks() {
+        let (host, guest) = connected_pair("127.0.0.1:0");
+        let mut host_sync = host.frame_sync();
+        let mut guest_sync = guest.frame_sync();
+
+        for _ in 0..=MAX_FRAME_LEAD {
+            assert!(host_sync.ready().unwrap());
+            host_sync.send_frame().unwrap();
+        }
+        assert!(!host_sync.ready().unwrap());
+
+        // Paused peers keep the link up, a frame from them unblocks us
+        guest_sync.set_paused(true);
+        assert!(!host_sync.ready().unwrap());
+        guest_sync.set_paused(false);
+        guest_sync.send_frame().unwrap();
+        let start = Instant::now();
+        while !host_sync.ready().unwrap() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        drop(guest_sync);
+        drop(guest);
+        while host_sync.ready().is_ok() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(test)]
+mod tests {
+    use emulator::palette::{Layer, LayerPalettes, Palette};
+
+    #[test]
+    fn test_presets() {
+        let presets = Palette::presets();
+
+        assert_eq!(presets[0], Palette::grey());
+        assert!(presets.iter().any(|p| p.name == "DMG Green"));
+        assert!(presets.iter().any(|p| p.name == "Pocket"));
+        assert!(presets.iter().any(|p| p.name == "Light"));
+        assert!(presets.iter().any(|p| p.name == "High Contrast"));
+    }
+
+    #[test]
+    fn test_rgba() {
+        let palette = Palette::classic_green();
+
+        assert_eq!(palette.rgba(0), [155, 188, 15, 255]);
+        assert_eq!(palette.rgba(3), [15, 56, 15, 255]);
+        assert_eq!(palette.rgba(4), [255, 0, 255, 255]); // invalid shade
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let text = "\
+# comment
+
+Mono = #FFFFFF #AAAAAA 555555 #000000
+  Blue=#E0F0FF #80A0C0 #405070 #102030
+";
+        let palettes = Palette::parse_config(text).unwrap();
+
+        assert_eq!(palettes.len(), 2);
+        assert_eq!(palettes[0].name, "Mono");
+        assert_eq!(palettes[0].colors[2], [0x55, 0x55, 0x55]);
+        assert_eq!(palettes[1].name, "Blue");
+        assert_eq!(palettes[1].colors[0], [0xE0, 0xF0, 0xFF]);
+    }
+
+    #[test]
+    fn test_parse_config_errors() {
+        assert!(Palette::parse_config("Broken #FFFFFF").is_err());
+        assert!(Palette::parse_config(" = #FFFFFF #AAAAAA #555555 #000000").is_err());
+        assert!(Palette::parse_config("Short = #FFFFFF #AAAAAA #555555").is_err());
+        assert!(Palette::parse_config("Bad = #FFFFFF #AAAAAA #555555 #GG0000").is_err());
+
+        let err =
+            Palette::parse_config("Ok = #FFFFFF #AAAAAA #555555 #000000\nBad = #FFF").unwrap_err();
+        assert!(err.starts_with("line 2"));
+    }
+
+    #[test]
+    fn test_layer_palettes() {
+        let mut palettes = LayerPalettes::uniform(&Palette::pocket());
+        palettes.obj1 = Palette::high_contrast();
+
+        assert_eq!(
+            palettes.rgba(0, Layer::Background),
+            Palette::pocket().rgba(0)
+        );
+        assert_eq!(palettes.rgba(0, Layer::Object0), Palette::pocket().rgba(0));
+        assert_eq!(palettes.rgba(0, Layer::Object1), [255, 255, 255, 255]);
+    }
+}
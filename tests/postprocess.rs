@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        palette::{LayerPalettes, Palette},
+        postprocess::{Filter, Frame, PostProcessor},
+        ppu::PPU,
+    };
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    fn solid(width: usize, height: usize, color: [u8; 4]) -> Frame {
+        let mut frame = Frame::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                frame.set_pixel(x, y, color);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_frame_from_ppu() {
+        let mut ppu = PPU::new();
+        ppu.framebuffer[0][0] = 3;
+        ppu.framebuffer[143][159] = 1;
+
+        let frame = Frame::from_ppu(&ppu, &LayerPalettes::uniform(&Palette::grey()));
+
+        assert_eq!((frame.width, frame.height), (160, 144));
+        assert_eq!(frame.pixel(0, 0), BLACK);
+        assert_eq!(frame.pixel(1, 0), WHITE);
+        assert_eq!(frame.pixel(159, 143), [170, 170, 170, 255]);
+    }
+
+    #[test]
+    fn test_ghosting_blends_previous_frame() {
+        let mut post = PostProcessor::new(0.5, Filter::None);
+
+        let first = post.process(&solid(2, 2, BLACK));
+        assert_eq!(first.pixel(0, 0), BLACK);
+
+        let second = post.process(&solid(2, 2, WHITE));
+        assert_eq!(second.pixel(1, 1), [128, 128, 128, 255]);
+
+        // Ghost fades over several frames
+        let third = post.process(&solid(2, 2, WHITE));
+        assert_eq!(third.pixel(1, 1), [192, 192, 192, 255]);
+
+        post.reset();
+        assert_eq!(post.process(&solid(2, 2, BLACK)).pixel(0, 0), BLACK);
+    }
+
+    #[test]
+    fn test_ghosting_disabled() {
+        let mut post = PostProcessor::new(0.0, Filter::None);
+        post.process(&solid(2, 2, BLACK));
+
+        assert_eq!(post.process(&solid(2, 2, WHITE)).pixel(0, 0), WHITE);
+    }
+
+    #[test]
+    fn test_dot_matrix_and_scanlines() {
+        let frame = solid(2, 2, WHITE);
+
+        let grid = Filter::DotMatrix.apply(&frame);
+        assert_eq!((grid.width, grid.height), (6, 6));
+        assert_eq!(grid.pixel(0, 0), WHITE);
+        assert_eq!(grid.pixel(2, 0), [178, 178, 178, 255]);
+        assert_eq!(grid.pixel(0, 2), [178, 178, 178, 255]);
+
+        let lines = Filter::Scanlines.apply(&frame);
+        assert_eq!((lines.width, lines.height), (6, 6));
+        assert_eq!(lines.pixel(2, 0), WHITE);
+        assert_eq!(lines.pixel(2, 2), [178, 178, 178, 255]);
+    }
+
+    #[test]
+    fn test_scale2x_smooths_diagonals() {
+        // B W
+        // W B
+        let mut frame = solid(2, 2, WHITE);
+        frame.set_pixel(0, 0, BLACK);
+        frame.set_pixel(1, 1, BLACK);
+
+        let output = Filter::Scale2x.apply(&frame);
+        assert_eq!((output.width, output.height), (4, 4));
+
+        // Top-right pixel: left and down neighbours are black, so the corner
+        // between them is filled in
+        assert_eq!(output.pixel(2, 0), WHITE);
+        assert_eq!(output.pixel(3, 0), WHITE);
+        assert_eq!(output.pixel(2, 1), BLACK);
+        assert_eq!(output.pixel(3, 1), WHITE);
+    }
+
+    #[test]
+    fn test_scale_filters_keep_flat_areas() {
+        let frame = solid(3, 3, WHITE);
+
+        for filter in [Filter::Scale2x, Filter::Scale3x] {
+            let output = filter.apply(&frame);
+            assert_eq!(output.width, 3 * filter.scale());
+            assert!(output.pixels.chunks(4).all(|p| p == WHITE));
+        }
+    }
+
+    #[test]
+    fn test_filter_from_name() {
+        assert_eq!(Filter::from_name("scale2x"), Some(Filter::Scale2x));
+        assert_eq!(Filter::from_name("dot-matrix"), Some(Filter::DotMatrix));
+        assert_eq!(Filter::from_name("Scanlines"), Some(Filter::Scanlines));
+        assert_eq!(Filter::from_name("hq4x"), None);
+    }
+
+    #[test]
+    fn test_save_png() {
+        let path = std::env::temp_dir().join("dmg_emu_test_screenshot.png");
+        let path = path.to_str().unwrap();
+
+        solid(4, 4, BLACK).save_png(path).unwrap();
+
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(&data[1..4], b"PNG");
+        std::fs::remove_file(path).unwrap();
+    }
+}
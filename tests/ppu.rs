@@ -2,6 +2,7 @@
 mod tests {
     use emulator::{
         memory::Memory,
+        palette::Layer,
         ppu::{PPU, PPUMode},
     };
 
@@ -264,6 +265,36 @@ mod tests {
         assert_eq!(ppu.read_register(0xFF41) & 0x03, 2); // OAMScan
     }
 
+    #[test]
+    fn test_layers_track_sprite_palette() {
+        let mut ppu = PPU::new();
+        let mut memory = Memory::new();
+
+        ppu.write_register(0xFF40, 0x93); // LCD + BG + OBJ, tiles at 0x8000
+        ppu.write_register(0xFF48, 0xE4);
+        ppu.write_register(0xFF49, 0xE4);
+
+        // Tile 1, first row fully set (color 3)
+        memory.write_byte(0x8010, 0xFF);
+        memory.write_byte(0x8011, 0xFF);
+
+        // Sprite 0 at (0, 0) using OBP0, sprite 1 at (8, 0) using OBP1
+        for (i, (x, attributes)) in [(8, 0x00), (16, 0x10)].iter().enumerate() {
+            let base = 0xFE00 + (i as u16 * 4);
+            memory.write_byte(base, 16);
+            memory.write_byte(base + 1, *x);
+            memory.write_byte(base + 2, 1);
+            memory.write_byte(base + 3, *attributes);
+        }
+
+        advance_ppu_lines(&mut ppu, &memory, 1);
+
+        assert_eq!(ppu.framebuffer[0][0], 3);
+        assert_eq!(ppu.layers[0][0], Layer::Object0);
+        assert_eq!(ppu.layers[0][8], Layer::Object1);
+        assert_eq!(ppu.layers[0][16], Layer::Background);
+    }
+
     #[test]
     fn test_not_implemented() {
         let mut ppu = PPU::new();
@@ -0,0 +1,144 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        printer::{PRINT_WIDTH, Printer, decompress, save_new_jobs},
+        serial::SerialLink,
+    };
+
+    const BAND_BYTES: usize = 640;
+
+    // Sends a whole packet, returns the printer's answers to the last two bytes
+    fn send_packet(printer: &mut Printer, command: u8, compression: u8, data: &[u8]) -> [u8; 2] {
+        let length = data.len() as u16;
+        let mut packet = vec![0x88, 0x33, command, compression];
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(data);
+        let checksum = packet[2..]
+            .iter()
+            .fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+        packet.extend_from_slice(&checksum.to_le_bytes());
+
+        for &byte in &packet {
+            assert_eq!(printer.transfer(byte), 0x00);
+        }
+        [printer.transfer(0x00), printer.transfer(0x00)]
+    }
+
+    fn print(printer: &mut Printer, margins: u8) -> [u8; 2] {
+        send_packet(printer, 0x02, 0, &[0x01, margins, 0xE4, 0x40])
+    }
+
+    #[test]
+    fn test_decompress() {
+        assert_eq!(decompress(&[0x02, 1, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(decompress(&[0x81, 7]), vec![7, 7, 7]);
+        assert_eq!(decompress(&[0x80, 9, 0x00, 4]), vec![9, 9, 4]);
+    }
+
+    #[test]
+    fn test_status_flow() {
+        let mut printer = Printer::new();
+        assert_eq!(send_packet(&mut printer, 0x01, 0, &[]), [0x81, 0x00]);
+
+        let [_, status] = send_packet(&mut printer, 0x04, 0, &[0u8; BAND_BYTES]);
+        assert_eq!(status & 0x08, 0x08); // unprocessed data
+
+        let [_, status] = print(&mut printer, 0x01);
+        assert_eq!(status & 0x0A, 0x02); // busy, data consumed
+
+        // Busy clears after a few status polls
+        let mut polls = 0;
+        while send_packet(&mut printer, 0x0F, 0, &[])[1] & 0x02 != 0 {
+            polls += 1;
+            assert!(polls < 100);
+        }
+    }
+
+    #[test]
+    fn test_checksum_error() {
+        let mut printer = Printer::new();
+        for byte in [0x88, 0x33, 0x04, 0x00, 0x01, 0x00, 0xAA, 0x00, 0x00] {
+            printer.transfer(byte);
+        }
+        assert_eq!(printer.transfer(0x00), 0x81);
+        assert_eq!(printer.transfer(0x00) & 0x09, 0x01);
+    }
+
+    #[test]
+    fn test_print_image_with_palette_and_margins() {
+        let mut printer = Printer::new();
+        let jobs = printer.jobs();
+
+        // Top tile row color 3, bottom tile row color 1
+        let mut band = vec![0xFF; BAND_BYTES / 2];
+        band.extend((0..BAND_BYTES / 2).map(|i| if i % 2 == 0 { 0xFF } else { 0x00 }));
+        send_packet(&mut printer, 0x01, 0, &[]);
+        send_packet(&mut printer, 0x04, 0, &band);
+        send_packet(&mut printer, 0x04, 0, &[]);
+
+        // No feed after: the job goes on
+        print(&mut printer, 0x00);
+        assert!(jobs.lock().unwrap().is_empty());
+
+        // Second band compressed: 640 bytes of 0x00
+        let mut compressed = vec![0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00];
+        compressed.extend_from_slice(&[0x80 | 122, 0x00]);
+        send_packet(&mut printer, 0x04, 0x01, &compressed);
+        print(&mut printer, 0x02);
+
+        let jobs = jobs.lock().unwrap();
+        assert_eq!(jobs.len(), 1);
+        let image = &jobs[0].image;
+        assert_eq!(image.width, PRINT_WIDTH);
+        assert_eq!(image.height, 16 + 16 + 2 * 16);
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(image.pixel(159, 8), [170, 170, 170, 255]);
+        assert_eq!(image.pixel(80, 20), [255, 255, 255, 255]);
+        assert_eq!(jobs[0].saved, None);
+    }
+
+    #[test]
+    fn test_top_margin_on_new_sheet() {
+        let mut printer = Printer::new();
+        let jobs = printer.jobs();
+
+        send_packet(&mut printer, 0x04, 0, &[0xFF; BAND_BYTES]);
+        print(&mut printer, 0x10);
+        assert!(jobs.lock().unwrap().is_empty());
+        print(&mut printer, 0x01);
+
+        let jobs = jobs.lock().unwrap();
+        let image = &jobs[0].image;
+        assert_eq!(image.height, 16 + 16 + 16);
+        assert_eq!(image.pixel(0, 15), [255, 255, 255, 255]);
+        assert_eq!(image.pixel(0, 16), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_print_saved_as_png() {
+        let dir = std::env::temp_dir().join(format!("dmg-printer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_name = dir.to_str().unwrap();
+        let mut printer = Printer::new();
+        let jobs = printer.jobs();
+
+        send_packet(&mut printer, 0x04, 0, &[0xFF; BAND_BYTES]);
+        print(&mut printer, 0x11);
+
+        let results = save_new_jobs(&jobs, dir_name);
+        assert_eq!(results.len(), 1);
+        let path = results[0].clone().unwrap();
+        assert!(std::path::Path::new(&path).exists());
+        assert_eq!(jobs.lock().unwrap()[0].saved, Some(Ok(path)));
+
+        // Each job is saved once, failures are kept in the job
+        assert!(save_new_jobs(&jobs, dir_name).is_empty());
+        send_packet(&mut printer, 0x04, 0, &[0xFF; BAND_BYTES]);
+        print(&mut printer, 0x11);
+        let missing = dir.join("missing");
+        let results = save_new_jobs(&jobs, missing.to_str().unwrap());
+        assert!(results[0].is_err());
+        assert!(jobs.lock().unwrap()[1].saved.as_ref().unwrap().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        call_stack::Function,
+        gameboy::Gameboy,
+        profiler::{Profiler, function_name},
+        symbols::SymbolTable,
+    };
+
+    // 0100: CALL $0200; CALL $0300; JR -2
+    // 0200: NOP; CALL $0300; RET
+    // 0300: NOP; RET
+    fn profiled_program() -> Gameboy {
+        let mut gameboy = Gameboy::new();
+        load(
+            &mut gameboy,
+            0x0100,
+            &[0xCD, 0x00, 0x02, 0xCD, 0x00, 0x03, 0x18, 0xFE],
+        );
+        load(&mut gameboy, 0x0200, &[0x00, 0xCD, 0x00, 0x03, 0xC9]);
+        load(&mut gameboy, 0x0300, &[0x00, 0xC9]);
+        gameboy.profiler = Some(Profiler::start(&gameboy.cpu.call_stack));
+        for _ in 0..10 {
+            gameboy.step().unwrap();
+        }
+        gameboy
+    }
+
+    #[test]
+    fn test_flat_profile() {
+        let gameboy = profiled_program();
+        let profile = gameboy.profiler.as_ref().unwrap();
+        assert_eq!(profile.total_cycles(), 144);
+
+        let flat = profile.flat();
+        let summary: Vec<_> = flat
+            .iter()
+            .map(|e| {
+                (
+                    e.function.map(|f| f.address),
+                    e.calls,
+                    e.self_cycles,
+                    e.total_cycles,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (None, 0, 60, 144),
+                (Some(0x0200), 1, 44, 64),
+                (Some(0x0300), 2, 40, 40),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_tree() {
+        let gameboy = profiled_program();
+        let profile = gameboy.profiler.as_ref().unwrap();
+
+        let children = profile.children(profile.root());
+        assert_eq!(children.len(), 2);
+        let (sub_a, entry) = children[0];
+        assert_eq!(entry.function, Some(Function::new(0x0200, 1)));
+        assert_eq!(entry.total_cycles, 64);
+
+        let nested = profile.children(sub_a);
+        assert_eq!(nested.len(), 1);
+        assert_eq!(nested[0].1.self_cycles, 20);
+        assert_eq!(nested[0].1.calls, 1);
+        assert_eq!(profile.entry(profile.root()).total_cycles, 144);
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let gameboy = profiled_program();
+        let symbols = SymbolTable::parse("00:0200 SubA\n00:0300 SubB").unwrap();
+
+        assert_eq!(
+            gameboy.profiler.as_ref().unwrap().folded_stacks(&symbols),
+            "(top level) 60\n\
+             (top level);SubA 44\n\
+             (top level);SubA;SubB 20\n\
+             (top level);SubB 20\n"
+        );
+    }
+
+    #[test]
+    fn test_recursion_counted_once() {
+        let mut gameboy = Gameboy::new();
+        // LD B,$02; CALL $0300; JR -2
+        load(
+            &mut gameboy,
+            0x0100,
+            &[0x06, 0x02, 0xCD, 0x00, 0x03, 0x18, 0xFE],
+        );
+        // DEC B; JR Z,+3; CALL $0300; RET
+        load(
+            &mut gameboy,
+            0x0300,
+            &[0x05, 0x28, 0x03, 0xCD, 0x00, 0x03, 0xC9],
+        );
+        gameboy.profiler = Some(Profiler::new());
+
+        while gameboy.cpu.pc != 0x0105 {
+            gameboy.step().unwrap();
+        }
+
+        let flat = gameboy.profiler.as_ref().unwrap().flat();
+        let entry = flat
+            .iter()
+            .find(|e| e.function.is_some_and(|f| f.address == 0x0300))
+            .unwrap();
+        assert_eq!(entry.calls, 2);
+        assert_eq!(entry.total_cycles, entry.self_cycles);
+    }
+
+    #[test]
+    fn test_function_name() {
+        let symbols = SymbolTable::parse("00:0200 Main").unwrap();
+        assert_eq!(
+            function_name(Some(Function::new(0x0200, 1)), &symbols),
+            "Main"
+        );
+        assert_eq!(
+            function_name(Some(Function::new(0x4000, 3)), &symbols),
+            "03:$4000"
+        );
+        assert_eq!(
+            function_name(Some(Function::new(0x0038, 3)), &symbols),
+            "$0038"
+        );
+        assert_eq!(function_name(None, &symbols), "(top level)");
+    }
+}
@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use emulator::recorder::{AudioRecorder, WavFormat, WavWriter, stem_paths};
+    use std::io::Cursor;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_pcm16_wav() {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), 44100, WavFormat::Pcm16).unwrap();
+        writer
+            .write_samples(&[(0.0, 1.0), (-1.0, 0.5), (2.0, -2.0)])
+            .unwrap();
+        assert_eq!(writer.frames(), 3);
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u16_at(&bytes, 20), 1); // PCM
+        assert_eq!(u16_at(&bytes, 22), 2); // stereo
+        assert_eq!(u32_at(&bytes, 24), 44100);
+        assert_eq!(u32_at(&bytes, 28), 44100 * 4);
+        assert_eq!(u16_at(&bytes, 34), 16);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 12);
+
+        let samples: Vec<i16> = bytes[44..]
+            .chunks(2)
+            .map(|c| i16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        assert_eq!(samples, vec![0, 32767, -32767, 16384, 32767, -32767]);
+    }
+
+    #[test]
+    fn test_float_wav() {
+        let mut writer =
+            WavWriter::new(Cursor::new(Vec::new()), 48000, WavFormat::Float32).unwrap();
+        writer.write_samples(&[(0.25, -0.5)]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(u32_at(&bytes, 16), 18);
+        assert_eq!(u16_at(&bytes, 20), 3); // IEEE float
+        assert_eq!(u16_at(&bytes, 34), 32);
+        assert_eq!(&bytes[38..42], b"fact");
+        assert_eq!(u32_at(&bytes, 46), 1);
+        assert_eq!(&bytes[50..54], b"data");
+        assert_eq!(u32_at(&bytes, 54), 8);
+        assert_eq!(f32::from_le_bytes(bytes[58..62].try_into().unwrap()), 0.25);
+        assert_eq!(f32::from_le_bytes(bytes[62..66].try_into().unwrap()), -0.5);
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(WavFormat::from_name("float"), Some(WavFormat::Float32));
+        assert_eq!(WavFormat::from_name("PCM16"), Some(WavFormat::Pcm16));
+        assert_eq!(WavFormat::from_name("mp3"), None);
+    }
+
+    #[test]
+    fn test_recorder_writes_stems() {
+        let dir = std::env::temp_dir().join(format!("dmg-emu-rec-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.wav").to_string_lossy().to_string();
+
+        let mut recorder = AudioRecorder::create(&path, 1000, WavFormat::Pcm16, true).unwrap();
+        recorder.write_samples(&[(0.0, 0.0); 500]).unwrap();
+        let stems: [Vec<(f32, f32)>; 4] = std::array::from_fn(|_| vec![(0.1, 0.1); 500]);
+        recorder.write_stems(&stems).unwrap();
+        assert_eq!(recorder.seconds(), 0.5);
+        recorder.finish().unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 44 + 500 * 4);
+        for stem in stem_paths(&path) {
+            assert!(stem.ends_with(".wav"));
+            assert_eq!(std::fs::metadata(&stem).unwrap().len(), 44 + 500 * 4);
+        }
+        assert!(stem_paths(&path)[2].ends_with("out.ch3.wav"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recorder_resamples_to_header_rate() {
+        let path = std::env::temp_dir()
+            .join(format!("dmg-emu-resample-{}.wav", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+
+        // Rate control running 0.5% fast for one second, in chunks
+        let mut recorder = AudioRecorder::create(&path, 1000, WavFormat::Float32, false).unwrap();
+        recorder.set_input_rate(1005);
+        let ramp: Vec<(f32, f32)> = (0..1005).map(|i| (i as f32, 0.0)).collect();
+        for chunk in ramp.chunks(201) {
+            recorder.write_samples(chunk).unwrap();
+        }
+        assert_eq!(recorder.seconds(), 1.0);
+        recorder.finish().unwrap();
+
+        // A ramp stays a ramp, stretched by the rate ratio
+        let data = std::fs::read(&path).unwrap();
+        let left = |frame: usize| {
+            let offset = data.len() - 1000 * 8 + frame * 8;
+            f32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+        };
+        assert_eq!(left(0), 0.0);
+        assert!((left(500) - 502.5).abs() < 0.01);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::load;
+    use emulator::{
+        gameboy::Gameboy,
+        serial::{CaptureLink, Loopback, Serial, SerialLink},
+    };
+
+    // Remote master that clocks one byte in once asked
+    #[derive(Debug)]
+    struct RemoteMaster {
+        byte: Option<u8>,
+    }
+
+    impl SerialLink for RemoteMaster {
+        fn transfer(&mut self, _outgoing: u8) -> u8 {
+            0xFF
+        }
+
+        fn receive(&mut self, _outgoing: u8) -> Option<u8> {
+            self.byte.take()
+        }
+    }
+
+    // Remote slave whose answer arrives once `reply` is filled in
+    #[derive(Debug)]
+    struct SlowRemote {
+        reply: std::sync::Arc<std::sync::Mutex<Option<u8>>>,
+    }
+
+    impl SerialLink for SlowRemote {
+        fn transfer(&mut self, _outgoing: u8) -> u8 {
+            0xFF
+        }
+
+        fn start_transfer(&mut self, _outgoing: u8) -> Option<u8> {
+            None
+        }
+
+        fn poll_transfer(&mut self) -> Option<u8> {
+            self.reply.lock().unwrap().take()
+        }
+    }
+
+    fn start_transfer(serial: &mut Serial, byte: u8) {
+        serial.write_register(0xFF01, byte);
+        serial.write_register(0xFF02, 0x81);
+    }
+
+    #[test]
+    fn test_disconnected_transfer_takes_8_bits() {
+        let mut serial = Serial::new();
+        start_transfer(&mut serial, 0x42);
+        assert_eq!(serial.read_register(0xFF02), 0xFF);
+
+        serial.step(255);
+        serial.step(255);
+        serial.step(255);
+        serial.step(255);
+        assert!(serial.transfer_requested());
+        assert!(!serial.take_interrupt());
+
+        serial.step(4);
+        assert!(!serial.transfer_requested());
+        assert!(serial.take_interrupt());
+        assert_eq!(serial.read_register(0xFF01), 0xFF);
+        assert_eq!(serial.read_register(0xFF02), 0x7F);
+    }
+
+    #[test]
+    fn test_bits_shift_in_during_transfer() {
+        let mut serial = Serial::new();
+        serial.set_link(Box::new(Loopback));
+        start_transfer(&mut serial, 0xA5);
+
+        serial.step(255);
+        serial.step(255);
+        serial.step(2);
+        assert_eq!(serial.read_register(0xFF01), 0x5A);
+
+        for _ in 0..4 {
+            serial.step(128);
+        }
+        assert_eq!(serial.read_register(0xFF01), 0xA5);
+        assert!(serial.take_interrupt());
+    }
+
+    #[test]
+    fn test_clock_waits_for_pending_reply() {
+        let reply = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let mut serial = Serial::new();
+        serial.set_link(Box::new(SlowRemote {
+            reply: reply.clone(),
+        }));
+        start_transfer(&mut serial, 0x12);
+
+        for _ in 0..16 {
+            serial.step(128);
+        }
+        assert!(serial.transfer_requested());
+        assert_eq!(serial.read_register(0xFF01), 0x12);
+
+        *reply.lock().unwrap() = Some(0xC3);
+        for _ in 0..8 {
+            serial.step(128);
+        }
+        assert!(serial.take_interrupt());
+        assert_eq!(serial.read_register(0xFF01), 0xC3);
+    }
+
+    #[test]
+    fn test_capture_link_records_output() {
+        let capture = CaptureLink::new();
+        let buffer = capture.buffer();
+        let mut serial = Serial::new();
+        serial.set_link(Box::new(capture));
+
+        for &byte in b"ok" {
+            start_transfer(&mut serial, byte);
+            for _ in 0..8 {
+                serial.step(128);
+            }
+        }
+        assert_eq!(buffer.lock().unwrap().as_slice(), b"ok");
+    }
+
+    #[test]
+    fn test_external_clock_waits_for_remote() {
+        let mut serial = Serial::new();
+        serial.write_register(0xFF01, 0x33);
+        serial.write_register(0xFF02, 0x80);
+        serial.step(255);
+        assert!(serial.transfer_requested());
+
+        serial.set_link(Box::new(RemoteMaster { byte: Some(0x99) }));
+        serial.step(1);
+        assert!(!serial.transfer_requested());
+        assert!(serial.take_interrupt());
+        assert_eq!(serial.read_register(0xFF01), 0x99);
+    }
+
+    #[test]
+    fn test_clone_keeps_cloneable_links() {
+        let mut serial = Serial::new();
+        serial.set_link(Box::new(Loopback));
+        let mut copy = serial.clone();
+        start_transfer(&mut copy, 0x5A);
+        for _ in 0..8 {
+            copy.step(128);
+        }
+        assert_eq!(copy.read_register(0xFF01), 0x5A);
+
+        // A peer that can't be duplicated leaves the copy unplugged
+        serial.set_link(Box::new(SlowRemote {
+            reply: std::sync::Arc::new(std::sync::Mutex::new(None)),
+        }));
+        let mut copy = serial.clone();
+        start_transfer(&mut copy, 0x5A);
+        for _ in 0..8 {
+            copy.step(128);
+        }
+        assert!(copy.take_interrupt());
+        assert_eq!(copy.read_register(0xFF01), 0xFF);
+    }
+
+    #[test]
+    fn test_serial_interrupt_flag() {
+        let mut gameboy = Gameboy::new();
+        let capture = CaptureLink::new();
+        let buffer = capture.buffer();
+        gameboy.bus.set_serial_link(Box::new(capture));
+
+        // LD A,'H'; LDH (01),A; LD A,0x81; LDH (02),A; loop: NOP; JR loop
+        load(
+            &mut gameboy,
+            0x0100,
+            &[
+                0x3E, 0x48, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x00, 0x18, 0xFD,
+            ],
+        );
+        gameboy.bus.write_byte(0xFF0F, 0x00);
+
+        for _ in 0..1000 {
+            gameboy.step().unwrap();
+        }
+        assert_eq!(buffer.lock().unwrap().as_slice(), b"H");
+        assert_ne!(gameboy.bus.read_byte(0xFF0F) & 0x08, 0);
+    }
+}
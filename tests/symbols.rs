@@ -0,0 +1,152 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{TestMapper, load, program};
+    use emulator::{
+        debugger::{BreakReason, Debugger},
+        disassembler::disassemble,
+        gameboy::{Gameboy, StopReason},
+        symbols::{SymbolTable, symbol_path},
+        trace::TraceLog,
+    };
+
+    const SYM: &str = "\
+; File generated by rgblink
+00:0100 Start
+00:0150 Main
+00:0150 Main.loop
+00:0200 InitVideo
+01:4000 Bank1Routine
+02:4000 Bank2Routine
+00:c000 wScore
+00:ff80 hFrameCounter
+";
+
+    #[test]
+    fn test_parse_and_lookup() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        assert_eq!(symbols.len(), 8);
+        assert_eq!(symbols.lookup("InitVideo"), Some((0, 0x0200)));
+        assert_eq!(symbols.lookup("Bank2Routine"), Some((2, 0x4000)));
+        // The global label is shown when a local one shares its address
+        assert_eq!(symbols.label_at(0x0150, 1), Some("Main"));
+
+        assert!(SymbolTable::parse("00:zz Bad").is_err());
+        assert!(SymbolTable::parse("0150 Main").is_err());
+    }
+
+    #[test]
+    fn test_banked_labels() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        assert_eq!(symbols.label_at(0x4000, 1), Some("Bank1Routine"));
+        assert_eq!(symbols.label_at(0x4000, 2), Some("Bank2Routine"));
+        assert_eq!(symbols.label_at(0x4000, 3), None);
+        assert_eq!(
+            symbols.describe(0x4010, 2).as_deref(),
+            Some("Bank2Routine+$10")
+        );
+    }
+
+    #[test]
+    fn test_nearest_label_stays_in_its_area() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        assert_eq!(symbols.describe(0x0155, 1).as_deref(), Some("Main+$5"));
+        assert_eq!(symbols.format_address(0x0200, 1), "$0200 (InitVideo)");
+        assert_eq!(symbols.describe(0xC002, 1).as_deref(), Some("wScore+$2"));
+        // ROM0 labels don't run on into the banked area or RAM
+        assert_eq!(symbols.describe(0x4000, 5), None);
+        assert_eq!(symbols.format_address(0x9800, 1), "$9800");
+        assert_eq!(
+            symbols.describe(0xFF81, 1).as_deref(),
+            Some("hFrameCounter+$1")
+        );
+    }
+
+    #[test]
+    fn test_parse_address_accepts_labels() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        assert_eq!(symbols.parse_address("Main"), Ok((None, 0x0150)));
+        assert_eq!(symbols.parse_address("$C000"), Ok((None, 0xC000)));
+        assert!(symbols.parse_address("Nowhere").is_err());
+
+        // Banks only matter in the switchable area
+        assert_eq!(symbols.parse_address("Bank2Routine"), Ok((Some(2), 0x4000)));
+        assert_eq!(symbols.parse_address("03:4010"), Ok((Some(3), 0x4010)));
+        assert_eq!(symbols.parse_address("01:C000"), Ok((None, 0xC000)));
+        assert!(symbols.parse_address("1FF:4000").is_err());
+
+        assert_eq!(symbol_path("roms/game.gb"), "roms/game.sym");
+        assert_eq!(symbol_path("roms.v2/game"), "roms.v2/game.sym");
+    }
+
+    #[test]
+    fn test_disassembly_uses_labels() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        let gameboy = program();
+
+        let call = disassemble(&gameboy.bus, 0x0102);
+        assert_eq!(call.text_with_symbols(&symbols, 1), "CALL InitVideo");
+        let store = disassemble(&gameboy.bus, 0x0105);
+        assert_eq!(store.text_with_symbols(&symbols, 1), "LD (wScore),A");
+        assert_eq!(
+            store.text_with_symbols(&SymbolTable::new(), 1),
+            "LD ($C000),A"
+        );
+    }
+
+    #[test]
+    fn test_trace_log_lines() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        let mut gameboy = program();
+        let mut trace = TraceLog::new(Vec::new());
+
+        for _ in 0..5 {
+            trace.log_instruction(&gameboy, &symbols).unwrap();
+            gameboy.step().unwrap();
+        }
+        assert_eq!(trace.lines(), 5);
+
+        let text = String::from_utf8(trace.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("00:0100 Start "));
+        assert!(lines[1].starts_with("00:0102 Start+$2 "));
+        assert!(lines[1].contains("CALL InitVideo"));
+        assert!(lines[2].starts_with("00:0200 InitVideo "));
+        assert!(lines[3].contains("RET"));
+        assert!(lines[4].contains("LD (wScore),A"));
+        assert!(lines[4].contains("SP=FFFE"));
+    }
+
+    #[test]
+    fn test_label_breakpoint_keeps_its_bank() {
+        let symbols = SymbolTable::parse(SYM).unwrap();
+        let mut gameboy = Gameboy::new();
+        // 0100: CALL $4000; LD A,$02; LD ($2000),A; CALL $4000; JR -2
+        load(
+            &mut gameboy,
+            0x0100,
+            &[
+                0xCD, 0x00, 0x40, 0x3E, 0x02, 0xEA, 0x00, 0x20, 0xCD, 0x00, 0x40, 0x18, 0xFE,
+            ],
+        );
+        // Every bank returns at once
+        load(&mut gameboy, 0x4000, &[0xC9]);
+        gameboy
+            .bus
+            .memory
+            .set_mapper(Box::new(TestMapper::new(vec![vec![0xC9; 0x4000]; 3])));
+
+        let (bank, address) = symbols.parse_address("Bank2Routine").unwrap();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(address, bank, None);
+
+        // Not hit by the call made with bank 1 mapped
+        assert_eq!(
+            debugger.resume(&mut gameboy, 1000),
+            StopReason::Break(BreakReason::Breakpoint(0x4000))
+        );
+        assert_eq!(gameboy.cpu.a, 0x02);
+        assert_eq!(gameboy.bus.memory.rom_bank(), 2);
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use emulator::timer::Timer;
+
+    // Timer at 262144 Hz: TIMA follows divider bit 3, every 4 M-cycles
+    fn fast_timer() -> Timer {
+        let mut timer = Timer::new();
+        timer.write_register(0xFF07, 0x05);
+        timer
+    }
+
+    #[test]
+    fn test_div_is_upper_byte_of_divider() {
+        let mut timer = Timer::new();
+        timer.step(63);
+        assert_eq!(timer.read_register(0xFF04), 0);
+        timer.step(1);
+        assert_eq!(timer.read_register(0xFF04), 1);
+        assert_eq!(timer.divider(), 0x100);
+
+        timer.write_register(0xFF04, 0x42);
+        assert_eq!(timer.divider(), 0);
+    }
+
+    #[test]
+    fn test_tima_increments_on_falling_edge() {
+        let mut timer = fast_timer();
+        timer.step(3);
+        assert_eq!(timer.read_register(0xFF05), 0);
+        timer.step(1);
+        assert_eq!(timer.read_register(0xFF05), 1);
+        timer.step(8);
+        assert_eq!(timer.read_register(0xFF05), 3);
+    }
+
+    #[test]
+    fn test_div_write_glitches_tima() {
+        let mut timer = fast_timer();
+        timer.step(2); // divider = 8, bit 3 high
+        timer.write_register(0xFF04, 0);
+        assert_eq!(timer.read_register(0xFF05), 1);
+
+        // With the bit low, no increment
+        timer.step(1);
+        timer.write_register(0xFF04, 0);
+        assert_eq!(timer.read_register(0xFF05), 1);
+    }
+
+    #[test]
+    fn test_tac_write_glitches_tima() {
+        let mut timer = fast_timer();
+        timer.step(2);
+        timer.write_register(0xFF07, 0x01); // disable while bit 3 is high
+        assert_eq!(timer.read_register(0xFF05), 1);
+
+        timer.write_register(0xFF07, 0x05);
+        timer.write_register(0xFF07, 0x04); // bit 9 is low
+        assert_eq!(timer.read_register(0xFF05), 2);
+    }
+
+    fn overflowed_timer() -> Timer {
+        let mut timer = fast_timer();
+        timer.write_register(0xFF06, 0xAB);
+        timer.write_register(0xFF05, 0xFF);
+        timer.step(4);
+        timer
+    }
+
+    #[test]
+    fn test_overflow_reload_is_delayed() {
+        let mut timer = overflowed_timer();
+        assert_eq!(timer.read_register(0xFF05), 0x00);
+        assert!(!timer.take_interrupt());
+
+        timer.step(1);
+        assert_eq!(timer.read_register(0xFF05), 0xAB);
+        assert!(timer.take_interrupt());
+    }
+
+    #[test]
+    fn test_tima_write_cancels_pending_reload() {
+        let mut timer = overflowed_timer();
+        timer.write_register(0xFF05, 0x12);
+        timer.step(1);
+        assert_eq!(timer.read_register(0xFF05), 0x12);
+        assert!(!timer.take_interrupt());
+    }
+
+    #[test]
+    fn test_writes_during_reload_cycle() {
+        let mut timer = overflowed_timer();
+        timer.step(1);
+
+        // TIMA writes are lost, TMA writes are copied to TIMA
+        timer.write_register(0xFF05, 0x12);
+        assert_eq!(timer.read_register(0xFF05), 0xAB);
+        timer.write_register(0xFF06, 0xCD);
+        assert_eq!(timer.read_register(0xFF05), 0xCD);
+
+        // Back to normal afterwards
+        timer.step(1);
+        timer.write_register(0xFF05, 0x12);
+        assert_eq!(timer.read_register(0xFF05), 0x12);
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use emulator::{
+        memory::Memory,
+        palette::Palette,
+        ppu::PPU,
+        vram_viewer::{self, TilePalette},
+    };
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 128, 255, 255];
+
+    // Tile whose first row is color 3 and the rest color 0
+    fn write_tile(memory: &mut Memory, address: u16) {
+        memory.write_byte(address, 0xFF);
+        memory.write_byte(address + 1, 0xFF);
+    }
+
+    #[test]
+    fn test_render_tiles_layout() {
+        let mut memory = Memory::new();
+        let ppu = PPU::new();
+        write_tile(&mut memory, 0x8000 + 17 * 16); // row 1, column 1
+
+        let frame = vram_viewer::render_tiles(&memory, &ppu, TilePalette::Raw, &Palette::grey());
+
+        assert_eq!((frame.width, frame.height), (128, 192));
+        assert_eq!(frame.pixel(8, 8), BLACK);
+        assert_eq!(frame.pixel(15, 8), BLACK);
+        assert_eq!(frame.pixel(8, 9), WHITE);
+        assert_eq!(frame.pixel(0, 0), WHITE);
+    }
+
+    #[test]
+    fn test_render_tiles_palette_register() {
+        let mut memory = Memory::new();
+        let mut ppu = PPU::new();
+        write_tile(&mut memory, 0x8000);
+        ppu.obp0 = 0x00; // every color id maps to shade 0
+
+        let raw = vram_viewer::render_tiles(&memory, &ppu, TilePalette::Raw, &Palette::grey());
+        let obp0 = vram_viewer::render_tiles(&memory, &ppu, TilePalette::Obp0, &Palette::grey());
+
+        assert_eq!(raw.pixel(0, 0), BLACK);
+        assert_eq!(obp0.pixel(0, 0), WHITE);
+    }
+
+    #[test]
+    fn test_render_tile_map_signed_addressing() {
+        let mut memory = Memory::new();
+        let mut ppu = PPU::new();
+        ppu.bgp = 0xE4;
+        ppu.lcdc = 0x81; // BG map 0x9800, tile data 0x8800 (signed)
+        ppu.scx = 100; // keep the viewport away from tile (1, 0)
+
+        // Tile id 0xFF = 0x9000 - 16
+        write_tile(&mut memory, 0x8FF0);
+        memory.write_byte(0x9801, 0xFF);
+
+        let frame = vram_viewer::render_tile_map(&memory, &ppu, 0x9800, &Palette::grey());
+
+        assert_eq!((frame.width, frame.height), (256, 256));
+        assert_eq!(frame.pixel(8, 0), BLACK);
+        assert_eq!(frame.pixel(8, 1), WHITE);
+    }
+
+    #[test]
+    fn test_render_tile_map_viewport_overlay() {
+        let memory = Memory::new();
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x91;
+        ppu.scx = 200;
+        ppu.scy = 10;
+
+        let bg = vram_viewer::render_tile_map(&memory, &ppu, 0x9800, &Palette::grey());
+        assert_eq!(bg.pixel(200, 10), RED);
+        assert_eq!(bg.pixel(200, 153), RED);
+        // Viewport wraps past the right edge: 200 + 159 - 256 = 103
+        assert_eq!(bg.pixel(103, 50), RED);
+        assert_eq!(bg.pixel(150, 50), WHITE);
+
+        // Not the BG map: no viewport
+        let other = vram_viewer::render_tile_map(&memory, &ppu, 0x9C00, &Palette::grey());
+        assert_eq!(other.pixel(200, 10), WHITE);
+    }
+
+    #[test]
+    fn test_render_tile_map_window_overlay() {
+        let memory = Memory::new();
+        let mut ppu = PPU::new();
+        ppu.lcdc = 0x91 | 0x20 | 0x40; // window on, window map 0x9C00
+        ppu.wx = 87; // screen x 80
+        ppu.wy = 100;
+
+        let window = vram_viewer::render_tile_map(&memory, &ppu, 0x9C00, &Palette::grey());
+        assert_eq!(window.pixel(0, 0), BLUE);
+        assert_eq!(window.pixel(79, 43), BLUE);
+        assert_eq!(window.pixel(80, 43), WHITE);
+    }
+
+    #[test]
+    fn test_read_oam_entries() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xFE04, 16);
+        memory.write_byte(0xFE05, 8);
+        memory.write_byte(0xFE06, 0x42);
+        memory.write_byte(0xFE07, 0xF0);
+
+        let entries = vram_viewer::read_oam_entries(&memory);
+
+        assert_eq!(entries.len(), 40);
+        let entry = entries[1];
+        assert_eq!(
+            (entry.index, entry.y, entry.x, entry.tile),
+            (1, 16, 8, 0x42)
+        );
+        assert!(entry.behind_bg() && entry.y_flip() && entry.x_flip() && entry.uses_obp1());
+        assert!(entry.is_visible());
+        assert!(!entries[0].is_visible());
+    }
+
+    #[test]
+    fn test_render_sprite_flips_and_tall_mode() {
+        let mut memory = Memory::new();
+        let mut ppu = PPU::new();
+        ppu.obp0 = 0xE4;
+
+        // Tile 2: leftmost pixel of the first row set
+        memory.write_byte(0x8020, 0x80);
+        memory.write_byte(0x8021, 0x80);
+
+        memory.write_byte(0xFE02, 2);
+        memory.write_byte(0xFE03, 0x60); // X + Y flip
+        let entry = vram_viewer::read_oam_entries(&memory)[0];
+
+        let sprite = vram_viewer::render_sprite(&memory, &ppu, &entry, &Palette::grey());
+        assert_eq!((sprite.width, sprite.height), (8, 8));
+        assert_eq!(sprite.pixel(7, 7), BLACK);
+        assert_eq!(sprite.pixel(0, 0)[3], 0); // color 0 is transparent
+
+        ppu.lcdc |= 0x04; // 8x16
+        let tall = vram_viewer::render_sprite(&memory, &ppu, &entry, &Palette::grey());
+        assert_eq!(tall.height, 16);
+        assert_eq!(tall.pixel(7, 15), BLACK);
+    }
+}